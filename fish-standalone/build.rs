@@ -0,0 +1,7 @@
+extern crate fish_in_a_jit as fj;
+
+fn main() {
+    // Compile the YAML to bytecode in $OUT_DIR, path is relative to this
+    // package's root. Errors in the YAML fail the build.
+    fj::embed::compile_dmo("../examples/fish-demo.yml");
+}
@@ -0,0 +1,28 @@
+#[macro_use]
+extern crate fish_in_a_jit as fj;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use fj::dmo::Dmo;
+use fj::bytecode::Bytecode;
+
+pub fn main() {
+    // Compiled from examples/fish-demo.yml by build.rs, included at compile
+    // time.
+    let bytecode: &'static [u8] = include_dmo!("fish-demo.dmo");
+
+    // Decode straight from the static slice, without copying it to a Vec.
+    let mut dmo = Dmo::read_bytecode(&mut &bytecode[..]).unwrap();
+    dmo.build_jit_fn().unwrap();
+
+    print!("\n");
+
+    while dmo.get_is_running() {
+        dmo.run_jit_fn();
+        sleep(Duration::from_millis(10));
+        dmo.add_to_time(0.01);
+    }
+
+    print!("\n");
+}
@@ -0,0 +1,56 @@
+extern crate fish_in_a_jit as fj;
+
+use std::time::Instant;
+
+use fj::dmo::Dmo;
+
+/// Runs each effect on a large canvas, with the JIT fn and with the
+/// interpreter, and prints the time per frame.
+fn main() {
+    let effects = [
+        ("Plasma", "Plasma: [ 0.2, 1.5 ]"),
+        ("Fire", "Fire: 6"),
+        ("Starfield", "Starfield: [ 500, 0.3 ]"),
+        ("SineScroll", "SineScroll: [ 0, 40.0, 20.0, 0.1 ]"),
+        ("Shade", "Shade: 0"),
+    ];
+    let n_frames = 200;
+
+    for &(name, op) in effects.iter() {
+        let text = format!(r#"
+operators:
+  - {}
+
+context:
+  width: 240
+  height: 80
+  shaders:
+    - name: "waves"
+      fill: "ramp(sin(x*0.1 + t) * cos(y*0.2 - t) + mod(x + y + t*10, 8) / 8 - 0.5)"
+  sprites:
+    - "  FISH IN A JIT  "
+"#, op);
+
+        let jit = ms_per_frame(&text, n_frames, true);
+        let interpreter = ms_per_frame(&text, n_frames, false);
+        println!("{:12} JIT: {:8.3} ms/frame   interpreter: {:8.3} ms/frame", name, jit, interpreter);
+    }
+}
+
+fn ms_per_frame(text: &str, n_frames: u32, jit: bool) -> f64 {
+    let mut dmo = Dmo::new_from_yml_str(text).unwrap();
+    dmo.build_jit_fn().unwrap();
+
+    let start = Instant::now();
+    for _ in 0 .. n_frames {
+        if jit {
+            dmo.run_jit_fn();
+        } else {
+            dmo.run_operators();
+        }
+        dmo.add_to_time(0.02);
+    }
+    let elapsed = start.elapsed();
+
+    (elapsed.as_secs() as f64 * 1000.0 + elapsed.subsec_nanos() as f64 / 1_000_000.0) / n_frames as f64
+}
@@ -0,0 +1,50 @@
+extern crate fish_in_a_jit as fj;
+
+use std::env;
+use std::process;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use fj::dmo::Dmo;
+
+/// Decompiles a `.dmo` bytecode blob to YAML. Writes to stdout, or to the file
+/// given as the second argument.
+///
+/// ```text
+/// cargo run --bin dmo_to_yml -- examples/fish-demo.dmo fish-demo.yml
+/// ```
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 {
+        eprintln!("Usage: dmo_to_yml <input.dmo> [output.yml]");
+        process::exit(2);
+    }
+
+    let dmo = match Dmo::new_from_blob(&PathBuf::from(&args[1])) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Failed to decode {}: {}", args[1], e);
+            process::exit(1);
+        },
+    };
+
+    let text = match dmo.to_yml_string() {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Failed to decompile {}: {}", args[1], e);
+            process::exit(1);
+        },
+    };
+
+    if args.len() > 2 {
+        let written = File::create(&args[2]).and_then(|mut f| f.write_all(text.as_bytes()));
+        if let Err(e) = written {
+            eprintln!("Failed to write {}: {}", args[2], e);
+            process::exit(1);
+        }
+    } else {
+        println!("{}", text);
+    }
+}
@@ -6,7 +6,7 @@ use fj::bytecode::Bytecode;
 fn main() {
     let text = r#"
 operators:
-  - Draw: [ 0, 2, 1.5 ]
+  - Draw: [ 0, 2, 0, 1.5, Right, Wrap ]
   - Print
 
 context:
@@ -17,7 +17,7 @@ context:
     let d = Dmo::new_from_yml_str(&text).unwrap();
     let bytecode = d.to_bytecode();
     let mut dmo = Dmo::from_bytecode(bytecode);
-    dmo.build_jit_fn();
+    dmo.build_jit_fn().unwrap();
 
     print!("\n");
     dmo.run_jit_fn();
@@ -0,0 +1,109 @@
+extern crate fish_in_a_jit as fj;
+
+use std::env;
+use std::process;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+use fj::image::{Conversion, read_image, image_to_sprite, sprite_yml_snippet};
+
+const USAGE: &'static str = "\
+Usage: img_to_sprite [options] <image> [output.yml]
+
+Options:
+  --name NAME         name of the sprite, default: the file name
+  --columns N         width in chars, default: one char per pixel
+  --aspect A          width of a char divided by its height, default: 0.5
+  --ramp CHARS        chars from dark to light
+  --invert            ramp from light to dark, for a light background
+  --color             add a color map with the 16 terminal colors
+  --transparent CHAR  transparent char, for the transparent pixels";
+
+/// Converts a PNG, PPM or PGM image to a sprite, and writes it as a `sprites`
+/// map of the YAML. Writes to stdout, or to the file given after the image.
+///
+/// ```text
+/// cargo run --bin img_to_sprite -- --columns 40 --color fish.png fish.yml
+/// ```
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let mut conversion = Conversion::default();
+    let mut name: Option<String> = None;
+    let mut paths: Vec<String> = vec![];
+
+    let mut i = 0;
+    while i < args.len() {
+        let value = args.get(i + 1).cloned().unwrap_or(String::new());
+        match args[i].as_str() {
+            "--name" => name = Some(value),
+            "--columns" => conversion.columns = parse_or_exit(&value, "--columns"),
+            "--aspect" => conversion.aspect = parse_or_exit(&value, "--aspect"),
+            "--ramp" => conversion.ramp = value,
+            "--transparent" => conversion.transparent = Some(parse_or_exit(&value, "--transparent")),
+            "--invert" => {
+                conversion.invert = true;
+                i += 1;
+                continue;
+            },
+            "--color" => {
+                conversion.color = true;
+                i += 1;
+                continue;
+            },
+            arg => {
+                paths.push(String::from(arg));
+                i += 1;
+                continue;
+            },
+        }
+        i += 2;
+    }
+
+    if paths.is_empty() || paths.len() > 2 {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    let path = PathBuf::from(&paths[0]);
+    let image = match read_image(&path) {
+        Ok(image) => image,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", paths[0], e);
+            process::exit(1);
+        },
+    };
+
+    let name = name.unwrap_or_else(|| {
+        path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or(String::from("sprite"))
+    });
+    let sprite = image_to_sprite(&image, &conversion);
+    let text = match sprite_yml_snippet(&name, &sprite) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Failed to write the sprite as YAML: {}", e);
+            process::exit(1);
+        },
+    };
+
+    if paths.len() > 1 {
+        let written = File::create(&paths[1]).and_then(|mut f| f.write_all(text.as_bytes()));
+        if let Err(e) = written {
+            eprintln!("Failed to write {}: {}", paths[1], e);
+            process::exit(1);
+        }
+    } else {
+        println!("{}", text);
+    }
+}
+
+fn parse_or_exit<T: std::str::FromStr>(value: &str, option: &str) -> T {
+    match value.parse::<T>() {
+        Ok(v) => v,
+        Err(_) => {
+            eprintln!("Invalid value for {}: \"{}\"\n\n{}", option, value, USAGE);
+            process::exit(2);
+        },
+    }
+}
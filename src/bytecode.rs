@@ -1,35 +1,273 @@
-use std::{mem, ptr, str};
+use std::io::{self, Read, Write};
+use std::fmt;
+use std::error::Error;
 use std::convert::TryFrom;
-use dmo::{Dmo, Context, Operator};
+use dmo::{Dmo, Context, Operator, anim_mode_to_code, anim_mode_from_code,
+          facing_to_code, facing_from_code, edge_to_code, edge_from_code,
+          blend_to_code, blend_from_code, overlap_to_code, overlap_from_code};
+use sprite::{Sprite, AnimMode, Facing, Edge};
+use color::{Color, ColorMode};
+use layer::Layer;
+use track::{Track, Keyframe, Ease, Param};
+use scene::Scene;
+use transition::Blend;
+use particle::Emitter;
+use shader::{Shader, MAX_REGISTERS, parse};
+use var::{Var, VarKind, Compare, MAX_VARS};
+use font::{Font, Banner, parse_flf};
+use post::{PostFx, Axis};
+use collision::Overlap;
+
+/// Marks a sprite without a transparent char. Not a valid char code.
+pub const NO_TRANSPARENT: u32 = 0xFFFF_FFFF;
+
+/// The most entries a section can have, the count is written as a u8.
+pub const MAX_COUNT: usize = 255;
 
 pub trait Bytecode {
     fn to_bytecode(&self) -> Vec<u8>;
     fn from_bytecode(data: Vec<u8>) -> Dmo;
+
+    /// Streams the bytecode sections into a writer, propagating I/O errors.
+    fn write_bytecode<W: Write>(&self, w: &mut W) -> io::Result<()>;
+
+    /// Decodes a `Dmo` from a reader. A `&[u8]` is also a reader, so an
+    /// `include_bytes!()` slice can be decoded without copying it first.
+    fn read_bytecode<R: Read>(r: &mut R) -> io::Result<Dmo>;
 }
 
 impl Bytecode for Dmo {
     fn to_bytecode(&self) -> Vec<u8> {
         let mut res: Vec<u8> = Vec::new();
+        // Writing to a Vec<u8> doesn't fail.
+        self.write_bytecode(&mut res).unwrap();
+        res
+    }
+
+    fn from_bytecode(data: Vec<u8>) -> Dmo {
+        Dmo::read_bytecode(&mut data.as_slice()).unwrap()
+    }
+
+    fn write_bytecode<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        // Canvas
+        // - u16: width
+        // - u16: height
+        // - u16: viewport width, 0 for the width
+        // - u16: viewport height, 0 for the height
+        // - u8: color mode
+        // - u8: number of mirror pairs
+        // - for each pair:
+        //   - u16: length in chars, 2
+        //   - [u32]: chars
+        // - u8: number of layers
+        // - for each layer:
+        //   - u16: length of the name in chars
+        //   - [u32]: name
+        //   - u16: z, as two's complement
+        //   - u8: visible, 0 or 1
+        //   - u16, u16: scroll x and y, as two's complement
+        // - u8: number of tracks
+        // - for each track:
+        //   - u16: length of the name in chars
+        //   - [u32]: name
+        //   - u16: number of keys
+        //   - for each key:
+        //     - f32: time
+        //     - f32: value
+        //     - u8: easing
+        // - u32: seed
+        // - u8: number of emitters
+        // - for each emitter:
+        //   - u16: length of the name in chars
+        //   - [u32]: name
+        //   - f32: rate
+        //   - f32: lifetime
+        //   - f32, f32: x speed min and max
+        //   - f32, f32: y speed min and max
+        //   - u16: number of glyphs
+        //   - [u32]: glyphs
+        // - u8: number of shaders
+        // - for each shader:
+        //   - u16: length of the name in chars
+        //   - [u32]: name
+        //   - u16: length of the expression in chars
+        //   - [u32]: expression
+        // - u8: number of variables
+        // - for each variable:
+        //   - u16: length of the name in chars
+        //   - [u32]: name
+        //   - u8: kind
+        //   - f32: value
+        // - u8: number of fonts
+        // - for each font:
+        //   - u16: length of the name in chars
+        //   - [u32]: name
+        //   - u32: length of the .flf text in bytes
+        //   - [u8]: .flf text, UTF-8
+        // - u8: number of banners
+        // - for each banner:
+        //   - u16: length of the name in chars
+        //   - [u32]: name
+        //   - u16: length of the font name in chars
+        //   - [u32]: font name
+        //   - u16: length of the text in chars
+        //   - [u32]: text
+        // - u8: number of post effects
+        // - for each post effect:
+        //   - u8: kind
+        //   - the arguments of the kind, see `write_post_fx()`
+
+        let context = self.get_context();
+
+        try!(write_u16(w, context.width));
+        try!(write_u16(w, context.height));
+        try!(write_u16(w, context.view_width.unwrap_or(0)));
+        try!(write_u16(w, context.view_height.unwrap_or(0)));
+        try!(write_u8(w, color_mode_to_code(context.color_mode)));
+
+        try!(write_u8(w, context.mirror.len() as u8));
+        for pair in context.mirror.iter() {
+            let chars: Vec<char> = pair.chars().collect();
+            try!(write_char_line(w, &chars));
+        }
+
+        try!(write_u8(w, context.layers.len() as u8));
+        for layer in context.layers.iter() {
+            let name: Vec<char> = layer.name.chars().collect();
+            try!(write_char_line(w, &name));
+            try!(write_u16(w, layer.z as u16));
+            try!(write_u8(w, layer.visible as u8));
+            try!(write_u16(w, layer.scroll.0 as u16));
+            try!(write_u16(w, layer.scroll.1 as u16));
+        }
+
+        try!(write_u8(w, context.tracks.len() as u8));
+        for track in context.tracks.iter() {
+            let name: Vec<char> = track.name.chars().collect();
+            try!(write_char_line(w, &name));
+            try!(write_u16(w, track.keys.len() as u16));
+            for key in track.keys.iter() {
+                try!(write_f32(w, key.t));
+                try!(write_f32(w, key.value));
+                try!(write_u8(w, ease_to_code(key.ease)));
+            }
+        }
+
+        try!(write_u32(w, context.seed));
+        try!(write_u8(w, context.emitters.len() as u8));
+        for emitter in context.emitters.iter() {
+            let name: Vec<char> = emitter.name.chars().collect();
+            try!(write_char_line(w, &name));
+            try!(write_f32(w, emitter.rate));
+            try!(write_f32(w, emitter.lifetime));
+            try!(write_f32(w, emitter.speed_x.0));
+            try!(write_f32(w, emitter.speed_x.1));
+            try!(write_f32(w, emitter.speed_y.0));
+            try!(write_f32(w, emitter.speed_y.1));
+            let glyphs: Vec<char> = emitter.glyphs.chars().collect();
+            try!(write_char_line(w, &glyphs));
+        }
+
+        try!(write_u8(w, context.shaders.len() as u8));
+        for shader in context.shaders.iter() {
+            let name: Vec<char> = shader.name.chars().collect();
+            try!(write_char_line(w, &name));
+            let fill: Vec<char> = shader.fill.chars().collect();
+            try!(write_char_line(w, &fill));
+        }
+
+        try!(write_u8(w, context.vars.len() as u8));
+        for var in context.vars.iter() {
+            let name: Vec<char> = var.name.chars().collect();
+            try!(write_char_line(w, &name));
+            try!(write_u8(w, var_kind_to_code(var.kind)));
+            try!(write_f32(w, var.value));
+        }
+
+        try!(write_u8(w, context.fonts.len() as u8));
+        for font in context.fonts.iter() {
+            let name: Vec<char> = font.name.chars().collect();
+            try!(write_char_line(w, &name));
+            // fonts are too long for a char line
+            try!(write_u32(w, font.flf.len() as u32));
+            try!(w.write_all(font.flf.as_bytes()));
+        }
+
+        try!(write_u8(w, context.banners.len() as u8));
+        for banner in context.banners.iter() {
+            let name: Vec<char> = banner.name.chars().collect();
+            try!(write_char_line(w, &name));
+            let font: Vec<char> = banner.font.chars().collect();
+            try!(write_char_line(w, &font));
+            let text: Vec<char> = banner.text.chars().collect();
+            try!(write_char_line(w, &text));
+        }
+
+        try!(write_u8(w, context.post.len() as u8));
+        for fx in context.post.iter() {
+            try!(write_post_fx(w, fx));
+        }
 
         // Sprites
         // - u8: number of sprites
-        // - u8: length of the sprite
-        // - [u8]: sprite
+        // - u16: width, length of the longest line in chars
+        // - u16: height, number of lines of the tallest frame
+        // - u32: transparent char, or NO_TRANSPARENT
+        // - u16: number of frames
+        // - for each frame:
+        //   - u16: number of lines, the text split at each newline, so a
+        //     trailing newline is an empty last line
+        //   - for each line:
+        //     - u16: length of the line in chars
+        //     - [u32]: chars
+        // - u8: number of palette colors
+        // - [u32]: palette colors, packed with Color::to_u32()
+        // - u16: number of lines in the color map, split as the frames
+        // - for each color map line:
+        //   - u16: length of the line in chars
+        //   - [u32]: chars
         // ...
 
         let sprites = self.get_sprites();
 
-        res.push(sprites.len() as u8);
+        try!(write_u8(w, sprites.len() as u8));
 
         // ASCII sprites can be unicode UTF-32, so expect the 4-byte char
         // instead of u8
         for sprite in sprites.iter() {
-            // length in chars, not in bytes
-            res.push(sprite.chars().count() as u8);
+            try!(write_u16(w, sprite.width() as u16));
+            try!(write_u16(w, sprite.height() as u16));
 
-            let v: Vec<char> = sprite.chars().collect();
-            for ch in v.iter() {
-                push_u32(&mut res, *ch as u32)
+            match sprite.transparent() {
+                Some(ch) => try!(write_u32(w, ch as u32)),
+                None => try!(write_u32(w, NO_TRANSPARENT)),
+            }
+
+            try!(write_u16(w, sprite.frame_count() as u16));
+
+            for frame in sprite.frames().iter() {
+                let lines: Vec<Vec<char>> = frame.split('\n').map(|line| line.chars().collect()).collect();
+                try!(write_u16(w, lines.len() as u16));
+
+                for line in lines.iter() {
+                    // length in chars, not in bytes
+                    try!(write_char_line(w, line));
+                }
+            }
+
+            let palette = sprite.palette();
+            try!(write_u8(w, palette.len() as u8));
+            for color in palette.iter() {
+                try!(write_u32(w, color.to_u32()));
+            }
+
+            let color_lines: Vec<Vec<char>> = match sprite.colors() {
+                Some(colors) => colors.split('\n').map(|line| line.chars().collect()).collect(),
+                None => vec![],
+            };
+            try!(write_u16(w, color_lines.len() as u16));
+            for line in color_lines.iter() {
+                try!(write_char_line(w, line));
             }
         }
 
@@ -38,91 +276,916 @@ impl Bytecode for Dmo {
         // - u8: number of operators
         // - u8: opcode
         // - []: arguments of different types, but we always know how many and what kind there are
+        //
+        // A Param argument is:
+        // - u8: 0 for a number, 1 for a track, 2 for a variable
+        // - f32: the number, or u8: the track or variable index
 
         let operators = self.get_operators();
 
-        res.push(operators.len() as u8);
-
-        for op in operators.iter() {
-            use dmo::Operator::*;
-            match *op {
-                NOOP => {},
+        try!(write_operators(w, operators));
 
-                Exit(limit) => {
-                    res.push(op_to_code(Exit(0.0)));
-                    push_f32(&mut res, limit as f32);
-                },
+        // === Scenes ===
 
-                Print => res.push(op_to_code(Print)),
+        // - u8: number of scenes
+        // - for each scene:
+        //   - u16: length of the name in chars
+        //   - [u32]: name
+        //   - f32: start
+        //   - u8: 1 if there is an end, or 0
+        //   - f32: end, only if there is one
+        //   - the operators, as above
+        // - u8: 1 if there is an end time, or 0
+        // - f32: end time, only if there is one
 
-                Draw(idx, offset, speed) => {
-                    res.push(op_to_code(Draw(0, 0, 0.0)));
+        let scenes = self.get_scenes();
 
-                    res.push(idx as u8);
-                    res.push(offset as u8);
-                    push_f32(&mut res, speed as f32);
-                },
-
-                Clear(charcode) => {
-                    res.push(op_to_code(Clear(0)));
-                    push_u32(&mut res, charcode as u32);
-                },
-            }
+        try!(write_u8(w, scenes.len() as u8));
+        for scene in scenes.iter() {
+            let name: Vec<char> = scene.name.chars().collect();
+            try!(write_char_line(w, &name));
+            try!(write_f32(w, scene.start));
+            try!(write_optional_f32(w, scene.end));
+            try!(write_operators(w, &scene.operators));
         }
 
-        res
+        try!(write_optional_f32(w, self.get_end()));
+
+        Ok(())
     }
 
-    fn from_bytecode(data: Vec<u8>) -> Dmo {
-        let mut blob = DataBlob::new(data);
+    fn read_bytecode<R: Read>(r: &mut R) -> io::Result<Dmo> {
+        let mut blob = DataBlob::new(r);
 
         let mut context = Context::new();
 
-        let mut n_sprites = blob.read_u8();
+        let width = try!(blob.read_u16());
+        let height = try!(blob.read_u16());
+        context.set_size(width, height);
+        context.view_width = match try!(blob.read_u16()) {
+            0 => None,
+            view_width => Some(view_width),
+        };
+        context.view_height = match try!(blob.read_u16()) {
+            0 => None,
+            view_height => Some(view_height),
+        };
+        context.color_mode = try!(code_to_color_mode(try!(blob.read_u8())));
+
+        context.mirror = vec![];
+        for _ in 0 .. try!(blob.read_u8()) {
+            context.mirror.push(try!(blob.read_char_line()));
+        }
+
+        for _ in 0 .. try!(blob.read_u8()) {
+            let mut layer = Layer::new(&try!(blob.read_char_line()));
+            layer.z = try!(blob.read_u16()) as i16;
+            layer.visible = try!(blob.read_u8()) != 0;
+            layer.scroll = (try!(blob.read_u16()) as i16, try!(blob.read_u16()) as i16);
+            context.layers.push(layer);
+        }
+        for _ in 0 .. try!(blob.read_u8()) {
+            let name = try!(blob.read_char_line());
+            let mut keys: Vec<Keyframe> = vec![];
+            for _ in 0 .. try!(blob.read_u16()) {
+                keys.push(Keyframe::new(try!(blob.read_f32()),
+                                        try!(blob.read_f32()),
+                                        try!(code_to_ease(try!(blob.read_u8())))));
+            }
+            context.tracks.push(Track::new(&name, keys));
+        }
+
+        context.seed = try!(blob.read_u32());
+        for _ in 0 .. try!(blob.read_u8()) {
+            let name = try!(blob.read_char_line());
+            let rate = try!(blob.read_f32());
+            let lifetime = try!(blob.read_f32());
+            let speed_x = (try!(blob.read_f32()), try!(blob.read_f32()));
+            let speed_y = (try!(blob.read_f32()), try!(blob.read_f32()));
+            let mut emitter = Emitter::new(&name, rate, lifetime, &try!(blob.read_char_line()));
+            emitter.speed_x = speed_x;
+            emitter.speed_y = speed_y;
+            context.emitters.push(emitter);
+        }
+        for _ in 0 .. try!(blob.read_u8()) {
+            let name = try!(blob.read_char_line());
+            context.shaders.push(Shader::new(&name, &try!(blob.read_char_line())));
+        }
+        for _ in 0 .. try!(blob.read_u8()) {
+            let name = try!(blob.read_char_line());
+            let kind = try!(code_to_var_kind(try!(blob.read_u8())));
+            context.vars.push(Var::new(&name, kind, try!(blob.read_f32())));
+        }
+        for _ in 0 .. try!(blob.read_u8()) {
+            let name = try!(blob.read_char_line());
+            let len = try!(blob.read_u32());
+            context.fonts.push(Font::new(&name, &try!(blob.read_str(len as usize))));
+        }
+        for _ in 0 .. try!(blob.read_u8()) {
+            let name = try!(blob.read_char_line());
+            let font = try!(blob.read_char_line());
+            context.banners.push(Banner::new(&name, &font, &try!(blob.read_char_line())));
+        }
+        for _ in 0 .. try!(blob.read_u8()) {
+            context.post.push(try!(blob.read_post_fx()));
+        }
+
+        // allocate the layers, the track values, the random numbers, the
+        // variables and the banners
+        context.reset();
+
+        let mut n_sprites = try!(blob.read_u8());
 
         while n_sprites >= 1 {
-            // length of the sprite in chars, not in u8
-            let l = blob.read_u8();
+            // the size is for players which allocate the sprite up front
+            let _width = try!(blob.read_u16());
+            let _height = try!(blob.read_u16());
+
+            let transparent = match try!(blob.read_u32()) {
+                NO_TRANSPARENT => None,
+                n => Some(try!(u32_to_char(n))),
+            };
+
+            let mut frames: Vec<String> = vec![];
+            for _ in 0 .. try!(blob.read_u16()) {
+                let mut lines: Vec<String> = vec![];
+                for _ in 0 .. try!(blob.read_u16()) {
+                    lines.push(try!(blob.read_char_line()));
+                }
+                frames.push(lines.join("\n"));
+            }
+
+            let mut sprite = Sprite::new("", transparent);
+            if !frames.is_empty() {
+                sprite = sprite.with_frames(frames);
+            }
+
+            let mut palette: Vec<Color> = vec![];
+            for _ in 0 .. try!(blob.read_u8()) {
+                palette.push(Color::from_u32(try!(blob.read_u32())));
+            }
+
+            let mut color_lines: Vec<String> = vec![];
+            for _ in 0 .. try!(blob.read_u16()) {
+                color_lines.push(try!(blob.read_char_line()));
+            }
 
-            let v = blob.read_char_vec(l as usize);
-            let s: String = v.into_iter().collect();
+            if !color_lines.is_empty() || !palette.is_empty() {
+                sprite = sprite.with_colors(&color_lines.join("\n"), palette);
+            }
 
-            context.sprites.push(s);
+            context.sprites.push(sprite);
 
             n_sprites -= 1;
         }
 
         // === Operators ===
 
-        let mut operators: Vec<Operator> = vec![];
+        let operators = try!(read_operators(&mut blob));
 
-        let mut n_operators = blob.read_u8();
+        // === Scenes ===
 
-        while n_operators >= 1 {
-            let op = code_to_op(blob.read_u8());
+        let mut scenes: Vec<Scene> = vec![];
 
-            use self::Operator::*;
-            let op_val = match op {
-                NOOP => NOOP,
-                Exit(_) => Exit(blob.read_f32()),
-                Print => Print,
-                Draw(_, _, _) => {
-                    Draw(blob.read_u8(),
-                         blob.read_u8(),
-                         blob.read_f32())
-                },
-                Clear(_) => Clear(blob.read_u32()),
-            };
+        for _ in 0 .. try!(blob.read_u8()) {
+            let name = try!(blob.read_char_line());
+            let start = try!(blob.read_f32());
+            let end = try!(blob.read_optional_f32());
+            let operators = try!(read_operators(&mut blob));
+            scenes.push(Scene::new(&name, start, end, operators));
+        }
 
-            match op_val {
-                NOOP => {},
-                _ => operators.push(op_val),
-            }
+        let end = try!(blob.read_optional_f32());
+
+        Ok(Dmo::new(context, operators).with_timeline(scenes, end))
+    }
+}
+
+/// Writes the number of operators, then each opcode and its arguments.
+pub fn write_operators<W: Write>(w: &mut W, operators: &Vec<Operator>) -> io::Result<()> {
+    try!(write_u8(w, operators.len() as u8));
+
+    for op in operators.iter() {
+        use dmo::Operator::*;
+        match *op {
+            NOOP => try!(write_u8(w, op_to_code(NOOP))),
+
+            Exit(limit) => {
+                try!(write_u8(w, op_to_code(Exit(Param::Value(0.0)))));
+                try!(write_param(w, limit));
+            },
+
+            Print => try!(write_u8(w, op_to_code(Print))),
+
+            Draw(idx, x, y, speed, facing, edge) => {
+                try!(write_u8(w, op_to_code(Draw(0, Param::Value(0.0), Param::Value(0.0), Param::Value(0.0),
+                                                  Facing::Right, Edge::Wrap))));
+
+                try!(write_u8(w, idx as u8));
+                try!(write_param(w, x));
+                try!(write_param(w, y));
+                try!(write_param(w, speed));
+                try!(write_u8(w, facing_to_code(facing)));
+                try!(write_u8(w, edge_to_code(edge)));
+            },
+
+            Clear(charcode) => {
+                try!(write_u8(w, op_to_code(Clear(0))));
+                try!(write_u32(w, charcode as u32));
+            },
+
+            SetColor(fg, bg, attrs) => {
+                try!(write_u8(w, op_to_code(SetColor(Color::Default, Color::Default, 0))));
+                try!(write_u32(w, fg.to_u32()));
+                try!(write_u32(w, bg.to_u32()));
+                try!(write_u8(w, attrs));
+            },
+
+            Animate(idx, fps, mode) => {
+                try!(write_u8(w, op_to_code(Animate(0, 0.0, AnimMode::Loop))));
+                try!(write_u8(w, idx));
+                try!(write_f32(w, fps));
+                try!(write_u8(w, anim_mode_to_code(mode)));
+            },
+
+            SetLayer(layer) => {
+                try!(write_u8(w, op_to_code(SetLayer(0))));
+                try!(write_u8(w, layer));
+            },
+
+            ClearLayer(layer) => {
+                try!(write_u8(w, op_to_code(ClearLayer(0))));
+                try!(write_u8(w, layer));
+            },
+
+            MoveLayer(layer, speed_x, speed_y) => {
+                try!(write_u8(w, op_to_code(MoveLayer(0, 0.0, 0.0))));
+                try!(write_u8(w, layer));
+                try!(write_f32(w, speed_x));
+                try!(write_f32(w, speed_y));
+            },
+
+            Transition(blend, duration, seed) => {
+                try!(write_u8(w, op_to_code(Transition(Blend::WipeLeft, Param::Value(0.0), 0))));
+                try!(write_u8(w, blend_to_code(blend)));
+                try!(write_param(w, duration));
+                try!(write_u32(w, seed));
+            },
+
+            Emit(emitter, x, y) => {
+                try!(write_u8(w, op_to_code(Emit(0, Param::Value(0.0), Param::Value(0.0)))));
+                try!(write_u8(w, emitter));
+                try!(write_param(w, x));
+                try!(write_param(w, y));
+            },
+
+            Plasma(scale, speed) => {
+                try!(write_u8(w, op_to_code(Plasma(Param::Value(0.0), Param::Value(0.0)))));
+                try!(write_param(w, scale));
+                try!(write_param(w, speed));
+            },
+
+            Fire(cooling) => {
+                try!(write_u8(w, op_to_code(Fire(0))));
+                try!(write_u8(w, cooling));
+            },
+
+            Starfield(count, speed) => {
+                try!(write_u8(w, op_to_code(Starfield(0, Param::Value(0.0)))));
+                try!(write_u16(w, count));
+                try!(write_param(w, speed));
+            },
+
+            SineScroll(idx, speed, amplitude, frequency) => {
+                try!(write_u8(w, op_to_code(SineScroll(0, Param::Value(0.0), 0.0, 0.0))));
+                try!(write_u8(w, idx));
+                try!(write_param(w, speed));
+                try!(write_f32(w, amplitude));
+                try!(write_f32(w, frequency));
+            },
+
+            Shade(shader) => {
+                try!(write_u8(w, op_to_code(Shade(0))));
+                try!(write_u8(w, shader));
+            },
+
+            Set(var, value) => {
+                try!(write_u8(w, op_to_code(Set(0, Param::Value(0.0)))));
+                try!(write_u8(w, var));
+                try!(write_param(w, value));
+            },
+
+            Add(var, value) => {
+                try!(write_u8(w, op_to_code(Add(0, Param::Value(0.0)))));
+                try!(write_u8(w, var));
+                try!(write_param(w, value));
+            },
+
+            Mul(var, value) => {
+                try!(write_u8(w, op_to_code(Mul(0, Param::Value(0.0)))));
+                try!(write_u8(w, var));
+                try!(write_param(w, value));
+            },
+
+            Sin(var, value) => {
+                try!(write_u8(w, op_to_code(Sin(0, Param::Value(0.0)))));
+                try!(write_u8(w, var));
+                try!(write_param(w, value));
+            },
+
+            Branch(var, compare, value, skip) => {
+                try!(write_u8(w, op_to_code(Branch(0, Compare::Less, Param::Value(0.0), 0))));
+                try!(write_u8(w, var));
+                try!(write_u8(w, compare_to_code(compare)));
+                try!(write_param(w, value));
+                try!(write_u8(w, skip));
+            },
+
+            Text(banner, x, y, speed, edge) => {
+                try!(write_u8(w, op_to_code(Text(0, Param::Value(0.0), Param::Value(0.0), Param::Value(0.0), Edge::Wrap))));
+                try!(write_u8(w, banner));
+                try!(write_param(w, x));
+                try!(write_param(w, y));
+                try!(write_param(w, speed));
+                try!(write_u8(w, edge_to_code(edge)));
+            },
+
+            Camera(x, y) => {
+                try!(write_u8(w, op_to_code(Camera(Param::Value(0.0), Param::Value(0.0)))));
+                try!(write_param(w, x));
+                try!(write_param(w, y));
+            },
+
+            Follow(sprite_idx) => {
+                try!(write_u8(w, op_to_code(Follow(0))));
+                try!(write_u8(w, sprite_idx));
+            },
+
+            Collide(a, b, overlap, var) => {
+                try!(write_u8(w, op_to_code(Collide(0, 0, Overlap::Bounds, 0))));
+                try!(write_u8(w, a));
+                try!(write_u8(w, b));
+                try!(write_u8(w, overlap_to_code(overlap)));
+                try!(write_u8(w, var));
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads what `write_operators()` wrote. `NOOP`s are kept, so that the
+/// operators which `Branch` skips stay the same.
+pub fn read_operators<R: Read>(blob: &mut DataBlob<R>) -> io::Result<Vec<Operator>> {
+    let mut operators: Vec<Operator> = vec![];
+
+    let mut n_operators = try!(blob.read_u8());
+
+    while n_operators >= 1 {
+        let op = code_to_op(try!(blob.read_u8()));
+
+        use self::Operator::*;
+        let op_val = match op {
+            NOOP => NOOP,
+            Exit(_) => Exit(try!(blob.read_param())),
+            Print => Print,
+            Draw(_, _, _, _, _, _) => {
+                Draw(try!(blob.read_u8()),
+                     try!(blob.read_param()),
+                     try!(blob.read_param()),
+                     try!(blob.read_param()),
+                     try!(facing_from_code(try!(blob.read_u8()))),
+                     try!(edge_from_code(try!(blob.read_u8()))))
+            },
+            Clear(_) => Clear(try!(blob.read_u32())),
+            SetColor(_, _, _) => {
+                SetColor(Color::from_u32(try!(blob.read_u32())),
+                         Color::from_u32(try!(blob.read_u32())),
+                         try!(blob.read_u8()))
+            },
+            Animate(_, _, _) => {
+                Animate(try!(blob.read_u8()),
+                        try!(blob.read_f32()),
+                        try!(anim_mode_from_code(try!(blob.read_u8()))))
+            },
+            SetLayer(_) => SetLayer(try!(blob.read_u8())),
+            ClearLayer(_) => ClearLayer(try!(blob.read_u8())),
+            MoveLayer(_, _, _) => {
+                MoveLayer(try!(blob.read_u8()),
+                          try!(blob.read_f32()),
+                          try!(blob.read_f32()))
+            },
+            Transition(_, _, _) => {
+                Transition(try!(blend_from_code(try!(blob.read_u8()))),
+                           try!(blob.read_param()),
+                           try!(blob.read_u32()))
+            },
+            Emit(_, _, _) => {
+                Emit(try!(blob.read_u8()),
+                     try!(blob.read_param()),
+                     try!(blob.read_param()))
+            },
+            Plasma(_, _) => Plasma(try!(blob.read_param()), try!(blob.read_param())),
+            Fire(_) => Fire(try!(blob.read_u8())),
+            Starfield(_, _) => Starfield(try!(blob.read_u16()), try!(blob.read_param())),
+            SineScroll(_, _, _, _) => {
+                SineScroll(try!(blob.read_u8()),
+                           try!(blob.read_param()),
+                           try!(blob.read_f32()),
+                           try!(blob.read_f32()))
+            },
+            Shade(_) => Shade(try!(blob.read_u8())),
+            Set(_, _) => Set(try!(blob.read_u8()), try!(blob.read_param())),
+            Add(_, _) => Add(try!(blob.read_u8()), try!(blob.read_param())),
+            Mul(_, _) => Mul(try!(blob.read_u8()), try!(blob.read_param())),
+            Sin(_, _) => Sin(try!(blob.read_u8()), try!(blob.read_param())),
+            Branch(_, _, _, _) => {
+                Branch(try!(blob.read_u8()),
+                       try!(code_to_compare(try!(blob.read_u8()))),
+                       try!(blob.read_param()),
+                       try!(blob.read_u8()))
+            },
+            Text(_, _, _, _, _) => {
+                Text(try!(blob.read_u8()),
+                     try!(blob.read_param()),
+                     try!(blob.read_param()),
+                     try!(blob.read_param()),
+                     try!(edge_from_code(try!(blob.read_u8()))))
+            },
+            Camera(_, _) => Camera(try!(blob.read_param()), try!(blob.read_param())),
+            Follow(_) => Follow(try!(blob.read_u8())),
+            Collide(_, _, _, _) => {
+                Collide(try!(blob.read_u8()),
+                        try!(blob.read_u8()),
+                        try!(overlap_from_code(try!(blob.read_u8()))),
+                        try!(blob.read_u8()))
+            },
+        };
+
+        operators.push(op_val);
+
+        n_operators -= 1;
+    }
+
+    Ok(operators)
+}
+
+/// A problem found by `verify()`, with the index of the offending operator.
+#[derive(Debug, PartialEq)]
+pub enum VerifyError {
+    /// The sprite index is not less than the number of sprites.
+    SpriteOutOfRange { op_idx: usize, sprite_idx: u8, n_sprites: usize },
+    /// The character code is not a Unicode scalar value.
+    InvalidCharcode { op_idx: usize, charcode: u32 },
+    /// A float argument is NaN or infinite.
+    NonFiniteFloat { op_idx: usize, value: f32 },
+    /// The canvas has no cells.
+    EmptyCanvas { width: u16, height: u16 },
+    /// The viewport has no cells, or is larger than the canvas.
+    InvalidViewport { width: u16, height: u16 },
+    /// The row is not less than the canvas height.
+    RowOutOfRange { op_idx: usize, y: u16, height: u16 },
+    /// An entry of the mirror table is not a pair of chars.
+    InvalidMirrorPair { idx: usize },
+    /// The layer number is greater than the number of layers.
+    LayerOutOfRange { op_idx: usize, layer: u8, n_layers: usize },
+    /// The track index is not less than the number of tracks.
+    TrackOutOfRange { op_idx: usize, track: u8, n_tracks: usize },
+    /// A track has no keys, a key which is not finite, or keys out of order.
+    InvalidTrack { idx: usize },
+    /// The emitter index is not less than the number of emitters.
+    EmitterOutOfRange { op_idx: usize, emitter: u8, n_emitters: usize },
+    /// An emitter has a rate or lifetime which is negative or not finite, a
+    /// speed which is not finite, or no glyphs.
+    InvalidEmitter { idx: usize },
+    /// The shader index is not less than the number of shaders.
+    ShaderOutOfRange { op_idx: usize, shader: u8, n_shaders: usize },
+    /// The expression of a shader doesn't parse, or needs too many registers.
+    InvalidShader { idx: usize, error: String },
+    /// The variable index is not less than the number of variables.
+    VarOutOfRange { op_idx: usize, var: u8, n_vars: usize },
+    /// A variable starts with a value which is not finite, or there are more
+    /// than `MAX_VARS` variables.
+    InvalidVar { idx: usize },
+    /// The banner index is not less than the number of banners.
+    BannerOutOfRange { op_idx: usize, banner: u8, n_banners: usize },
+    /// The `.flf` text of a font doesn't parse.
+    InvalidFont { idx: usize, error: String },
+    /// A banner is set in a font which is not in the context.
+    InvalidBanner { idx: usize, font: String },
+    /// A post effect has a number which is not finite, or a `Remap` with
+    /// strings of a different length.
+    InvalidPost { idx: usize },
+    /// A scene starts at a time which is not finite, or has an end which is
+    /// not finite or before the start.
+    InvalidScene { idx: usize },
+    /// The end time of the demo is not finite.
+    InvalidEnd { end: f32 },
+    /// A section has more than `MAX_COUNT` entries: operators, scenes,
+    /// sprites, the colors of a palette, and the lists of the context.
+    TooMany { section: &'static str, count: usize },
+    /// A problem with an operator of a scene.
+    InScene { scene: usize, error: Box<VerifyError> },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::VerifyError::*;
+        match *self {
+            SpriteOutOfRange { op_idx, sprite_idx, n_sprites } =>
+                write!(f, "operator {}: sprite index {} is out of range, there are {} sprites",
+                       op_idx, sprite_idx, n_sprites),
+            InvalidCharcode { op_idx, charcode } =>
+                write!(f, "operator {}: {:#x} is not a valid char", op_idx, charcode),
+            NonFiniteFloat { op_idx, value } =>
+                write!(f, "operator {}: {} is not a finite number", op_idx, value),
+            EmptyCanvas { width, height } =>
+                write!(f, "canvas size {}x{} is empty", width, height),
+            InvalidViewport { width, height } =>
+                write!(f, "viewport size {}x{} is empty or larger than the canvas", width, height),
+            RowOutOfRange { op_idx, y, height } =>
+                write!(f, "operator {}: row {} is out of range, the canvas height is {}",
+                       op_idx, y, height),
+            InvalidMirrorPair { idx } =>
+                write!(f, "mirror table entry {} is not a pair of chars", idx),
+            LayerOutOfRange { op_idx, layer, n_layers } =>
+                write!(f, "operator {}: layer {} is out of range, there are {} layers",
+                       op_idx, layer, n_layers),
+            TrackOutOfRange { op_idx, track, n_tracks } =>
+                write!(f, "operator {}: track {} is out of range, there are {} tracks",
+                       op_idx, track, n_tracks),
+            InvalidTrack { idx } =>
+                write!(f, "track {} needs keys with finite numbers, in the order of their time", idx),
+            EmitterOutOfRange { op_idx, emitter, n_emitters } =>
+                write!(f, "operator {}: emitter {} is out of range, there are {} emitters",
+                       op_idx, emitter, n_emitters),
+            InvalidEmitter { idx } =>
+                write!(f, "emitter {} needs a rate and a lifetime which are not negative, finite speeds and glyphs", idx),
+            ShaderOutOfRange { op_idx, shader, n_shaders } =>
+                write!(f, "operator {}: shader {} is out of range, there are {} shaders",
+                       op_idx, shader, n_shaders),
+            InvalidShader { idx, ref error } =>
+                write!(f, "shader {}: {}", idx, error),
+            VarOutOfRange { op_idx, var, n_vars } =>
+                write!(f, "operator {}: variable {} is out of range, there are {} variables",
+                       op_idx, var, n_vars),
+            InvalidVar { idx } =>
+                write!(f, "variable {} needs a finite value, and there can be at most {} variables",
+                       idx, MAX_VARS),
+            BannerOutOfRange { op_idx, banner, n_banners } =>
+                write!(f, "operator {}: banner {} is out of range, there are {} banners",
+                       op_idx, banner, n_banners),
+            InvalidFont { idx, ref error } =>
+                write!(f, "font {}: {}", idx, error),
+            InvalidBanner { idx, ref font } =>
+                write!(f, "banner {}: unknown font \"{}\"", idx, font),
+            InvalidPost { idx } =>
+                write!(f, "post effect {} needs finite numbers, and a char for each char of a Remap", idx),
+            InvalidScene { idx } =>
+                write!(f, "scene {} needs a finite start, and an end which is finite and not before it", idx),
+            InvalidEnd { end } =>
+                write!(f, "end time {} is not a finite number", end),
+            TooMany { section, count } =>
+                write!(f, "{} {} are too many, there can be at most {}", count, section, MAX_COUNT),
+            InScene { scene, ref error } =>
+                write!(f, "scene {}: {}", scene, error),
+        }
+    }
+}
+
+impl Error for VerifyError {
+    fn description(&self) -> &str {
+        "invalid operator"
+    }
+}
+
+/// Checks every operator against the context before it is compiled to a
+/// `JitFn`. Returns all the problems found, not just the first one.
+pub fn verify(context: &Context, operators: &Vec<Operator>) -> Result<(), Vec<VerifyError>> {
+    let mut errors: Vec<VerifyError> = vec![];
+
+    if context.width == 0 || context.height == 0 {
+        errors.push(VerifyError::EmptyCanvas { width: context.width, height: context.height });
+    }
+
+    let view_width = context.view_width.unwrap_or(context.width);
+    let view_height = context.view_height.unwrap_or(context.height);
+    if view_width == 0 || view_height == 0 || view_width > context.width || view_height > context.height {
+        errors.push(VerifyError::InvalidViewport { width: view_width, height: view_height });
+    }
+
+    verify_count(&mut errors, "mirror pairs", context.mirror.len());
+    verify_count(&mut errors, "layers", context.layers.len());
+    verify_count(&mut errors, "tracks", context.tracks.len());
+    verify_count(&mut errors, "emitters", context.emitters.len());
+    verify_count(&mut errors, "shaders", context.shaders.len());
+    verify_count(&mut errors, "fonts", context.fonts.len());
+    verify_count(&mut errors, "banners", context.banners.len());
+    verify_count(&mut errors, "post effects", context.post.len());
+    verify_count(&mut errors, "sprites", context.sprites.len());
+    for sprite in context.sprites.iter() {
+        verify_count(&mut errors, "palette colors", sprite.palette().len());
+    }
+
+    for (idx, pair) in context.mirror.iter().enumerate() {
+        if pair.chars().count() != 2 {
+            errors.push(VerifyError::InvalidMirrorPair { idx: idx });
+        }
+    }
+
+    for (idx, track) in context.tracks.iter().enumerate() {
+        let finite = track.keys.iter().all(|k| k.t.is_finite() && k.value.is_finite());
+        let ordered = track.keys.windows(2).all(|w| w[0].t <= w[1].t);
+        if track.keys.is_empty() || !finite || !ordered {
+            errors.push(VerifyError::InvalidTrack { idx: idx });
+        }
+    }
+
+    for (idx, emitter) in context.emitters.iter().enumerate() {
+        let non_negative = |x: f32| x.is_finite() && x >= 0.0;
+        let speeds = [emitter.speed_x.0, emitter.speed_x.1, emitter.speed_y.0, emitter.speed_y.1];
+        if !non_negative(emitter.rate) || !non_negative(emitter.lifetime) ||
+            !speeds.iter().all(|s| s.is_finite()) || emitter.glyphs.is_empty() {
+            errors.push(VerifyError::InvalidEmitter { idx: idx });
+        }
+    }
+
+    for (idx, shader) in context.shaders.iter().enumerate() {
+        match parse(&shader.fill) {
+            Ok(expr) => {
+                if expr.registers() > MAX_REGISTERS {
+                    errors.push(VerifyError::InvalidShader {
+                        idx: idx,
+                        error: String::from("the expression is nested too deep"),
+                    });
+                }
+            },
+            Err(e) => errors.push(VerifyError::InvalidShader { idx: idx, error: e }),
+        }
+    }
+
+    for (idx, var) in context.vars.iter().enumerate() {
+        if !var.value.is_finite() || idx >= MAX_VARS {
+            errors.push(VerifyError::InvalidVar { idx: idx });
+        }
+    }
+
+    for (idx, font) in context.fonts.iter().enumerate() {
+        if let Err(e) = parse_flf(&font.flf) {
+            errors.push(VerifyError::InvalidFont { idx: idx, error: e });
+        }
+    }
+
+    for (idx, banner) in context.banners.iter().enumerate() {
+        if !context.fonts.iter().any(|f| f.name == banner.font) {
+            errors.push(VerifyError::InvalidBanner { idx: idx, font: banner.font.clone() });
+        }
+    }
+
+    for (idx, fx) in context.post.iter().enumerate() {
+        if !fx.is_valid() {
+            errors.push(VerifyError::InvalidPost { idx: idx });
+        }
+    }
+
+    verify_operators(&mut errors, context, operators);
 
-            n_operators -= 1;
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Checks the scenes of the timeline and their operators. Errors of the
+/// operators are wrapped in `InScene`.
+pub fn verify_scenes(context: &Context, scenes: &Vec<Scene>, end: Option<f32>) -> Result<(), Vec<VerifyError>> {
+    let mut errors: Vec<VerifyError> = vec![];
+
+    verify_count(&mut errors, "scenes", scenes.len());
+
+    for (idx, scene) in scenes.iter().enumerate() {
+        let ends_after_start = match scene.end {
+            Some(end) => end.is_finite() && end >= scene.start,
+            None => true,
+        };
+        if !scene.start.is_finite() || !ends_after_start {
+            errors.push(VerifyError::InvalidScene { idx: idx });
+        }
+
+        let mut scene_errors: Vec<VerifyError> = vec![];
+        verify_operators(&mut scene_errors, context, &scene.operators);
+        for e in scene_errors.into_iter() {
+            errors.push(VerifyError::InScene { scene: idx, error: Box::new(e) });
+        }
+    }
+
+    if let Some(end) = end {
+        if !end.is_finite() {
+            errors.push(VerifyError::InvalidEnd { end: end });
         }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn verify_operators(errors: &mut Vec<VerifyError>, context: &Context, operators: &Vec<Operator>) {
+    verify_count(errors, "operators", operators.len());
+
+    for (op_idx, op) in operators.iter().enumerate() {
+        use dmo::Operator::*;
+        match *op {
+            NOOP => {},
+
+            Exit(limit) => verify_param(errors, op_idx, limit, context),
+
+            Print => {},
+
+            Draw(sprite_idx, x, y, speed, _, _) => {
+                verify_sprite_idx(errors, op_idx, sprite_idx, context);
+                verify_param(errors, op_idx, x, context);
+                verify_param(errors, op_idx, y, context);
+                // a track or a variable can move the sprite off the canvas,
+                // a number can't
+                if let Param::Value(y) = y {
+                    if y >= context.height as f32 {
+                        errors.push(VerifyError::RowOutOfRange { op_idx: op_idx, y: y as u16, height: context.height });
+                    }
+                }
+                verify_param(errors, op_idx, speed, context);
+            },
+
+            Clear(charcode) => {
+                let ch: Result<char, _> = TryFrom::try_from(charcode);
+                if ch.is_err() {
+                    errors.push(VerifyError::InvalidCharcode { op_idx: op_idx, charcode: charcode });
+                }
+            },
+
+            SetColor(_, _, _) => {},
+
+            Animate(sprite_idx, fps, _) => {
+                verify_sprite_idx(errors, op_idx, sprite_idx, context);
+                verify_finite(errors, op_idx, fps);
+            },
+
+            SetLayer(layer) => verify_layer(errors, op_idx, layer, context),
+
+            ClearLayer(layer) => verify_layer(errors, op_idx, layer, context),
+
+            MoveLayer(layer, speed_x, speed_y) => {
+                verify_layer(errors, op_idx, layer, context);
+                verify_finite(errors, op_idx, speed_x);
+                verify_finite(errors, op_idx, speed_y);
+            },
+
+            Transition(_, duration, _) => verify_param(errors, op_idx, duration, context),
+
+            Emit(emitter, x, y) => {
+                let n_emitters = context.emitters.len();
+                if (emitter as usize) >= n_emitters {
+                    errors.push(VerifyError::EmitterOutOfRange {
+                        op_idx: op_idx,
+                        emitter: emitter,
+                        n_emitters: n_emitters,
+                    });
+                }
+                verify_param(errors, op_idx, x, context);
+                verify_param(errors, op_idx, y, context);
+            },
+
+            Plasma(scale, speed) => {
+                verify_param(errors, op_idx, scale, context);
+                verify_param(errors, op_idx, speed, context);
+            },
+
+            Fire(_) => {},
+
+            Starfield(_, speed) => verify_param(errors, op_idx, speed, context),
+
+            SineScroll(sprite_idx, speed, amplitude, frequency) => {
+                verify_sprite_idx(errors, op_idx, sprite_idx, context);
+                verify_param(errors, op_idx, speed, context);
+                verify_finite(errors, op_idx, amplitude);
+                verify_finite(errors, op_idx, frequency);
+            },
+
+            Shade(shader) => {
+                let n_shaders = context.shaders.len();
+                if (shader as usize) >= n_shaders {
+                    errors.push(VerifyError::ShaderOutOfRange {
+                        op_idx: op_idx,
+                        shader: shader,
+                        n_shaders: n_shaders,
+                    });
+                }
+            },
+
+            Set(var, value) | Add(var, value) | Mul(var, value) | Sin(var, value) => {
+                verify_var(errors, op_idx, var, context);
+                verify_param(errors, op_idx, value, context);
+            },
+
+            Branch(var, _, value, _) => {
+                verify_var(errors, op_idx, var, context);
+                verify_param(errors, op_idx, value, context);
+            },
+
+            Text(banner, x, y, speed, _) => {
+                let n_banners = context.banners.len();
+                if (banner as usize) >= n_banners {
+                    errors.push(VerifyError::BannerOutOfRange {
+                        op_idx: op_idx,
+                        banner: banner,
+                        n_banners: n_banners,
+                    });
+                }
+                verify_param(errors, op_idx, x, context);
+                verify_param(errors, op_idx, y, context);
+                verify_param(errors, op_idx, speed, context);
+            },
+
+            Camera(x, y) => {
+                verify_param(errors, op_idx, x, context);
+                verify_param(errors, op_idx, y, context);
+            },
+
+            Follow(sprite_idx) => verify_sprite_idx(errors, op_idx, sprite_idx, context),
+
+            Collide(a, b, _, var) => {
+                verify_sprite_idx(errors, op_idx, a, context);
+                verify_sprite_idx(errors, op_idx, b, context);
+                verify_var(errors, op_idx, var, context);
+            },
+        }
+    }
+}
+
+fn verify_sprite_idx(errors: &mut Vec<VerifyError>, op_idx: usize, sprite_idx: u8, context: &Context) {
+    let n_sprites = context.sprites.len();
+    if (sprite_idx as usize) >= n_sprites {
+        errors.push(VerifyError::SpriteOutOfRange {
+            op_idx: op_idx,
+            sprite_idx: sprite_idx,
+            n_sprites: n_sprites,
+        });
+    }
+}
 
-        Dmo::new(context, operators)
+/// Layer numbers are 1 to the number of layers, or 0 for the canvas.
+fn verify_layer(errors: &mut Vec<VerifyError>, op_idx: usize, layer: u8, context: &Context) {
+    let n_layers = context.layers.len();
+    if (layer as usize) > n_layers {
+        errors.push(VerifyError::LayerOutOfRange {
+            op_idx: op_idx,
+            layer: layer,
+            n_layers: n_layers,
+        });
+    }
+}
+
+fn verify_var(errors: &mut Vec<VerifyError>, op_idx: usize, var: u8, context: &Context) {
+    let n_vars = context.vars.len();
+    if (var as usize) >= n_vars {
+        errors.push(VerifyError::VarOutOfRange {
+            op_idx: op_idx,
+            var: var,
+            n_vars: n_vars,
+        });
+    }
+}
+
+/// A number must be finite, a track or a variable must exist.
+fn verify_param(errors: &mut Vec<VerifyError>, op_idx: usize, param: Param, context: &Context) {
+    match param {
+        Param::Value(value) => verify_finite(errors, op_idx, value),
+        Param::Track(arg) => {
+            let n_tracks = context.tracks.len();
+            if (arg.track as usize) >= n_tracks {
+                errors.push(VerifyError::TrackOutOfRange {
+                    op_idx: op_idx,
+                    track: arg.track,
+                    n_tracks: n_tracks,
+                });
+            }
+        },
+        Param::Var(arg) => verify_var(errors, op_idx, arg.var, context),
+    }
+}
+
+fn verify_count(errors: &mut Vec<VerifyError>, section: &'static str, count: usize) {
+    if count > MAX_COUNT {
+        errors.push(VerifyError::TooMany { section: section, count: count });
+    }
+}
+
+fn verify_finite(errors: &mut Vec<VerifyError>, op_idx: usize, value: f32) {
+    if !value.is_finite() {
+        errors.push(VerifyError::NonFiniteFloat { op_idx: op_idx, value: value });
     }
 }
 
@@ -131,8 +1194,29 @@ pub fn op_to_code(op: Operator) -> u8 {
     match op {
         NOOP          => 0x00,
         Exit(_)       => 0x01,
-        Draw(_, _, _) => 0x02,
+        Draw(_, _, _, _, _, _) => 0x02,
         Clear(_)      => 0x03,
+        SetColor(_, _, _) => 0x04,
+        Animate(_, _, _) => 0x05,
+        SetLayer(_)   => 0x06,
+        ClearLayer(_) => 0x07,
+        MoveLayer(_, _, _) => 0x08,
+        Transition(_, _, _) => 0x09,
+        Emit(_, _, _) => 0x0A,
+        Plasma(_, _)  => 0x0B,
+        Fire(_)       => 0x0C,
+        Starfield(_, _) => 0x0D,
+        SineScroll(_, _, _, _) => 0x0E,
+        Shade(_)      => 0x0F,
+        Set(_, _)     => 0x10,
+        Add(_, _)     => 0x11,
+        Mul(_, _)     => 0x12,
+        Sin(_, _)     => 0x13,
+        Branch(_, _, _, _) => 0x14,
+        Text(_, _, _, _, _) => 0x15,
+        Camera(_, _)  => 0x16,
+        Follow(_)     => 0x17,
+        Collide(_, _, _, _) => 0x18,
         Print         => 0xFF,
     }
 }
@@ -141,127 +1225,424 @@ pub fn code_to_op(code: u8) -> Operator {
     use dmo::Operator::*;
     match code {
         0x00 => NOOP,
-        0x01 => Exit(0.0),
-        0x02 => Draw(0, 0, 0.0),
+        0x01 => Exit(Param::Value(0.0)),
+        0x02 => Draw(0, Param::Value(0.0), Param::Value(0.0), Param::Value(0.0), Facing::Right, Edge::Wrap),
         0x03 => Clear(0),
+        0x04 => SetColor(Color::Default, Color::Default, 0),
+        0x05 => Animate(0, 0.0, AnimMode::Loop),
+        0x06 => SetLayer(0),
+        0x07 => ClearLayer(0),
+        0x08 => MoveLayer(0, 0.0, 0.0),
+        0x09 => Transition(Blend::WipeLeft, Param::Value(0.0), 0),
+        0x0A => Emit(0, Param::Value(0.0), Param::Value(0.0)),
+        0x0B => Plasma(Param::Value(0.0), Param::Value(0.0)),
+        0x0C => Fire(0),
+        0x0D => Starfield(0, Param::Value(0.0)),
+        0x0E => SineScroll(0, Param::Value(0.0), 0.0, 0.0),
+        0x0F => Shade(0),
+        0x10 => Set(0, Param::Value(0.0)),
+        0x11 => Add(0, Param::Value(0.0)),
+        0x12 => Mul(0, Param::Value(0.0)),
+        0x13 => Sin(0, Param::Value(0.0)),
+        0x14 => Branch(0, Compare::Less, Param::Value(0.0), 0),
+        0x15 => Text(0, Param::Value(0.0), Param::Value(0.0), Param::Value(0.0), Edge::Wrap),
+        0x16 => Camera(Param::Value(0.0), Param::Value(0.0)),
+        0x17 => Follow(0),
+        0x18 => Collide(0, 0, Overlap::Bounds, 0),
         0xFF => Print,
         _ => NOOP,
     }
 }
 
-pub struct DataBlob {
-    data: Vec<u8>,
+/// Reads the bytecode values from any `Read` source, counting the bytes
+/// consumed so far.
+pub struct DataBlob<R: Read> {
+    reader: R,
     idx: usize,
 }
 
-impl DataBlob {
-    pub fn new(data: Vec<u8>) -> DataBlob {
+impl<R: Read> DataBlob<R> {
+    pub fn new(reader: R) -> DataBlob<R> {
         DataBlob {
-            data: data,
+            reader: reader,
             idx: 0,
         }
     }
 
-    pub fn skip(&mut self, skip_len: usize) {
-        self.idx += skip_len
+    /// Number of bytes read so far.
+    pub fn get_idx(&self) -> usize {
+        self.idx
     }
 
-    pub fn read_u8(&mut self) -> u8 {
-        let number = self.data[self.idx];
-        self.idx += 1;
-        number
+    pub fn skip(&mut self, skip_len: usize) -> io::Result<()> {
+        try!(self.read_u8_vec(skip_len));
+        Ok(())
     }
 
-    pub fn read_u32(&mut self) -> u32 {
-        let bytes: &[u8] = &self.data[self.idx .. self.idx+4];
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        try!(self.reader.read_exact(buf));
+        self.idx += buf.len();
+        Ok(())
+    }
 
-        let mut number: u32 = 0;
-        unsafe {
-            ptr::copy_nonoverlapping(
-                bytes.as_ptr(),
-                &mut number as *mut u32 as *mut u8,
-                4);
-        };
-        number.to_le();
+    pub fn read_u8(&mut self) -> io::Result<u8> {
+        let mut bytes = [0u8; 1];
+        try!(self.read_exact(&mut bytes));
+        Ok(bytes[0])
+    }
 
-        self.idx += 4;
-        number
+    pub fn read_u16(&mut self) -> io::Result<u16> {
+        let mut bytes = [0u8; 2];
+        try!(self.read_exact(&mut bytes));
+        Ok(le_bytes_to_u16(&bytes))
     }
 
-    pub fn read_f32(&mut self) -> f32 {
-        let number: f32 = unsafe { mem::transmute(self.read_u32()) };
-        number
+    pub fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0u8; 4];
+        try!(self.read_exact(&mut bytes));
+        Ok(le_bytes_to_u32(&bytes))
     }
 
-    pub fn read_str(&mut self, str_len: usize) -> &str {
-        if str_len == 0 {
-            return "";
+    pub fn read_u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0u8; 8];
+        try!(self.read_exact(&mut bytes));
+        Ok(le_bytes_to_u64(&bytes))
+    }
+
+    pub fn read_i32(&mut self) -> io::Result<i32> {
+        Ok(try!(self.read_u32()) as i32)
+    }
+
+    pub fn read_f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_bits(try!(self.read_u32())))
+    }
+
+    pub fn read_f64(&mut self) -> io::Result<f64> {
+        Ok(f64::from_bits(try!(self.read_u64())))
+    }
+
+    pub fn read_str(&mut self, str_len: usize) -> io::Result<String> {
+        let bytes = try!(self.read_u8_vec(str_len));
+        match String::from_utf8(bytes) {
+            Ok(text) => Ok(text),
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+
+    pub fn read_u8_vec(&mut self, str_len: usize) -> io::Result<Vec<u8>> {
+        let mut text: Vec<u8> = vec![0; str_len];
+        try!(self.read_exact(&mut text));
+        Ok(text)
+    }
+
+    /// Reads what `write_optional_f32()` wrote.
+    pub fn read_optional_f32(&mut self) -> io::Result<Option<f32>> {
+        match try!(self.read_u8()) {
+            0 => Ok(None),
+            _ => Ok(Some(try!(self.read_f32()))),
         }
-        let text = str::from_utf8(&self.data[self.idx .. self.idx+str_len]).unwrap();
-        self.idx += str_len;
-        text
     }
 
-    pub fn read_u8_vec(&mut self, str_len: usize) -> Vec<u8> {
-        let mut text: Vec<u8> = Vec::new();
+    /// Reads what `write_param()` wrote.
+    pub fn read_param(&mut self) -> io::Result<Param> {
+        match try!(self.read_u8()) {
+            0 => Ok(Param::Value(try!(self.read_f32()))),
+            1 => Ok(Param::track(try!(self.read_u8()))),
+            2 => Ok(Param::var(try!(self.read_u8()))),
+            n => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                    format!("invalid param kind: {:#x}", n))),
+        }
+    }
 
-        text.extend(self.data[self.idx .. self.idx+str_len].iter().cloned());
+    /// Reads what `write_post_fx()` wrote.
+    pub fn read_post_fx(&mut self) -> io::Result<PostFx> {
+        match try!(self.read_u8()) {
+            0 => {
+                let axis = try!(code_to_axis(try!(self.read_u8())));
+                let amplitude = try!(self.read_f32());
+                let frequency = try!(self.read_f32());
+                Ok(PostFx::Wave(axis, amplitude, frequency, try!(self.read_f32())))
+            },
+            1 => Ok(PostFx::Mirror(try!(code_to_axis(try!(self.read_u8()))))),
+            2 => Ok(PostFx::Invert(try!(self.read_char_line()))),
+            3 => {
+                let from = try!(self.read_char_line());
+                Ok(PostFx::Remap(from, try!(self.read_char_line())))
+            },
+            4 => Ok(PostFx::Ascii),
+            n => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                    format!("invalid post effect: {:#x}", n))),
+        }
+    }
 
-        self.idx += str_len;
-        text
+    /// Reads what `write_char_line()` wrote.
+    pub fn read_char_line(&mut self) -> io::Result<String> {
+        // length of the line in chars, not in u8
+        let l = try!(self.read_u16());
+        let v = try!(self.read_char_vec(l as usize));
+        Ok(v.into_iter().collect())
     }
 
-    pub fn read_char_vec(&mut self, str_len: usize) -> Vec<char> {
+    pub fn read_char_vec(&mut self, str_len: usize) -> io::Result<Vec<char>> {
         let mut text: Vec<char> = Vec::new();
 
         for _ in 0 .. str_len {
-            let n: u32 = self.read_u32();
-            let ch: char = TryFrom::try_from(n).unwrap();
-            text.push(ch);
-        }
-
-        text
-    }
-}
-
-pub fn push_u32(mut v: &mut Vec<u8>, n: u32) {
-    let bytes = unsafe { mem::transmute::<_, [u8; 4]>(n.to_le()) };
-    v.push(bytes[0]);
-    v.push(bytes[1]);
-    v.push(bytes[2]);
-    v.push(bytes[3]);
-}
-
-pub fn push_f32(mut v: &mut Vec<u8>, n: f32) {
-    let val_u32: u32 = unsafe { mem::transmute(n) };
-    push_u32(v, val_u32);
-}
-
-// NOTE: read_num_bytes and write_num_bytes macro in the byteorder crate by
-// BurntSushi
-//
-// macro_rules! read_num_bytes {
-//     ($ty:ty, $size:expr, $src:expr, $which:ident) => ({
-//         assert!($size == ::core::mem::size_of::<$ty>());
-//         assert!($size <= $src.len());
-//         let mut data: $ty = 0;
-//         unsafe {
-//             copy_nonoverlapping(
-//                 $src.as_ptr(),
-//                 &mut data as *mut $ty as *mut u8,
-//                 $size);
-//         }
-//         data.$which()
-//     });
-// }
-//
-// macro_rules! write_num_bytes {
-//     ($ty:ty, $size:expr, $n:expr, $dst:expr, $which:ident) => ({
-//         assert!($size <= $dst.len());
-//         unsafe {
-//             // N.B. https://github.com/rust-lang/rust/issues/22776
-//             let bytes = transmute::<_, [u8; $size]>($n.$which());
-//             copy_nonoverlapping((&bytes).as_ptr(), $dst.as_mut_ptr(), $size);
-//         }
-//     });
-// }
+            let n: u32 = try!(self.read_u32());
+            text.push(try!(u32_to_char(n)));
+        }
+
+        Ok(text)
+    }
+}
+
+pub fn color_mode_to_code(mode: ColorMode) -> u8 {
+    match mode {
+        ColorMode::NoColor   => 0x00,
+        ColorMode::Ansi256   => 0x01,
+        ColorMode::TrueColor => 0x02,
+    }
+}
+
+pub fn code_to_color_mode(code: u8) -> io::Result<ColorMode> {
+    match code {
+        0x00 => Ok(ColorMode::NoColor),
+        0x01 => Ok(ColorMode::Ansi256),
+        0x02 => Ok(ColorMode::TrueColor),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                format!("invalid color mode: {:#x}", code))),
+    }
+}
+
+pub fn ease_to_code(ease: Ease) -> u8 {
+    match ease {
+        Ease::Step       => 0x00,
+        Ease::Linear     => 0x01,
+        Ease::Smoothstep => 0x02,
+        Ease::Cubic      => 0x03,
+        Ease::EaseIn     => 0x04,
+        Ease::EaseOut    => 0x05,
+        Ease::EaseInOut  => 0x06,
+    }
+}
+
+pub fn code_to_ease(code: u8) -> io::Result<Ease> {
+    match code {
+        0x00 => Ok(Ease::Step),
+        0x01 => Ok(Ease::Linear),
+        0x02 => Ok(Ease::Smoothstep),
+        0x03 => Ok(Ease::Cubic),
+        0x04 => Ok(Ease::EaseIn),
+        0x05 => Ok(Ease::EaseOut),
+        0x06 => Ok(Ease::EaseInOut),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                format!("invalid easing: {:#x}", code))),
+    }
+}
+
+pub fn var_kind_to_code(kind: VarKind) -> u8 {
+    match kind {
+        VarKind::Float => 0x00,
+        VarKind::Int   => 0x01,
+    }
+}
+
+pub fn code_to_var_kind(code: u8) -> io::Result<VarKind> {
+    match code {
+        0x00 => Ok(VarKind::Float),
+        0x01 => Ok(VarKind::Int),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                format!("invalid variable kind: {:#x}", code))),
+    }
+}
+
+pub fn compare_to_code(compare: Compare) -> u8 {
+    match compare {
+        Compare::Less           => 0x00,
+        Compare::LessOrEqual    => 0x01,
+        Compare::Greater        => 0x02,
+        Compare::GreaterOrEqual => 0x03,
+        Compare::Equal          => 0x04,
+        Compare::NotEqual       => 0x05,
+    }
+}
+
+pub fn code_to_compare(code: u8) -> io::Result<Compare> {
+    match code {
+        0x00 => Ok(Compare::Less),
+        0x01 => Ok(Compare::LessOrEqual),
+        0x02 => Ok(Compare::Greater),
+        0x03 => Ok(Compare::GreaterOrEqual),
+        0x04 => Ok(Compare::Equal),
+        0x05 => Ok(Compare::NotEqual),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                format!("invalid comparison: {:#x}", code))),
+    }
+}
+
+/// Writes 1 and the value, or 0 for `None`.
+pub fn write_optional_f32<W: Write>(w: &mut W, value: Option<f32>) -> io::Result<()> {
+    match value {
+        Some(value) => {
+            try!(write_u8(w, 1));
+            write_f32(w, value)
+        },
+        None => write_u8(w, 0),
+    }
+}
+
+/// Writes an operator argument, a number, a track index or a variable index.
+pub fn write_param<W: Write>(w: &mut W, param: Param) -> io::Result<()> {
+    match param {
+        Param::Value(value) => {
+            try!(write_u8(w, 0));
+            write_f32(w, value)
+        },
+        Param::Track(arg) => {
+            try!(write_u8(w, 1));
+            write_u8(w, arg.track)
+        },
+        Param::Var(arg) => {
+            try!(write_u8(w, 2));
+            write_u8(w, arg.var)
+        },
+    }
+}
+
+/// Writes the kind of the effect as `u8`, then its arguments: the axis as
+/// `u8` and the numbers as `f32`, the strings as char lines.
+pub fn write_post_fx<W: Write>(w: &mut W, fx: &PostFx) -> io::Result<()> {
+    match *fx {
+        PostFx::Wave(axis, amplitude, frequency, speed) => {
+            try!(write_u8(w, 0));
+            try!(write_u8(w, axis_to_code(axis)));
+            try!(write_f32(w, amplitude));
+            try!(write_f32(w, frequency));
+            write_f32(w, speed)
+        },
+        PostFx::Mirror(axis) => {
+            try!(write_u8(w, 1));
+            write_u8(w, axis_to_code(axis))
+        },
+        PostFx::Invert(ref ramp) => {
+            try!(write_u8(w, 2));
+            let ramp: Vec<char> = ramp.chars().collect();
+            write_char_line(w, &ramp)
+        },
+        PostFx::Remap(ref from, ref to) => {
+            try!(write_u8(w, 3));
+            let from: Vec<char> = from.chars().collect();
+            try!(write_char_line(w, &from));
+            let to: Vec<char> = to.chars().collect();
+            write_char_line(w, &to)
+        },
+        PostFx::Ascii => write_u8(w, 4),
+    }
+}
+
+pub fn axis_to_code(axis: Axis) -> u8 {
+    match axis {
+        Axis::Horizontal => 0x00,
+        Axis::Vertical => 0x01,
+    }
+}
+
+pub fn code_to_axis(code: u8) -> io::Result<Axis> {
+    match code {
+        0x00 => Ok(Axis::Horizontal),
+        0x01 => Ok(Axis::Vertical),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                format!("invalid axis: {:#x}", code))),
+    }
+}
+
+/// Writes the length of the line in chars as `u16`, then the chars.
+pub fn write_char_line<W: Write>(w: &mut W, line: &[char]) -> io::Result<()> {
+    try!(write_u16(w, line.len() as u16));
+    for ch in line.iter() {
+        try!(write_u32(w, *ch as u32));
+    }
+    Ok(())
+}
+
+fn u32_to_char(n: u32) -> io::Result<char> {
+    match TryFrom::try_from(n) {
+        Ok(ch) => Ok(ch),
+        Err(_) => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                     format!("invalid char code: {:#x}", n))),
+    }
+}
+
+pub fn write_u8<W: Write>(w: &mut W, n: u8) -> io::Result<()> {
+    w.write_all(&[n])
+}
+
+pub fn write_u16<W: Write>(w: &mut W, n: u16) -> io::Result<()> {
+    w.write_all(&u16_to_le_bytes(n))
+}
+
+pub fn write_u32<W: Write>(w: &mut W, n: u32) -> io::Result<()> {
+    w.write_all(&u32_to_le_bytes(n))
+}
+
+pub fn write_u64<W: Write>(w: &mut W, n: u64) -> io::Result<()> {
+    w.write_all(&u64_to_le_bytes(n))
+}
+
+pub fn write_i32<W: Write>(w: &mut W, n: i32) -> io::Result<()> {
+    write_u32(w, n as u32)
+}
+
+pub fn write_f32<W: Write>(w: &mut W, n: f32) -> io::Result<()> {
+    write_u32(w, n.to_bits())
+}
+
+pub fn write_f64<W: Write>(w: &mut W, n: f64) -> io::Result<()> {
+    write_u64(w, n.to_bits())
+}
+
+pub fn push_u32(v: &mut Vec<u8>, n: u32) {
+    v.extend_from_slice(&u32_to_le_bytes(n));
+}
+
+pub fn push_f32(v: &mut Vec<u8>, n: f32) {
+    push_u32(v, n.to_bits());
+}
+
+// The bytecode is always Little-Endian, least-significant byte first,
+// regardless of the host. Shifting works on the numeric value, so these don't
+// depend on the byte order of the host in memory.
+
+pub fn u16_to_le_bytes(n: u16) -> [u8; 2] {
+    [(n & 0xFF) as u8,
+     ((n >> 8) & 0xFF) as u8]
+}
+
+pub fn u32_to_le_bytes(n: u32) -> [u8; 4] {
+    [((n >>  0) & 0xFF) as u8,
+     ((n >>  8) & 0xFF) as u8,
+     ((n >> 16) & 0xFF) as u8,
+     ((n >> 24) & 0xFF) as u8]
+}
+
+pub fn u64_to_le_bytes(n: u64) -> [u8; 8] {
+    let lo = u32_to_le_bytes(n as u32);
+    let hi = u32_to_le_bytes((n >> 32) as u32);
+    [lo[0], lo[1], lo[2], lo[3], hi[0], hi[1], hi[2], hi[3]]
+}
+
+pub fn le_bytes_to_u16(bytes: &[u8; 2]) -> u16 {
+    (bytes[0] as u16) | ((bytes[1] as u16) << 8)
+}
+
+pub fn le_bytes_to_u32(bytes: &[u8; 4]) -> u32 {
+    ((bytes[0] as u32) <<  0) |
+    ((bytes[1] as u32) <<  8) |
+    ((bytes[2] as u32) << 16) |
+    ((bytes[3] as u32) << 24)
+}
+
+pub fn le_bytes_to_u64(bytes: &[u8; 8]) -> u64 {
+    let lo = le_bytes_to_u32(&[bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let hi = le_bytes_to_u32(&[bytes[4], bytes[5], bytes[6], bytes[7]]);
+    (lo as u64) | ((hi as u64) << 32)
+}
@@ -0,0 +1,56 @@
+/// Where a sprite was drawn once during the frame, kept by `Draw` for
+/// `Collide`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Footprint {
+    /// Left, top, right and bottom of the frame of the sprite, in canvas
+    /// columns and rows. The right and bottom are not in it.
+    pub bounds: (i64, i64, i64, i64),
+    /// The cells with a char of the sprite, not the transparent ones, as
+    /// `row * width + column`
+    pub cells: Vec<usize>,
+}
+
+/// How `Collide` tells that two sprites overlap.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Overlap {
+    /// Their bounds overlap.
+    Bounds,
+    /// A cell has a char of both. The transparent chars don't count.
+    Cells,
+}
+
+/// Two sprites which overlapped during the frame, found by `Collide`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Collision {
+    pub a: u8,
+    pub b: u8,
+}
+
+impl Footprint {
+    pub fn overlaps(&self, other: &Footprint, overlap: Overlap) -> bool {
+        match overlap {
+            Overlap::Bounds => {
+                let (left, top, right, bottom) = self.bounds;
+                let (o_left, o_top, o_right, o_bottom) = other.bounds;
+                left < o_right && o_left < right && top < o_bottom && o_top < bottom
+            },
+            Overlap::Cells => self.cells.iter().any(|n| other.cells.contains(n)),
+        }
+    }
+}
+
+/// Whether any draw of `a` overlaps any draw of `b`. When they are the same
+/// sprite, it is any two different draws of it.
+pub fn any_overlap(a: &[Footprint], b: &[Footprint], same: bool, overlap: Overlap) -> bool {
+    for (i, fa) in a.iter().enumerate() {
+        for (j, fb) in b.iter().enumerate() {
+            if same && j <= i {
+                continue;
+            }
+            if fa.overlaps(fb, overlap) {
+                return true;
+            }
+        }
+    }
+    false
+}
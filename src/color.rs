@@ -0,0 +1,167 @@
+/// A terminal color. In the YAML: `Default`, `{ Indexed: 208 }` for the
+/// 256-color palette, or `{ Rgb: [ 255, 140, 0 ] }` for truecolor.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Color {
+    /// The terminal's own foreground or background color
+    Default,
+    /// 256-color palette index
+    Indexed(u8),
+    /// 24-bit color
+    Rgb(u8, u8, u8),
+}
+
+/// How `Context::render_frame()` writes colors to the terminal.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ColorMode {
+    /// Only the chars, for dumb terminals and tests.
+    NoColor,
+    /// `Rgb` colors are mapped to the 6x6x6 color cube of the 256-color
+    /// palette.
+    Ansi256,
+    /// 24-bit colors.
+    TrueColor,
+}
+
+pub const ATTR_BOLD: u8 = 0x01;
+pub const ATTR_UNDERLINE: u8 = 0x02;
+
+/// Colors and attributes of a cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Style {
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub underline: bool,
+}
+
+/// One char of the canvas, with its style.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cell {
+    pub ch: char,
+    pub style: Style,
+}
+
+impl Default for ColorMode {
+    fn default() -> ColorMode {
+        ColorMode::NoColor
+    }
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            fg: Color::Default,
+            bg: Color::Default,
+            bold: false,
+            underline: false,
+        }
+    }
+}
+
+impl Color {
+    /// Packs the color in a `u32` for the bytecode and the JIT: the tag in the
+    /// high byte, and the value in the low 3 bytes.
+    pub fn to_u32(&self) -> u32 {
+        match *self {
+            Color::Default => 0,
+            Color::Indexed(n) => 0x0100_0000 | n as u32,
+            Color::Rgb(r, g, b) => 0x0200_0000 | (r as u32) << 16 | (g as u32) << 8 | b as u32,
+        }
+    }
+
+    /// Unpacks a color from `.to_u32()`. Unknown tags are `Default`.
+    pub fn from_u32(n: u32) -> Color {
+        match n >> 24 {
+            1 => Color::Indexed((n & 0xFF) as u8),
+            2 => Color::Rgb(((n >> 16) & 0xFF) as u8,
+                            ((n >> 8) & 0xFF) as u8,
+                            (n & 0xFF) as u8),
+            _ => Color::Default,
+        }
+    }
+
+    /// SGR parameters, `base` is 38 for foreground and 48 for background.
+    fn sgr(&self, base: u8, mode: ColorMode) -> Option<String> {
+        match (*self, mode) {
+            (Color::Default, _) => None,
+            (Color::Indexed(n), _) => Some(format!("{};5;{}", base, n)),
+            (Color::Rgb(r, g, b), ColorMode::TrueColor) => Some(format!("{};2;{};{};{}", base, r, g, b)),
+            (Color::Rgb(r, g, b), _) => Some(format!("{};5;{}", base, rgb_to_ansi256(r, g, b))),
+        }
+    }
+}
+
+/// Nearest color in the 6x6x6 cube of the 256-color palette.
+pub fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |c: u8| ((c as u32 * 5 + 127) / 255) as u8;
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+impl Style {
+    pub fn new(fg: Color, bg: Color, attrs: u8) -> Style {
+        Style {
+            fg: fg,
+            bg: bg,
+            bold: attrs & ATTR_BOLD != 0,
+            underline: attrs & ATTR_UNDERLINE != 0,
+        }
+    }
+
+    pub fn attrs(&self) -> u8 {
+        let mut attrs = 0;
+        if self.bold { attrs |= ATTR_BOLD; }
+        if self.underline { attrs |= ATTR_UNDERLINE; }
+        attrs
+    }
+
+    /// The escape sequence which switches the terminal to this style. It
+    /// starts with a reset, so it doesn't depend on the previous style.
+    pub fn to_sgr(&self, mode: ColorMode) -> String {
+        let mut params: Vec<String> = vec![String::from("0")];
+
+        if self.bold { params.push(String::from("1")); }
+        if self.underline { params.push(String::from("4")); }
+        if let Some(p) = self.fg.sgr(38, mode) { params.push(p); }
+        if let Some(p) = self.bg.sgr(48, mode) { params.push(p); }
+
+        format!("\x1b[{}m", params.join(";"))
+    }
+}
+
+impl Cell {
+    pub fn new(ch: char, style: Style) -> Cell {
+        Cell {
+            ch: ch,
+            style: style,
+        }
+    }
+}
+
+/// Writes the cells as text, with an escape sequence only where the style
+/// changes. The style is reset at the end if it was changed. `NoColor` writes
+/// only the chars.
+pub fn cells_to_string(cells: &[Cell], mode: ColorMode) -> String {
+    let mut text = String::new();
+
+    if mode == ColorMode::NoColor {
+        text.extend(cells.iter().map(|c| c.ch));
+        return text;
+    }
+
+    let default_style = Style::default();
+    let mut current = default_style;
+
+    for cell in cells.iter() {
+        if cell.style != current {
+            text.push_str(&cell.style.to_sgr(mode));
+            current = cell.style;
+        }
+        text.push(cell.ch);
+    }
+
+    if current != default_style {
+        text.push_str("\x1b[0m");
+    }
+
+    text
+}
@@ -1,36 +1,198 @@
 use std::str;
+use std::fmt;
+use std::io;
+use std::mem;
 use std::fs::File;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::error::Error;
-use std::io::Write;
-use std::convert::TryFrom;
+use std::cmp::Ordering;
 
-use serde_yaml;
+use serde_yaml::{self, Value};
 
-use jit::JitFn;
-use bytecode::Bytecode;
+use jit::{JitFn, pages_for};
+use sprite::{Sprite, SpriteTable, AnimMode, Facing, Edge, DEFAULT_MIRROR, mirror_char};
+use color::{Color, ColorMode, Style, Cell, cells_to_string};
+use layer::Layer;
+use track::{Track, Param};
+use var::{Var, Compare, MAX_VARS};
+use scene::{Scene, scene_end, active_scene, timeline_end};
+use transition::Blend;
+use random::Rng;
+use particle::Emitter;
+use shader::{self, Shader, shade_char};
+use font::{Font, Banner, banner_sprite};
+use post::PostFx;
+use collision::{Footprint, Overlap, Collision, any_overlap};
+use effect::{Star, ramp_char, plasma_level, fire_step, new_stars, star_position, sine_row};
+use bytecode::{Bytecode, VerifyError, verify, verify_scenes};
+use utils::file_to_string;
+use yml;
 
+/// Default canvas size, a single line of 50 chars.
 pub const BUFFER_SIZE: usize = 50;
+pub const DEFAULT_WIDTH: u16 = BUFFER_SIZE as u16;
+pub const DEFAULT_HEIGHT: u16 = 1;
+
+/// Layer number of the canvas in `SetLayer`, the layers in `Context.layers`
+/// are numbered from 1.
+pub const CANVAS_LAYER: u8 = 0;
 
 /// Holds the data we need to access when running the code.
 ///
 /// The `Context` and `Vec<Operator>` are private to make them only accessible
 /// through API calls which should remember to rebuild the `JitFn` as well.
-#[derive(Serialize, Deserialize)]
+///
+/// It is read from the YAML with `new_from_yml_str()`, which resolves the
+/// sprite, track and variable names.
+#[derive(Serialize)]
 pub struct Dmo {
     context: Context,
+    /// Run every frame, when there are no scenes
     operators: Vec<Operator>,
+    /// The timeline, see `scene::Scene`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    scenes: Vec<Scene>,
+    /// The demo stops at this global time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end: Option<f32>,
 
+    /// The scene of the last frame, see `.run_frame()`
+    #[serde(skip_serializing, skip_deserializing)]
+    current_scene: Option<usize>,
     #[serde(skip_serializing, skip_deserializing)]
     jit_fn: JitFn,
+    /// One for each scene
+    #[serde(skip_serializing, skip_deserializing)]
+    scene_fns: Vec<JitFn>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct Context {
-    pub sprites: Vec<String>,
+    /// Canvas width in chars
+    #[serde(default = "default_width")]
+    pub width: u16,
+    /// Canvas height in lines
+    #[serde(default = "default_height")]
+    pub height: u16,
+    /// Viewport width in chars, the part of the canvas which `Print` shows.
+    /// The canvas width if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view_width: Option<u16>,
+    /// Viewport height in lines, the canvas height if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view_height: Option<u16>,
+
+    /// How colors are printed, `NoColor` if not given
+    #[serde(default)]
+    pub color_mode: ColorMode,
+
+    /// A list in the YAML output. The YAML input can also be a map from
+    /// names to sprites, see `sprite::SpriteTable`, which is read into
+    /// `sprite_table` and moved here by `new_from_yml_str()`.
+    #[serde(skip_deserializing)]
+    pub sprites: Vec<Sprite>,
+    /// The `sprites` as they are read from the YAML, with their names
+    #[serde(rename = "sprites", default, skip_serializing)]
+    sprite_table: Option<SpriteTable>,
+
+    /// Char pairs which are swapped in mirrored sprites, such as `"<>"`. See
+    /// `sprite::DEFAULT_MIRROR` for the default.
+    #[serde(default = "default_mirror")]
+    pub mirror: Vec<String>,
+
+    /// Layers composited over the canvas, see `layer::Layer`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layers: Vec<Layer>,
+
+    /// Keyframed values for operator arguments, see `track::Track`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tracks: Vec<Track>,
+
+    /// Where the random numbers start, see `.rng`
+    #[serde(default)]
+    pub seed: u32,
+    /// Particle emitters, see `particle::Emitter`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub emitters: Vec<Emitter>,
+    /// Expressions which fill the canvas, see `shader::Shader`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shaders: Vec<Shader>,
+    /// Values which the operators change, see `var::Var`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vars: Vec<Var>,
+    /// FIGlet fonts, see `font::Font`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fonts: Vec<Font>,
+    /// Texts set in a font, for `Text`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub banners: Vec<Banner>,
+    /// Effects on the whole frame when it is printed, see `post::PostFx`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post: Vec<PostFx>,
 
+    /// The canvas, `width * height` cells, row by row.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub buffer: Vec<Cell>,
+    /// The style for `Draw` and `Clear`, set by `SetColor`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub pen: Style,
+    /// The current frame of each sprite, set by `Animate`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub frames: Vec<usize>,
+    /// Where `Draw` and `Clear` go, `CANVAS_LAYER` or a layer number, set by
+    /// `SetLayer`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub target: u8,
+    /// The value of each track at `.time`, set by `.update_tracks()`. The JIT
+    /// fn reads the values from here, so the buffer must not move after the
+    /// JIT fn is built.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub track_values: Vec<f32>,
+    /// The frame where the scene started, blank before the first scene. Set
+    /// by `.take_snapshot()`.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub snapshot: Vec<Cell>,
+    /// How the `.snapshot` is blended over the frame: blend, progress, seed.
+    /// Set by `Transition`, `None` when it is done.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub transition: Option<(Blend, f32, u32)>,
+    /// The random numbers, started from `.seed` by `.reset()`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub rng: Rng,
+    /// `width * height` cells of heat for `Fire`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub heat: Vec<u8>,
+    /// The stars of `Starfield`, made when the number of stars changes
+    #[serde(skip_serializing, skip_deserializing)]
+    pub stars: Vec<Star>,
+    /// `width * height` values of the last `Shade`. The JIT fn writes to
+    /// here, so the buffer must not move after the JIT fn is built.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub shade_values: Vec<f32>,
+    /// The value of each variable, started from `.vars` by `.reset()`. The
+    /// JIT fn reads and writes them at their offset from the context
+    /// pointer, see `.var_offset()`.
     #[serde(skip_serializing, skip_deserializing)]
-    pub buffer: Vec<char>,
+    pub var_values: [f32; MAX_VARS],
+    /// The `.banners` set in their font, made by `.reset()`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub banner_sprites: Vec<Sprite>,
+    /// The top left of the viewport on the canvas, column and row, set by
+    /// `Camera` and `Follow`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub camera: (i64, i64),
+    /// Where each sprite was drawn last, the column and row of its top left,
+    /// set by `Draw`. `Follow` goes there.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub drawn: Vec<Option<(i64, i64)>>,
+    /// Where each sprite was drawn during the frame, set by `Draw` and
+    /// cleared by `.start_frame()`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub footprints: Vec<Vec<Footprint>>,
+    /// The overlaps which `Collide` found during the frame, cleared by
+    /// `.start_frame()`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub collisions: Vec<Collision>,
     #[serde(skip_serializing, skip_deserializing)]
     pub is_running: bool,
     #[serde(skip_serializing, skip_deserializing)]
@@ -39,18 +201,419 @@ pub struct Context {
 
 /// Represents instructions which are executed by the JIT fn, which is assembled
 /// while iterating over a `Vec<Operator>`.
+///
+/// `S` is how sprites, tracks, variables, emitters, shaders and banners are
+/// referred to. It is the index, except in the YAML, where it can also be a
+/// name (`Operator<NameRef>`).
+///
+/// A `Param` argument is a number, a track or a variable.
 #[derive(Serialize, Deserialize)]
-pub enum Operator {
+pub enum Operator<S = u8> {
     /// No operation
     NOOP,
     /// Exit the main loop if time is greater than this value
-    Exit(f32),
+    Exit(Param<S>),
     /// Print the text buffer
     Print,
-    /// Draw a sprite into the buffer: sprite idx, offset, time speed
-    Draw(u8, u8, f32),
+    /// Draw a sprite into the buffer: sprite idx, x, y, time speed, facing,
+    /// edge mode. The YAML can leave out the arguments which came later,
+    /// see `fill_draw_defaults()`.
+    Draw(S, Param<S>, Param<S>, Param<S>, Facing, Edge),
     /// Clear the text buffer with a character code, expect UTF-32 unicode
     Clear(u32),
+    /// Set the style for the next draws and clears: foreground, background,
+    /// attribute bits (1: bold, 2: underline)
+    SetColor(Color, Color, u8),
+    /// Select the frame of an animated sprite from the time: sprite idx,
+    /// frames per second, mode
+    Animate(S, f32, AnimMode),
+    /// Send the next draws and clears to a layer, 0 is the canvas
+    SetLayer(u8),
+    /// Make every cell of a layer transparent
+    ClearLayer(u8),
+    /// Move a layer from its scroll offset with the time: layer, x speed,
+    /// y speed
+    MoveLayer(u8, f32, f32),
+    /// Blend the frame where the scene started with the new frame over a
+    /// duration from the start of the scene: blend, duration in seconds,
+    /// seed of `Dissolve`
+    Transition(Blend, Param<S>, u32),
+    /// Spawn, move and draw the particles of an emitter: emitter idx, x and y
+    /// where the particles start
+    Emit(S, Param<S>, Param<S>),
+    /// Fill the layer with a plasma of sines: scale of the waves, speed
+    Plasma(Param<S>, Param<S>),
+    /// Fill the layer with fire, which rises from the bottom row: heat lost
+    /// per row, from 0 to 255
+    Fire(u8),
+    /// Fill the layer with stars flying closer: number of stars, speed in
+    /// depths per second
+    Starfield(u16, Param<S>),
+    /// Scroll the first line of a sprite from right to left along a sine
+    /// wave: sprite idx, speed in chars per second, amplitude in rows,
+    /// frequency in radians per column
+    SineScroll(S, Param<S>, f32, f32),
+    /// Fill the layer with the chars from a shader: shader idx
+    Shade(S),
+    /// Set a variable: var idx, value
+    Set(S, Param<S>),
+    /// Add to a variable: var idx, value
+    Add(S, Param<S>),
+    /// Multiply a variable: var idx, value
+    Mul(S, Param<S>),
+    /// Set a variable to the sine of a value: var idx, value in radians
+    Sin(S, Param<S>),
+    /// Jump over the next operators if the comparison of a variable with a
+    /// value holds: var idx, comparison, value, number of operators. The jump
+    /// stops at the end of the list.
+    Branch(S, Compare, Param<S>, u8),
+    /// Draw a banner, a text in a FIGlet font, into the buffer: banner idx,
+    /// x, y, speed in chars per second, edge mode
+    Text(S, Param<S>, Param<S>, Param<S>, Edge),
+    /// Move the viewport to show the canvas from a column and a row: x, y.
+    /// It stays on the canvas.
+    Camera(Param<S>, Param<S>),
+    /// Move the viewport so that a sprite is in the middle, where the last
+    /// `Draw` of it put it: sprite idx
+    Follow(S),
+    /// Set a variable to 1 if two sprites overlap in the draws of the frame
+    /// so far, else to 0, and keep the `Collision` for the host: sprite idx,
+    /// sprite idx, how they overlap, var idx
+    Collide(S, S, Overlap, S),
+}
+
+/// How an operator refers to a sprite, a track, a variable, an emitter, a
+/// shader or a banner in the YAML, by index or by name.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum NameRef {
+    Index(u8),
+    Name(String),
+}
+
+/// The YAML document as it is parsed, before the names are resolved.
+#[derive(Deserialize)]
+struct DmoYml {
+    context: Context,
+    #[serde(default)]
+    operators: Vec<Operator<NameRef>>,
+    #[serde(default)]
+    scenes: Vec<Scene<NameRef>>,
+    #[serde(default)]
+    end: Option<f32>,
+}
+
+/// An operator refers to a sprite, track or variable name which doesn't
+/// exist, or which is past the 256 that an operator can refer to.
+#[derive(Debug, PartialEq)]
+pub struct UnknownName {
+    /// The index of the scene, `None` for the top-level operators
+    pub scene: Option<usize>,
+    pub op_idx: usize,
+    /// `"sprite"`, `"track"`, `"variable"`, `"emitter"`, `"shader"` or
+    /// `"banner"`
+    pub kind: &'static str,
+    pub name: String,
+    /// The index of the name when it exists but is over 255
+    pub idx: Option<usize>,
+    /// The line of the operator in the YAML, counting from 1, if it could be
+    /// found
+    pub line: Option<usize>,
+}
+
+/// Every unknown name in the YAML, returned by `new_from_yml_str()`.
+#[derive(Debug, PartialEq)]
+pub struct UnknownNames(pub Vec<UnknownName>);
+
+impl fmt::Display for UnknownName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(line) = self.line {
+            try!(write!(f, "line {}: ", line));
+        }
+        if let Some(scene) = self.scene {
+            try!(write!(f, "scene {}: ", scene));
+        }
+        match self.idx {
+            Some(idx) => write!(f, "operator {}: {} \"{}\" has index {}, over {}",
+                                self.op_idx, self.kind, self.name, idx, u8::max_value()),
+            None => write!(f, "operator {}: unknown {} \"{}\"", self.op_idx, self.kind, self.name),
+        }
+    }
+}
+
+impl fmt::Display for UnknownNames {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines: Vec<String> = self.0.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl Error for UnknownNames {
+    fn description(&self) -> &str {
+        "unknown name in an operator"
+    }
+}
+
+/// Why `NameRef::to_idx()` can't give an index.
+#[derive(Debug, PartialEq)]
+pub enum NameError {
+    /// The name is not in the names
+    Unknown(String),
+    /// The name is there, but its index doesn't fit in a `u8`
+    OutOfRange(String, usize),
+}
+
+impl NameRef {
+    /// The index of the name in `names`. Indices are not checked, that is up
+    /// to `bytecode::verify()`.
+    pub fn to_idx(self, names: &Vec<String>) -> Result<u8, NameError> {
+        match self {
+            NameRef::Index(idx) => Ok(idx),
+            NameRef::Name(name) => match names.iter().position(|n| *n == name) {
+                Some(idx) if idx > u8::max_value() as usize => Err(NameError::OutOfRange(name, idx)),
+                Some(idx) => Ok(idx as u8),
+                None => Err(NameError::Unknown(name)),
+            },
+        }
+    }
+}
+
+impl Param<NameRef> {
+    /// Replaces the track or variable name with its index in `tracks` or
+    /// `vars`, see `NameRef::to_idx()`. Returns the kind and the error of the
+    /// name.
+    pub fn resolve(self, tracks: &Vec<String>, vars: &Vec<String>)
+                   -> Result<Param, (&'static str, NameError)> {
+        match self {
+            Param::Value(value) => Ok(Param::Value(value)),
+            Param::Track(arg) => arg.track.to_idx(tracks).map(Param::track).map_err(|n| ("track", n)),
+            Param::Var(arg) => arg.var.to_idx(vars).map(Param::var).map_err(|n| ("variable", n)),
+        }
+    }
+}
+
+/// The names that operators can refer to, in index order.
+#[derive(Default)]
+pub struct Names {
+    pub sprites: Vec<String>,
+    pub tracks: Vec<String>,
+    pub vars: Vec<String>,
+    pub emitters: Vec<String>,
+    pub shaders: Vec<String>,
+    pub banners: Vec<String>,
+}
+
+impl Names {
+    /// The names in the context. The sprites have no names in the context,
+    /// they come from `sprite::SpriteTable`.
+    pub fn new(context: &Context, sprites: Vec<String>) -> Names {
+        Names {
+            sprites: sprites,
+            tracks: context.tracks.iter().map(|t| t.name.clone()).collect(),
+            vars: context.vars.iter().map(|v| v.name.clone()).collect(),
+            emitters: context.emitters.iter().map(|e| e.name.clone()).collect(),
+            shaders: context.shaders.iter().map(|s| s.name.clone()).collect(),
+            banners: context.banners.iter().map(|b| b.name.clone()).collect(),
+        }
+    }
+}
+
+impl Operator<NameRef> {
+    /// Replaces the names with their index. Returns the kind and the error of
+    /// the name.
+    pub fn resolve(self, names: &Names) -> Result<Operator, (&'static str, NameError)> {
+        use self::Operator::*;
+        let sprite = |s: NameRef| s.to_idx(&names.sprites).map_err(|n| ("sprite", n));
+        let track = |p: Param<NameRef>| p.resolve(&names.tracks, &names.vars);
+        let var = |s: NameRef| s.to_idx(&names.vars).map_err(|n| ("variable", n));
+        let emitter = |s: NameRef| s.to_idx(&names.emitters).map_err(|n| ("emitter", n));
+        let shader = |s: NameRef| s.to_idx(&names.shaders).map_err(|n| ("shader", n));
+        let banner = |s: NameRef| s.to_idx(&names.banners).map_err(|n| ("banner", n));
+        let op = match self {
+            NOOP => NOOP,
+            Exit(limit) => Exit(try!(track(limit))),
+            Print => Print,
+            Draw(s, x, y, speed, facing, edge) =>
+                Draw(try!(sprite(s)), try!(track(x)), try!(track(y)), try!(track(speed)), facing, edge),
+            Clear(charcode) => Clear(charcode),
+            SetColor(fg, bg, attrs) => SetColor(fg, bg, attrs),
+            Animate(s, fps, mode) => Animate(try!(sprite(s)), fps, mode),
+            SetLayer(layer) => SetLayer(layer),
+            ClearLayer(layer) => ClearLayer(layer),
+            MoveLayer(layer, speed_x, speed_y) => MoveLayer(layer, speed_x, speed_y),
+            Transition(blend, duration, seed) => Transition(blend, try!(track(duration)), seed),
+            Emit(e, x, y) => Emit(try!(emitter(e)), try!(track(x)), try!(track(y))),
+            Plasma(scale, speed) => Plasma(try!(track(scale)), try!(track(speed))),
+            Fire(cooling) => Fire(cooling),
+            Starfield(count, speed) => Starfield(count, try!(track(speed))),
+            SineScroll(s, speed, amplitude, frequency) =>
+                SineScroll(try!(sprite(s)), try!(track(speed)), amplitude, frequency),
+            Shade(s) => Shade(try!(shader(s))),
+            Set(v, value) => Set(try!(var(v)), try!(track(value))),
+            Add(v, value) => Add(try!(var(v)), try!(track(value))),
+            Mul(v, value) => Mul(try!(var(v)), try!(track(value))),
+            Sin(v, value) => Sin(try!(var(v)), try!(track(value))),
+            Branch(v, compare, value, skip) => Branch(try!(var(v)), compare, try!(track(value)), skip),
+            Text(b, x, y, speed, edge) => Text(try!(banner(b)), try!(track(x)), try!(track(y)), try!(track(speed)), edge),
+            Camera(x, y) => Camera(try!(track(x)), try!(track(y))),
+            Follow(s) => Follow(try!(sprite(s))),
+            Collide(a, b, overlap, v) => Collide(try!(sprite(a)), try!(sprite(b)), overlap, try!(var(v))),
+        };
+        Ok(op)
+    }
+}
+
+/// The lines of the items of each `operators:` list in the YAML, counting
+/// from 1, with the indentation of the key.
+///
+/// `serde_yaml` doesn't tell where a value was, so this looks for the lines
+/// in the text. It only knows the block style: a list in the flow style,
+/// `operators: [ Print ]`, has no items, and an item which is a flow list
+/// across lines counts once. The lines are only hints for the errors, the
+/// callers drop them when the count doesn't match.
+fn operator_lists(text: &str) -> Vec<(usize, Vec<usize>)> {
+    let mut lists: Vec<(usize, Vec<usize>)> = vec![];
+    // key indentation, item indentation, item lines
+    let mut current: Option<(usize, Option<usize>, Vec<usize>)> = None;
+
+    for (n, line) in text.lines().enumerate() {
+        let trimmed = line.trim_left();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+        let is_item = trimmed == "-" || trimmed.starts_with("- ");
+
+        if let Some((key_indent, mut item_indent, mut items)) = current.take() {
+            if indent > key_indent || (is_item && indent == key_indent) {
+                if is_item {
+                    if item_indent.is_none() {
+                        item_indent = Some(indent);
+                    }
+                    if item_indent == Some(indent) {
+                        items.push(n + 1);
+                    }
+                }
+                current = Some((key_indent, item_indent, items));
+                continue;
+            }
+            lists.push((key_indent, items));
+        }
+
+        // the key can follow the dash of a list item, "- operators:"
+        let (key_indent, key) = if is_item {
+            let rest = trimmed[1..].trim_left();
+            (line.len() - rest.len(), rest)
+        } else {
+            (indent, trimmed)
+        };
+        if key.trim_right() == "operators:" {
+            current = Some((key_indent, None, vec![]));
+        }
+    }
+
+    if let Some((key_indent, _, items)) = current {
+        lists.push((key_indent, items));
+    }
+
+    lists
+}
+
+/// Resolves the names in a list of operators, see `Operator::resolve()`.
+/// `lines` are the lines of the operators in the YAML, they are not used when
+/// there is not one for each operator.
+fn resolve_operators(operators: Vec<Operator<NameRef>>,
+                     names: &Names,
+                     scene: Option<usize>,
+                     lines: &[usize],
+                     unknown: &mut Vec<UnknownName>) -> Vec<Operator> {
+    let mut resolved: Vec<Operator> = vec![];
+    let lines: &[usize] = if lines.len() == operators.len() { lines } else { &[] };
+
+    for (op_idx, op) in operators.into_iter().enumerate() {
+        match op.resolve(names) {
+            Ok(op) => resolved.push(op),
+            Err((kind, error)) => {
+                let (name, idx) = match error {
+                    NameError::Unknown(name) => (name, None),
+                    NameError::OutOfRange(name, idx) => (name, Some(idx)),
+                };
+                unknown.push(UnknownName {
+                    scene: scene,
+                    op_idx: op_idx,
+                    kind: kind,
+                    name: name,
+                    idx: idx,
+                    line: lines.get(op_idx).cloned(),
+                });
+            },
+        }
+    }
+
+    resolved
+}
+
+/// Fills in the arguments of `Draw` which the YAML left out, so that the
+/// demos written before they were added still load:
+///
+/// ```yaml
+/// operators:
+///   - Draw: [ 0, 5, 1.5 ]                   # sprite, x, speed
+///   - Draw: [ 0, 5, 2, 1.5 ]                # sprite, x, y, speed
+///   - Draw: [ 0, 5, 2, 1.5, Auto ]          # and the facing
+///   - Draw: [ 0, 5, 2, 1.5, Auto, Bounce ]  # and the edge mode
+/// ```
+///
+/// y is 0, the facing is `Right` and the edge mode is `Wrap`. Other lengths
+/// are left to the deserializer to report.
+fn fill_draw_defaults(doc: &mut Value) {
+    let map = match doc.as_mapping_mut() {
+        Some(map) => map,
+        None => return,
+    };
+
+    for (key, value) in map.iter_mut() {
+        match key.as_str() {
+            Some("operators") => fill_draw_defaults_in(value),
+            Some("scenes") => {
+                let scenes = match value.as_sequence_mut() {
+                    Some(scenes) => scenes,
+                    None => continue,
+                };
+                for scene in scenes.iter_mut() {
+                    let key = Value::String(String::from("operators"));
+                    if let Some(operators) = scene.as_mapping_mut().and_then(|s| s.get_mut(&key)) {
+                        fill_draw_defaults_in(operators);
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+fn fill_draw_defaults_in(operators: &mut Value) {
+    let operators = match operators.as_sequence_mut() {
+        Some(operators) => operators,
+        None => return,
+    };
+
+    let key = Value::String(String::from("Draw"));
+    for op in operators.iter_mut() {
+        let args = match op.as_mapping_mut().and_then(|op| op.get_mut(&key)).and_then(|a| a.as_sequence_mut()) {
+            Some(args) => args,
+            None => continue,
+        };
+        if args.len() == 3 {
+            args.insert(2, Value::F64(0.0));
+        }
+        if args.len() == 4 {
+            args.push(Value::String(String::from("Right")));
+        }
+        if args.len() == 5 {
+            args.push(Value::String(String::from("Wrap")));
+        }
+    }
 }
 
 impl Default for Dmo {
@@ -58,7 +621,11 @@ impl Default for Dmo {
         Dmo {
             context: Context::default(),
             operators: vec![],
+            scenes: vec![],
+            end: None,
+            current_scene: None,
             jit_fn: JitFn::default(),
+            scene_fns: vec![],
         }
     }
 }
@@ -66,24 +633,485 @@ impl Default for Dmo {
 impl Default for Context {
     fn default() -> Context {
         Context {
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            view_width: None,
+            view_height: None,
+            color_mode: ColorMode::default(),
             sprites: vec![],
-            buffer: ['_'; BUFFER_SIZE].to_vec(),
+            sprite_table: None,
+            mirror: default_mirror(),
+            layers: vec![],
+            tracks: vec![],
+            seed: 0,
+            emitters: vec![],
+            shaders: vec![],
+            vars: vec![],
+            fonts: vec![],
+            banners: vec![],
+            post: vec![],
+            buffer: [Cell::new('_', Style::default()); BUFFER_SIZE].to_vec(),
+            pen: Style::default(),
+            frames: vec![],
+            target: CANVAS_LAYER,
+            track_values: vec![],
+            snapshot: vec![],
+            transition: None,
+            rng: Rng::default(),
+            heat: vec![],
+            stars: vec![],
+            shade_values: vec![],
+            var_values: [0.0; MAX_VARS],
+            banner_sprites: vec![],
+            camera: (0, 0),
+            drawn: vec![],
+            footprints: vec![],
+            collisions: vec![],
             is_running: true,
             time: 0.0,
         }
     }
 }
 
+/// `AnimMode` as passed to the JIT ops.
+pub fn anim_mode_to_code(mode: AnimMode) -> u8 {
+    match mode {
+        AnimMode::Loop     => 0x00,
+        AnimMode::PingPong => 0x01,
+        AnimMode::Once     => 0x02,
+    }
+}
+
+pub fn anim_mode_from_code(code: u8) -> io::Result<AnimMode> {
+    match code {
+        0x00 => Ok(AnimMode::Loop),
+        0x01 => Ok(AnimMode::PingPong),
+        0x02 => Ok(AnimMode::Once),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                format!("invalid animation mode: {:#x}", code))),
+    }
+}
+
+pub fn facing_to_code(facing: Facing) -> u8 {
+    match facing {
+        Facing::Right => 0x00,
+        Facing::Left  => 0x01,
+        Facing::Auto  => 0x02,
+    }
+}
+
+pub fn facing_from_code(code: u8) -> io::Result<Facing> {
+    match code {
+        0x00 => Ok(Facing::Right),
+        0x01 => Ok(Facing::Left),
+        0x02 => Ok(Facing::Auto),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                format!("invalid facing: {:#x}", code))),
+    }
+}
+
+pub fn edge_to_code(edge: Edge) -> u8 {
+    match edge {
+        Edge::Wrap   => 0x00,
+        Edge::Clip   => 0x01,
+        Edge::Bounce => 0x02,
+        Edge::Clamp  => 0x03,
+    }
+}
+
+pub fn edge_from_code(code: u8) -> io::Result<Edge> {
+    match code {
+        0x00 => Ok(Edge::Wrap),
+        0x01 => Ok(Edge::Clip),
+        0x02 => Ok(Edge::Bounce),
+        0x03 => Ok(Edge::Clamp),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                format!("invalid edge mode: {:#x}", code))),
+    }
+}
+
+pub fn overlap_to_code(overlap: Overlap) -> u8 {
+    match overlap {
+        Overlap::Bounds => 0x00,
+        Overlap::Cells  => 0x01,
+    }
+}
+
+pub fn overlap_from_code(code: u8) -> io::Result<Overlap> {
+    match code {
+        0x00 => Ok(Overlap::Bounds),
+        0x01 => Ok(Overlap::Cells),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                format!("invalid overlap: {:#x}", code))),
+    }
+}
+
+pub fn blend_to_code(blend: Blend) -> u8 {
+    match blend {
+        Blend::WipeLeft  => 0x00,
+        Blend::WipeRight => 0x01,
+        Blend::Dissolve  => 0x02,
+        Blend::Fade      => 0x03,
+    }
+}
+
+pub fn blend_from_code(code: u8) -> io::Result<Blend> {
+    match code {
+        0x00 => Ok(Blend::WipeLeft),
+        0x01 => Ok(Blend::WipeRight),
+        0x02 => Ok(Blend::Dissolve),
+        0x03 => Ok(Blend::Fade),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData,
+                                format!("invalid blend: {:#x}", code))),
+    }
+}
+
+fn default_mirror() -> Vec<String> {
+    DEFAULT_MIRROR.iter().map(|p| String::from(*p)).collect()
+}
+
+fn default_width() -> u16 {
+    DEFAULT_WIDTH
+}
+
+fn default_height() -> u16 {
+    DEFAULT_HEIGHT
+}
+
 impl Context {
     pub fn new() -> Context {
         Context::default()
     }
 
-    /// Prints the text buffer, followed by a `\r` (rewind)
+    /// Sets the canvas size and resets the runtime state, see `.reset()`.
+    ///
+    /// A new size reallocates `.shade_values`, whose address and size are
+    /// in the JIT fn, so the JIT fn has to be built again after it.
+    pub fn set_size(&mut self, width: u16, height: u16) {
+        self.width = width;
+        self.height = height;
+        self.reset();
+    }
+
+    /// Allocates the buffer for the current `width` and `height`, and resets
+    /// the time. Fields which are not in the YAML or the bytecode are set up
+    /// here.
+    pub fn reset(&mut self) {
+        self.buffer = vec![Cell::new('_', Style::default()); self.width as usize * self.height as usize];
+        self.pen = Style::default();
+        self.frames = vec![];
+        self.target = CANVAS_LAYER;
+        for layer in self.layers.iter_mut() {
+            layer.reset(self.width, self.height);
+        }
+        self.snapshot = vec![Cell::new(' ', Style::default()); self.width as usize * self.height as usize];
+        self.transition = None;
+        for emitter in self.emitters.iter_mut() {
+            emitter.reset();
+        }
+        self.rng = Rng::new(self.seed);
+        self.heat = vec![0; self.width as usize * self.height as usize];
+        self.stars = vec![];
+        for shader in self.shaders.iter_mut() {
+            shader.reset();
+        }
+        self.update_shade_values();
+        self.reset_vars();
+        for font in self.fonts.iter_mut() {
+            font.reset();
+        }
+        let banner_sprites: Vec<Sprite> = self.banners.iter().map(|b| banner_sprite(b, &self.fonts)).collect();
+        self.banner_sprites = banner_sprites;
+        self.camera = (0, 0);
+        self.drawn = vec![];
+        self.start_frame();
+        self.is_running = true;
+        self.time = 0.0;
+        self.update_tracks();
+    }
+
+    /// Forgets the sprites drawn and the collisions of the last frame.
+    pub fn start_frame(&mut self) {
+        for footprints in self.footprints.iter_mut() {
+            footprints.clear();
+        }
+        self.collisions.clear();
+    }
+
+    /// Evaluates the tracks at the current `.time` into `.track_values`. The
+    /// buffer is only reallocated when the number of tracks changes.
+    ///
+    /// The JIT fn reads the values at their address from when it was built,
+    /// so it has to be built again after the number of tracks changes.
+    pub fn update_tracks(&mut self) {
+        if self.track_values.len() != self.tracks.len() {
+            self.track_values = vec![0.0; self.tracks.len()];
+        }
+        for (value, track) in self.track_values.iter_mut().zip(self.tracks.iter()) {
+            *value = track.value_at(self.time);
+        }
+    }
+
+    /// Allocates `.shade_values` for the canvas size. The buffer is only
+    /// reallocated when the size changes.
+    ///
+    /// The JIT fn writes `width * height` values at the address of the
+    /// buffer from when it was built, see `JitMemory::shade_loop()`, so it
+    /// has to be built again after the size changes.
+    pub fn update_shade_values(&mut self) {
+        let n_cells = self.width as usize * self.height as usize;
+        if self.shade_values.len() != n_cells {
+            self.shade_values = vec![0.0; n_cells];
+        }
+    }
+
+    /// Reads the `file` of the fonts which have no `flf` text yet. Relative
+    /// paths are relative to `dir`.
+    pub fn load_fonts(&mut self, dir: &Path) -> Result<(), Box<Error>> {
+        for font in self.fonts.iter_mut() {
+            if let Some(ref file) = font.file {
+                if font.flf.is_empty() {
+                    let path = dir.join(file);
+                    font.flf = match file_to_string(&path) {
+                        Ok(text) => text,
+                        Err(e) => return Err(From::from(format!("font \"{}\": {}: {}", font.name, path.display(), e))),
+                    };
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the variables to their start values, the others to 0.
+    pub fn reset_vars(&mut self) {
+        self.var_values = [0.0; MAX_VARS];
+        for (value, var) in self.var_values.iter_mut().zip(self.vars.iter()) {
+            *value = var.kind.store(var.value);
+        }
+    }
+
+    /// The value of an operator argument, the number, the track value from
+    /// the last `.update_tracks()`, or the variable. Unknown tracks and
+    /// variables are 0.
+    pub fn param_value(&self, param: Param) -> f32 {
+        match param {
+            Param::Value(value) => value,
+            Param::Track(arg) => self.track_values.get(arg.track as usize).cloned().unwrap_or(0.0),
+            Param::Var(arg) => self.var_value(arg.var),
+        }
+    }
+
+    /// The value of a variable, 0 if it is not there.
+    pub fn var_value(&self, var: u8) -> f32 {
+        self.var_values.get(var as usize).cloned().unwrap_or(0.0)
+    }
+
+    /// Where the value of a variable is in memory, in bytes from the start of
+    /// the context. `None` if the index is not less than `MAX_VARS`.
+    pub fn var_offset(&self, var: u8) -> Option<u32> {
+        self.var_values.get(var as usize).map(|value| {
+            (value as *const f32 as usize - self as *const Context as usize) as u32
+        })
+    }
+
+    /// Runs the operators one by one, without the JIT. This does the same as
+    /// the JIT fn, which is useful for testing and for platforms the JIT
+    /// doesn't support.
+    pub fn run_operators(&mut self, operators: &Vec<Operator>) {
+        self.update_tracks();
+
+        // operators left to jump over, see `Branch`
+        let mut skip = 0;
+
+        for op in operators.iter() {
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+
+            use self::Operator::*;
+            match *op {
+                NOOP => {},
+                Exit(limit) => {
+                    let limit = self.param_value(limit);
+                    self.impl_exit(limit);
+                },
+                Print => self.impl_print(),
+                Draw(sprite_idx, x, y, speed, facing, edge) => {
+                    let x = self.param_value(x);
+                    let y = self.param_value(y);
+                    let speed = self.param_value(speed);
+                    self.impl_draw(sprite_idx, x, y.floor() as i32, facing_to_code(facing), edge_to_code(edge), speed);
+                },
+                Clear(charcode) => self.impl_clear(charcode),
+                SetColor(fg, bg, attrs) => self.impl_set_color(fg.to_u32(), bg.to_u32(), attrs),
+                Animate(sprite_idx, fps, mode) => self.impl_animate(sprite_idx, anim_mode_to_code(mode), fps),
+                SetLayer(layer) => self.impl_set_layer(layer),
+                ClearLayer(layer) => self.impl_clear_layer(layer),
+                MoveLayer(layer, speed_x, speed_y) => self.impl_move_layer(layer, speed_x, speed_y),
+                Transition(blend, duration, seed) => {
+                    let duration = self.param_value(duration);
+                    self.impl_transition(blend_to_code(blend), seed, duration);
+                },
+                Emit(emitter, x, y) => {
+                    let x = self.param_value(x);
+                    let y = self.param_value(y);
+                    self.impl_emit(emitter, x, y);
+                },
+                Plasma(scale, speed) => {
+                    let scale = self.param_value(scale);
+                    let speed = self.param_value(speed);
+                    self.impl_plasma(scale, speed);
+                },
+                Fire(cooling) => self.impl_fire(cooling),
+                Starfield(count, speed) => {
+                    let speed = self.param_value(speed);
+                    self.impl_starfield(count, speed);
+                },
+                SineScroll(sprite_idx, speed, amplitude, frequency) => {
+                    let speed = self.param_value(speed);
+                    self.impl_sine_scroll(sprite_idx, speed, amplitude, frequency);
+                },
+                Shade(shader) => {
+                    self.eval_shader(shader);
+                    self.impl_shade(shader);
+                },
+                Set(var, value) => {
+                    let value = self.param_value(value);
+                    self.impl_set_var(var, value);
+                },
+                Add(var, value) => {
+                    let value = self.var_value(var) + self.param_value(value);
+                    self.impl_set_var(var, value);
+                },
+                Mul(var, value) => {
+                    let value = self.var_value(var) * self.param_value(value);
+                    self.impl_set_var(var, value);
+                },
+                Sin(var, value) => {
+                    let value = shader::sin(self.param_value(value));
+                    self.impl_set_var(var, value);
+                },
+                Branch(var, compare, value, n) => {
+                    if compare.holds(self.var_value(var), self.param_value(value)) {
+                        skip = n;
+                    }
+                },
+                Text(banner, x, y, speed, edge) => {
+                    let x = self.param_value(x);
+                    let y = self.param_value(y);
+                    let speed = self.param_value(speed);
+                    self.impl_text(banner, x, y.floor() as i32, edge_to_code(edge), speed);
+                },
+                Camera(x, y) => {
+                    let x = self.param_value(x);
+                    let y = self.param_value(y);
+                    self.impl_camera(x, y);
+                },
+                Follow(sprite_idx) => self.impl_follow(sprite_idx),
+                Collide(a, b, overlap, var) => self.impl_collide(a, b, overlap_to_code(overlap), var),
+            }
+        }
+    }
+
+    /// The canvas with the visible layers over it, in the order of their `z`,
+    /// blended with the `.snapshot` during a `Transition`.
+    pub fn composite(&self) -> Vec<Cell> {
+        let mut cells = self.buffer.clone();
+
+        let mut layers: Vec<&Layer> = self.layers.iter().filter(|l| l.visible).collect();
+        // stable, so equal z keeps the list order
+        layers.sort_by_key(|l| l.z);
+
+        let width = self.width as usize;
+        for layer in layers.iter() {
+            for (n, cell) in cells.iter_mut().enumerate() {
+                if let Some(c) = layer.cell_at(n / width, n % width, self.width, self.height) {
+                    *cell = c;
+                }
+            }
+        }
+
+        if let Some((blend, progress, seed)) = self.transition {
+            if self.snapshot.len() == cells.len() {
+                cells = blend.blend(&self.snapshot, &cells, self.width, progress, seed);
+            }
+        }
+
+        cells
+    }
+
+    /// Keeps the frame as it is shown now for the next `Transition`, and ends
+    /// the current one.
+    pub fn take_snapshot(&mut self) {
+        self.snapshot = self.composite();
+        self.transition = None;
+    }
+
+    /// The viewport size, width and height, which is not larger than the
+    /// canvas.
+    pub fn view_size(&self) -> (u16, u16) {
+        (self.view_width.unwrap_or(self.width).min(self.width),
+         self.view_height.unwrap_or(self.height).min(self.height))
+    }
+
+    /// The cells of the canvas under the viewport, row by row.
+    pub fn viewport(&self, cells: &[Cell]) -> Vec<Cell> {
+        let (view_width, view_height) = self.view_size();
+        let (left, top) = (self.camera.0 as usize, self.camera.1 as usize);
+        let width = self.width as usize;
+
+        let mut view: Vec<Cell> = Vec::with_capacity(view_width as usize * view_height as usize);
+        for row in top .. top + view_height as usize {
+            let start = row * width + left;
+            view.extend_from_slice(&cells[start .. start + view_width as usize]);
+        }
+        view
+    }
+
+    /// The viewport after the `.post` effects, in order.
+    pub fn post_process(&self, cells: Vec<Cell>) -> Vec<Cell> {
+        let (width, height) = self.view_size();
+        let (width, height) = (width as usize, height as usize);
+        let mut cells = cells;
+        for fx in self.post.iter() {
+            cells = fx.apply(&cells, width, height, self.time, &self.mirror);
+        }
+        cells
+    }
+
+    /// Formats the viewport of the canvas and the layers for the terminal,
+    /// after the `.post` effects.
+    ///
+    /// A single line is followed by a `\r` (rewind), so the next frame
+    /// overwrites it. Multiple lines start with a cursor-home escape code and
+    /// each line is followed by a newline.
+    ///
+    /// Colors are written according to `.color_mode`, see
+    /// `color::cells_to_string()`.
+    pub fn render_frame(&self) -> String {
+        let (width, height) = self.view_size();
+        let width = width as usize;
+        let cells = self.post_process(self.viewport(&self.composite()));
+
+        if height <= 1 {
+            let s = cells_to_string(&cells, self.color_mode);
+            return format!("     {}\r", s);
+        }
+
+        let mut text = String::from("\x1b[H");
+        for row in cells.chunks(width) {
+            let s = cells_to_string(row, self.color_mode);
+            text.push_str("     ");
+            text.push_str(&s);
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Prints the frame, see `.render_frame()`.
     pub fn impl_print(&self) {
-        let s: String = self.buffer.iter().cloned().collect();
-        let text = format!("     {}\r", s);
-        print!("{}", text)
+        print!("{}", self.render_frame())
     }
 
     /// `.is_running` is the break condition for the main drawing loop. This
@@ -94,32 +1122,410 @@ impl Context {
         }
     }
 
-    /// Write a text sprite into the buffer, starting at `offset` and moving
-    /// with `speed`.
-    pub fn impl_draw(&mut self, sprite_idx: u8, offset: u8, speed: f32) {
+    /// Write a text sprite into the current layer, its first line at row `y`,
+    /// starting at `x` and moving with `speed`, which can be negative. At the
+    /// ends of the row the lines wrap, are clipped, bounce or stop, see
+    /// `Edge`. Lines above the first row or below the last row are not drawn.
+    /// Transparent chars are skipped. Cells take the color from the sprite's
+    /// color map, or else the `.pen`.
+    ///
+    /// A mirrored sprite (see `Facing`) is flipped within its width, and the
+    /// chars are swapped according to the `.mirror` table.
+    ///
+    /// Where the sprite went is kept for `Follow` and `Collide`. Codes which
+    /// are not from `facing_to_code()` and `edge_to_code()` draw nothing.
+    pub fn impl_draw(&mut self, sprite_idx: u8, x: f32, y: i32, facing: u8, edge: u8, speed: f32) {
+        let (facing, edge) = match (facing_from_code(facing), edge_from_code(edge)) {
+            (Ok(facing), Ok(edge)) => (facing, edge),
+            _ => return,
+        };
         if (sprite_idx as usize) < self.sprites.len() {
+            let frame = self.frames.get(sprite_idx as usize).cloned().unwrap_or(0);
+            // taken out while the other fields are written
+            let sprites = mem::replace(&mut self.sprites, vec![]);
+            let (left, cells) = self.draw_sprite(&sprites[sprite_idx as usize], frame, x, y,
+                                                 facing, edge, speed);
+            let sprite_width = sprites[sprite_idx as usize].width() as i64;
+            let sprite_height = sprites[sprite_idx as usize].frame_lines(frame).len() as i64;
+            let bounds = (left, y as i64, left + sprite_width, y as i64 + sprite_height);
+            self.sprites = sprites;
+
+            if self.drawn.len() < self.sprites.len() {
+                self.drawn.resize(self.sprites.len(), None);
+            }
+            self.drawn[sprite_idx as usize] = Some((left, y as i64));
 
-            let total_offset: usize = ((offset as f32 + self.time * speed) % (self.buffer.len() as f32)) as usize;
+            if self.footprints.len() < self.sprites.len() {
+                self.footprints.resize(self.sprites.len(), vec![]);
+            }
+            self.footprints[sprite_idx as usize].push(Footprint { bounds: bounds, cells: cells });
+        }
+    }
+
+    /// Write a banner into the current layer, moving as in `.impl_draw()`.
+    /// Its spaces are transparent.
+    pub fn impl_text(&mut self, banner: u8, x: f32, y: i32, edge: u8, speed: f32) {
+        let edge = match edge_from_code(edge) {
+            Ok(edge) => edge,
+            Err(_) => return,
+        };
+        if (banner as usize) < self.banner_sprites.len() {
+            let sprites = mem::replace(&mut self.banner_sprites, vec![]);
+            self.draw_sprite(&sprites[banner as usize], 0, x, y, Facing::Right, edge, speed);
+            self.banner_sprites = sprites;
+        }
+    }
 
-            let ref sprite = self.sprites[sprite_idx as usize];
+    /// Draws a frame of a sprite, see `.impl_draw()`. Returns the column of
+    /// its left edge, and the cells it wrote.
+    fn draw_sprite(&mut self, sprite: &Sprite, frame: usize, x: f32, y: i32, facing: Facing, edge: Edge, speed: f32)
+                   -> (i64, Vec<usize>) {
+        let width = self.width as i64;
+        let height = self.height as usize;
 
-            let mut v: Vec<char> = vec![];
-            for ch in sprite.chars() { v.push(ch); }
+        let transparent = sprite.transparent();
+        let color_lines = sprite.color_lines();
+        let sprite_width = sprite.width();
 
-            for i in 0 .. v.len() {
-                let n = (total_offset + i) % self.buffer.len();
-                self.buffer[n] = v[i];
+        // floor(), so that negative positions are one step to the left
+        let pos = (x + self.time * speed).floor() as i64;
+        let (left, velocity) = edge.position(pos, speed, sprite_width as i64, width);
+        if width == 0 {
+            return (left, vec![]);
+        }
+
+        let mirrored = facing.is_mirrored(velocity);
+        let mut written: Vec<usize> = vec![];
+
+        for (i, line) in sprite.frame_lines(frame).iter().enumerate() {
+            let row = y as i64 + i as i64;
+            if row < 0 {
+                continue;
+            }
+            let row = row as usize;
+            if row >= height {
+                break;
+            }
+
+            for (j, ch) in line.iter().enumerate() {
+                if Some(*ch) == transparent {
+                    continue;
+                }
+                let mut style = self.pen;
+                if let Some(fg) = color_lines.get(i).and_then(|c| c.get(j)).and_then(|c| *c) {
+                    style.fg = fg;
+                }
+
+                let (col, ch) = if mirrored {
+                    (sprite_width - 1 - j, mirror_char(*ch, &self.mirror))
+                } else {
+                    (j, *ch)
+                };
+
+                let column = match edge {
+                    Edge::Wrap => (left + col as i64) % width,
+                    _ => left + col as i64,
+                };
+                if column < 0 || column >= width {
+                    continue;
+                }
+
+                let n = row * width as usize + column as usize;
+                let cell = Cell::new(ch, style);
+                match self.target {
+                    CANVAS_LAYER => self.buffer[n] = cell,
+                    t => self.layers[t as usize - 1].cells[n] = Some(cell),
+                }
+                written.push(n);
             }
         }
+
+        (left, written)
     }
 
-    /// Clear the buffer by filling it with a character code.
+    /// Clear the current layer by filling it with a character code, in the
+    /// style of the `.pen`. A code which is not a char clears nothing.
     pub fn impl_clear(&mut self, charcode: u32) {
-        let ch = TryFrom::try_from(charcode).unwrap();
-        for i in 0 .. self.buffer.len() {
-            self.buffer[i] = ch;
+        let ch = match ::std::char::from_u32(charcode) {
+            Some(ch) => ch,
+            None => return,
+        };
+        let cell = Cell::new(ch, self.pen);
+        match self.target {
+            CANVAS_LAYER => {
+                for i in 0 .. self.buffer.len() {
+                    self.buffer[i] = cell;
+                }
+            },
+            t => {
+                for c in self.layers[t as usize - 1].cells.iter_mut() {
+                    *c = Some(cell);
+                }
+            },
         }
     }
+
+    /// Sets the layer for `Draw` and `Clear`. Unknown layers select the
+    /// canvas.
+    pub fn impl_set_layer(&mut self, layer: u8) {
+        self.target = if (layer as usize) <= self.layers.len() { layer } else { CANVAS_LAYER };
+    }
+
+    /// Makes a layer transparent. The canvas has no transparency, it is
+    /// left as is.
+    pub fn impl_clear_layer(&mut self, layer: u8) {
+        if layer != CANVAS_LAYER && (layer as usize) <= self.layers.len() {
+            self.layers[layer as usize - 1].clear();
+        }
+    }
+
+    /// Moves a layer by `time * speed` from its scroll offset.
+    pub fn impl_move_layer(&mut self, layer: u8, speed_x: f32, speed_y: f32) {
+        if layer != CANVAS_LAYER && (layer as usize) <= self.layers.len() {
+            let offset = ((self.time * speed_x).floor() as i64,
+                          (self.time * speed_y).floor() as i64);
+            self.layers[layer as usize - 1].offset = offset;
+        }
+    }
+
+    /// Moves the viewport to the column `x` and the row `y`, as far as it
+    /// stays on the canvas.
+    pub fn impl_camera(&mut self, x: f32, y: f32) {
+        self.move_camera(x.floor() as i64, y.floor() as i64);
+    }
+
+    /// Moves the viewport so that the middle of the sprite is in its middle,
+    /// as far as it stays on the canvas. A sprite which wasn't drawn yet
+    /// leaves it where it is.
+    pub fn impl_follow(&mut self, sprite_idx: u8) {
+        let idx = sprite_idx as usize;
+        if let Some(&Some((left, top))) = self.drawn.get(idx) {
+            let frame = self.frames.get(idx).cloned().unwrap_or(0);
+            let sprite_width = self.sprites[idx].width() as i64;
+            let sprite_height = self.sprites[idx].frame_lines(frame).len() as i64;
+            let (view_width, view_height) = self.view_size();
+            self.move_camera(left + sprite_width / 2 - view_width as i64 / 2,
+                             top + sprite_height / 2 - view_height as i64 / 2);
+        }
+    }
+
+    /// Sets the variable to 1 if the sprites overlap, in any of their draws
+    /// since the start of the frame, else to 0. An overlap is kept in
+    /// `.collisions`.
+    pub fn impl_collide(&mut self, a: u8, b: u8, overlap: u8, var: u8) {
+        let overlap = match overlap_from_code(overlap) {
+            Ok(overlap) => overlap,
+            Err(_) => return,
+        };
+        let hit = match (self.footprints.get(a as usize), self.footprints.get(b as usize)) {
+            (Some(fa), Some(fb)) => any_overlap(fa, fb, a == b, overlap),
+            _ => false,
+        };
+        if hit {
+            self.collisions.push(Collision { a: a, b: b });
+        }
+        self.impl_set_var(var, if hit { 1.0 } else { 0.0 });
+    }
+
+    fn move_camera(&mut self, x: i64, y: i64) {
+        let (view_width, view_height) = self.view_size();
+        let max_x = self.width as i64 - view_width as i64;
+        let max_y = self.height as i64 - view_height as i64;
+        self.camera = (x.max(0).min(max_x), y.max(0).min(max_y));
+    }
+
+    /// Sets the current frame of the sprite, which `Draw` will use.
+    pub fn impl_animate(&mut self, sprite_idx: u8, mode: u8, fps: f32) {
+        let idx = sprite_idx as usize;
+        if idx < self.sprites.len() {
+            if self.frames.len() < self.sprites.len() {
+                self.frames.resize(self.sprites.len(), 0);
+            }
+            let n_frames = self.sprites[idx].frame_count();
+            if let Ok(mode) = anim_mode_from_code(mode) {
+                self.frames[idx] = mode.frame_at(self.time, fps, n_frames);
+            }
+        }
+    }
+
+    /// Starts or continues the blend from the `.snapshot`, with the progress
+    /// from the time and the `duration`. It is done when the time is over the
+    /// `duration`.
+    pub fn impl_transition(&mut self, blend: u8, seed: u32, duration: f32) {
+        let progress = if duration > 0.0 { self.time / duration } else { 1.0 };
+        self.transition = match blend_from_code(blend) {
+            Ok(blend) if progress < 1.0 => Some((blend, progress, seed)),
+            _ => None,
+        };
+    }
+
+    /// Updates the particles of an emitter, spawning at `x`, `y`, and draws
+    /// them into the current layer in the style of the `.pen`. Particles
+    /// outside of the canvas are not drawn.
+    pub fn impl_emit(&mut self, emitter: u8, x: f32, y: f32) {
+        let idx = emitter as usize;
+        if idx >= self.emitters.len() {
+            return;
+        }
+
+        let time = self.time;
+        self.emitters[idx].update(time, x, y, &mut self.rng);
+
+        let (width, height) = (self.width as i64, self.height as i64);
+        for particle in self.emitters[idx].particles.iter() {
+            let (px, py) = particle.position(time);
+            let (col, row) = (px.floor() as i64, py.floor() as i64);
+            if col < 0 || col >= width || row < 0 || row >= height {
+                continue;
+            }
+
+            let n = (row * width + col) as usize;
+            let cell = Cell::new(particle.glyph, self.pen);
+            match self.target {
+                CANVAS_LAYER => self.buffer[n] = cell,
+                t => self.layers[t as usize - 1].cells[n] = Some(cell),
+            }
+        }
+    }
+
+    /// Writes a cell into the current layer.
+    fn put_cell(&mut self, n: usize, cell: Cell) {
+        match self.target {
+            CANVAS_LAYER => self.buffer[n] = cell,
+            t => self.layers[t as usize - 1].cells[n] = Some(cell),
+        }
+    }
+
+    /// Fills the current layer with a plasma, see `effect::plasma_level()`,
+    /// in the style of the `.pen`.
+    pub fn impl_plasma(&mut self, scale: f32, speed: f32) {
+        let width = self.width as usize;
+        let t = self.time * speed;
+        for n in 0 .. width * self.height as usize {
+            let ch = ramp_char(plasma_level(n % width, n / width, scale, t));
+            let cell = Cell::new(ch, self.pen);
+            self.put_cell(n, cell);
+        }
+    }
+
+    /// Moves the fire one step up, see `effect::fire_step()`, and fills the
+    /// current layer with it in the style of the `.pen`. The fire moves with
+    /// the frames, not with the time.
+    pub fn impl_fire(&mut self, cooling: u8) {
+        let (width, height) = (self.width as usize, self.height as usize);
+        fire_step(&mut self.heat, width, height, cooling, &mut self.rng);
+        for n in 0 .. self.heat.len() {
+            let cell = Cell::new(ramp_char(self.heat[n] as f32 / 255.0), self.pen);
+            self.put_cell(n, cell);
+        }
+    }
+
+    /// Fills the current layer with blanks and stars in the style of the
+    /// `.pen`, see `effect::star_position()`. Closer stars are brighter.
+    pub fn impl_starfield(&mut self, count: u16, speed: f32) {
+        if self.stars.len() != count as usize {
+            self.stars = new_stars(count as usize, &mut self.rng);
+        }
+
+        let (width, height) = (self.width as i64, self.height as i64);
+        let blank = Cell::new(' ', self.pen);
+        for n in 0 .. (width * height) as usize {
+            self.put_cell(n, blank);
+        }
+
+        // far stars first, so that close ones are drawn over them
+        let mut stars: Vec<(f32, f32, f32)> = self.stars.iter()
+            .map(|star| star_position(star, self.time, speed))
+            .collect();
+        stars.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(Ordering::Equal));
+
+        for &(x, y, depth) in stars.iter() {
+            let col = ((x + 1.0) * width as f32 / 2.0).floor() as i64;
+            let row = ((y + 1.0) * height as f32 / 2.0).floor() as i64;
+            if col < 0 || col >= width || row < 0 || row >= height {
+                continue;
+            }
+            let ch = if depth < 0.3 { '*' } else if depth < 0.6 { '+' } else { '.' };
+            let cell = Cell::new(ch, self.pen);
+            self.put_cell((row * width + col) as usize, cell);
+        }
+    }
+
+    /// Draws the first line of a sprite into the current layer, coming in
+    /// from the right edge and going out at the left, each char on the row
+    /// of a sine wave around the middle row, see `effect::sine_row()`. It
+    /// starts over when it is out. Transparent chars are skipped.
+    pub fn impl_sine_scroll(&mut self, sprite_idx: u8, speed: f32, amplitude: f32, frequency: f32) {
+        let idx = sprite_idx as usize;
+        if idx >= self.sprites.len() {
+            return;
+        }
+
+        let frame = self.frames.get(idx).cloned().unwrap_or(0);
+        let line: Vec<char> = match self.sprites[idx].frame_lines(frame).into_iter().next() {
+            Some(line) => line,
+            None => return,
+        };
+        let transparent = self.sprites[idx].transparent();
+
+        let (width, height) = (self.width as i64, self.height as i64);
+        let length = width + line.len() as i64;
+        let offset = (self.time * speed).floor() as i64;
+        let left = width - (((offset % length) + length) % length);
+        let base = (height - 1) as f32 / 2.0;
+
+        for (i, ch) in line.iter().enumerate() {
+            let col = left + i as i64;
+            if Some(*ch) == transparent || col < 0 || col >= width {
+                continue;
+            }
+            let row = sine_row(base, amplitude, frequency, col as usize, self.time);
+            if row < 0 || row >= height {
+                continue;
+            }
+            let cell = Cell::new(*ch, self.pen);
+            self.put_cell((row * width + col) as usize, cell);
+        }
+    }
+
+    /// Evaluates a shader for every cell into `.shade_values`, without the
+    /// JIT.
+    pub fn eval_shader(&mut self, shader: u8) {
+        let width = self.width as usize;
+        let time = self.time;
+        if let Some(expr) = self.shaders.get(shader as usize).and_then(|s| s.expr.as_ref()) {
+            for (n, value) in self.shade_values.iter_mut().enumerate() {
+                *value = expr.eval((n % width) as f32, (n / width) as f32, time);
+            }
+        }
+    }
+
+    /// Fills the current layer with the chars of the `.shade_values`, see
+    /// `shader::shade_char()`, in the style of the `.pen`. The values are
+    /// from the JIT fn or `.eval_shader()`.
+    pub fn impl_shade(&mut self, shader: u8) {
+        if (shader as usize) >= self.shaders.len() || self.shade_values.len() != self.buffer.len() {
+            return;
+        }
+        for n in 0 .. self.shade_values.len() {
+            let cell = Cell::new(shade_char(self.shade_values[n]), self.pen);
+            self.put_cell(n, cell);
+        }
+    }
+
+    /// Writes a variable, rounded if it is an `Int`. Indices which are not
+    /// less than `MAX_VARS` are ignored.
+    pub fn impl_set_var(&mut self, var: u8, value: f32) {
+        let kind = self.vars.get(var as usize).map(|v| v.kind).unwrap_or_default();
+        if let Some(slot) = self.var_values.get_mut(var as usize) {
+            *slot = kind.store(value);
+        }
+    }
+
+    /// Sets the `.pen`, the colors are packed with `Color::to_u32()`.
+    pub fn impl_set_color(&mut self, fg: u32, bg: u32, attrs: u8) {
+        self.pen = Style::new(Color::from_u32(fg), Color::from_u32(bg), attrs);
+    }
 }
 
 impl Dmo {
@@ -128,33 +1534,208 @@ impl Dmo {
         Dmo {
             context: context,
             operators: operators,
+            scenes: vec![],
+            end: None,
+            current_scene: None,
             jit_fn: JitFn::default(),
+            scene_fns: vec![],
         }
     }
 
+    /// Adds the scenes and the end time. With scenes, the top-level
+    /// operators are not run.
+    pub fn with_timeline(mut self, scenes: Vec<Scene>, end: Option<f32>) -> Dmo {
+        self.scenes = scenes;
+        self.end = end;
+        self
+    }
+
     /// This must happen after `dmo` is assigned, so that the JIT is
     /// built with the pointer address of the new `dmo.context`.
-    pub fn build_jit_fn(&mut self) {
-        self.jit_fn = JitFn::new(1, &mut self.context, &self.operators);
+    ///
+    /// The operators are verified first, nothing is compiled if there are
+    /// errors.
+    pub fn build_jit_fn(&mut self) -> Result<(), Vec<VerifyError>> {
+        try!(self.verify());
+        // allocates the track values which the JIT fn reads
+        self.context.update_tracks();
+        self.context.update_shade_values();
+        let pages = pages_for(&self.context, &self.operators);
+        self.jit_fn = JitFn::new(pages, &mut self.context, &self.operators);
+
+        let mut scene_fns: Vec<JitFn> = vec![];
+        for scene in self.scenes.iter() {
+            let pages = pages_for(&self.context, &scene.operators);
+            scene_fns.push(JitFn::new(pages, &mut self.context, &scene.operators));
+        }
+        self.scene_fns = scene_fns;
+
+        Ok(())
     }
 
+    /// Checks the operators and the scenes against the context, see
+    /// `bytecode::verify()` and `bytecode::verify_scenes()`.
+    pub fn verify(&self) -> Result<(), Vec<VerifyError>> {
+        let mut errors: Vec<VerifyError> = vec![];
+        if let Err(e) = verify(&self.context, &self.operators) {
+            errors.extend(e);
+        }
+        if let Err(e) = verify_scenes(&self.context, &self.scenes, self.end) {
+            errors.extend(e);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Runs a frame without the JIT, see `Context::run_operators()`.
+    pub fn run_operators(&mut self) {
+        self.run_frame(false)
+    }
+
+    /// Runs a frame with the JIT fns from `.build_jit_fn()`.
     pub fn run_jit_fn(&mut self) {
-        self.jit_fn.run(&mut self.context)
+        self.run_frame(true)
+    }
+
+    /// Runs the operators, or the scene at the current time. This is the
+    /// sequencer: a scene runs with its local time, and when it exits, the
+    /// time skips to its end. The demo stops at the `end`, or when every
+    /// scene has ended. When the scene changes, the frame is kept for its
+    /// `Transition`.
+    fn run_frame(&mut self, jit: bool) {
+        self.context.start_frame();
+
+        if self.scenes.is_empty() {
+            if jit {
+                self.jit_fn.run(&mut self.context);
+            } else {
+                self.context.run_operators(&self.operators);
+            }
+        } else {
+            let time = self.context.time;
+
+            if let Some(idx) = active_scene(&self.scenes, time) {
+                // a new scene starts from the last frame of the one before
+                if self.current_scene.map_or(false, |current| current != idx) {
+                    self.context.take_snapshot();
+                }
+                self.current_scene = Some(idx);
+
+                self.context.time = time - self.scenes[idx].start;
+                self.context.is_running = true;
+
+                if jit {
+                    if let Some(jit_fn) = self.scene_fns.get(idx) {
+                        jit_fn.run(&mut self.context);
+                    }
+                } else {
+                    self.context.run_operators(&self.scenes[idx].operators);
+                }
+
+                let exited = !self.context.is_running;
+                self.context.is_running = true;
+                self.context.time = if exited { scene_end(&self.scenes, idx) } else { time };
+            }
+
+            if self.context.time >= timeline_end(&self.scenes) {
+                self.context.is_running = false;
+            }
+        }
+
+        if let Some(end) = self.end {
+            if self.context.time >= end {
+                self.context.is_running = false;
+            }
+        }
     }
 
+    /// Parses the YAML and resolves the names in the operators, see
+    /// `Names`. All unknown names are returned as `UnknownNames`.
+    ///
+    /// Font files are read relative to the current directory.
     pub fn new_from_yml_str(text: &str) -> Result<Dmo, Box<Error>> {
-        let dmo: Dmo = try!(serde_yaml::from_str(text));
+        Dmo::new_from_yml_str_in(text, Path::new("."))
+    }
+
+    /// Reads a YAML file, see `.new_from_yml_str()`. Font files are read
+    /// relative to the YAML file.
+    pub fn new_from_yml_file(path: &PathBuf) -> Result<Dmo, Box<Error>> {
+        let text = try!(file_to_string(path));
+        let dir = path.parent().unwrap_or(Path::new("."));
+        Dmo::new_from_yml_str_in(&text, dir)
+    }
+
+    fn new_from_yml_str_in(text: &str, dir: &Path) -> Result<Dmo, Box<Error>> {
+        let mut doc: Value = try!(serde_yaml::from_str(text));
+        fill_draw_defaults(&mut doc);
+        let mut yml: DmoYml = try!(serde_yaml::from_value(doc));
+        let (sprite_names, sprites) = match yml.context.sprite_table.take() {
+            Some(table) => table.into_parts(),
+            None => (vec![], vec![]),
+        };
+        let names = Names::new(&yml.context, sprite_names);
+
+        // the top-level list is not indented, the lists of the scenes follow
+        // in order, if there is one for each scene
+        let lists = operator_lists(text);
+        let top_lines: Vec<usize> = lists.iter().find(|l| l.0 == 0).map(|l| l.1.clone()).unwrap_or(vec![]);
+        let mut scene_lines: Vec<Vec<usize>> = lists.into_iter().filter(|l| l.0 > 0).map(|l| l.1).collect();
+        if scene_lines.len() != yml.scenes.len() {
+            scene_lines.clear();
+        }
+
+        let mut unknown: Vec<UnknownName> = vec![];
+
+        let operators = resolve_operators(yml.operators, &names, None, &top_lines, &mut unknown);
+
+        let mut scenes: Vec<Scene> = vec![];
+        for (idx, scene) in yml.scenes.into_iter().enumerate() {
+            let lines: &[usize] = scene_lines.get(idx).map(|l| l.as_slice()).unwrap_or(&[]);
+            let operators = resolve_operators(scene.operators, &names, Some(idx), lines, &mut unknown);
+            scenes.push(Scene::new(&scene.name, scene.start, scene.end, operators));
+        }
+
+        if !unknown.is_empty() {
+            return Err(Box::new(UnknownNames(unknown)));
+        }
+
+        let mut context = yml.context;
+        context.sprites = sprites;
+        try!(context.load_fonts(dir));
+        context.reset();
+
+        Ok(Dmo::new(context, operators).with_timeline(scenes, yml.end))
+    }
+
+    /// Serializes to the same YAML schema that `new_from_yml_str()` reads.
+    /// Returns an error when the text doesn't read back as the same YAML.
+    pub fn to_yml_string(&self) -> Result<String, Box<Error>> {
+        let value = try!(serde_yaml::to_value(self));
+        yml::to_yml_string(&value)
+    }
+
+    /// Decodes a bytecode file, i.e. what `write_to_blob()` wrote.
+    pub fn new_from_blob(path: &PathBuf) -> Result<Dmo, Box<Error>> {
+        let mut f: File = try!(File::open(&path));
+        let dmo = try!(Dmo::read_bytecode(&mut f));
         Ok(dmo)
     }
 
     pub fn write_to_blob(&self, path: &PathBuf) -> Result<(), Box<Error>> {
         let mut f: File = try!(File::create(&path));
-        let bytecode = self.to_bytecode();
-        f.write_all(bytecode.as_slice()).unwrap();
+        try!(self.write_bytecode(&mut f));
         Ok(())
     }
 
-    pub fn get_sprites(&self) -> &Vec<String> {
+    pub fn get_context(&self) -> &Context {
+        &self.context
+    }
+
+    pub fn get_sprites(&self) -> &Vec<Sprite> {
         &self.context.sprites
     }
 
@@ -162,6 +1743,44 @@ impl Dmo {
         &self.operators
     }
 
+    pub fn get_scenes(&self) -> &Vec<Scene> {
+        &self.scenes
+    }
+
+    pub fn get_end(&self) -> Option<f32> {
+        self.end
+    }
+
+    /// The overlaps which `Collide` found in the last frame, in the order
+    /// of the operators.
+    pub fn get_collisions(&self) -> &Vec<Collision> {
+        &self.context.collisions
+    }
+
+    /// Overrides the color mode of the YAML or bytecode, e.g. `NoColor` for a
+    /// dumb terminal.
+    pub fn set_color_mode(&mut self, mode: ColorMode) {
+        self.context.color_mode = mode;
+    }
+
+    /// Replaces the effects which are applied when a frame is printed, e.g.
+    /// `Ascii` for a terminal without Unicode. See `post::PostFx`.
+    pub fn set_post(&mut self, post: Vec<PostFx>) {
+        self.context.post = post;
+    }
+
+    /// Shows or hides the layer with this name. Returns `false` if there is
+    /// no such layer.
+    pub fn set_layer_visible(&mut self, name: &str, visible: bool) -> bool {
+        match self.context.layers.iter_mut().find(|l| l.name == name) {
+            Some(layer) => {
+                layer.visible = visible;
+                true
+            },
+            None => false,
+        }
+    }
+
     pub fn get_is_running(&self) -> bool {
         self.context.is_running
     }
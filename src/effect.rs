@@ -0,0 +1,87 @@
+use random::Rng;
+
+/// The chars of the effects, from empty to dense.
+pub const RAMP: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+/// A star of the `Starfield`: x and y from -1 to 1, and the depth where it
+/// starts, from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Star {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+/// The char of the `RAMP` at `level`, 0 is empty and 1 is dense.
+pub fn ramp_char(level: f32) -> char {
+    let last = RAMP.len() - 1;
+    let idx = if level <= 0.0 { 0 } else { (level * last as f32).round() as usize };
+    RAMP[if idx > last { last } else { idx }]
+}
+
+/// The `Plasma` at a cell, a sum of sines from 0 to 1. Rows count twice,
+/// as a char is about twice as tall as it is wide.
+pub fn plasma_level(col: usize, row: usize, scale: f32, t: f32) -> f32 {
+    let x = col as f32 * scale;
+    let y = row as f32 * scale * 2.0;
+    let v = (x + t).sin() +
+        ((y + t) / 2.0).sin() +
+        ((x + y + t) / 2.0).sin() +
+        ((x * x + y * y).sqrt() + t).sin();
+    (v + 4.0) / 8.0
+}
+
+/// One step of the `Fire`: the bottom row gets random heat, and each row
+/// above takes the average of the three cells below it and the one two rows
+/// below, minus the `cooling`. The heat rises over the steps.
+pub fn fire_step(heat: &mut Vec<u8>, width: usize, height: usize, cooling: u8, rng: &mut Rng) {
+    if width == 0 || height == 0 || heat.len() != width * height {
+        return;
+    }
+
+    let bottom = (height - 1) * width;
+    for col in 0 .. width {
+        heat[bottom + col] = rng.below(256) as u8;
+    }
+
+    // from the top, so that each row reads the rows below from the last step
+    for row in 0 .. height - 1 {
+        for col in 0 .. width {
+            let left = if col > 0 { col - 1 } else { col };
+            let right = if col + 1 < width { col + 1 } else { col };
+            let two_below = if row + 2 < height { row + 2 } else { row + 1 };
+
+            let sum = {
+                let below = |r: usize, c: usize| heat[r * width + c] as u32;
+                below(row + 1, left) + below(row + 1, col) + below(row + 1, right) + below(two_below, col)
+            };
+            let avg = sum / 4;
+            let value = if avg > cooling as u32 { avg - cooling as u32 } else { 0 };
+            heat[row * width + col] = value as u8;
+        }
+    }
+}
+
+/// `count` stars at random places.
+pub fn new_stars(count: usize, rng: &mut Rng) -> Vec<Star> {
+    (0 .. count).map(|_| Star {
+        x: rng.range(-1.0, 1.0),
+        y: rng.range(-1.0, 1.0),
+        z: rng.next_f32(),
+    }).collect()
+}
+
+/// Where a star of the `Starfield` is on the screen at `time`: x and y from
+/// -1 to 1 over the canvas, and the depth. The stars come closer with the
+/// `speed`, and start over far away when they pass the screen.
+pub fn star_position(star: &Star, time: f32, speed: f32) -> (f32, f32, f32) {
+    let z = star.z - time * speed;
+    let depth = 0.05 + (z - z.floor()) * 0.95;
+    (star.x / depth, star.y / depth, depth)
+}
+
+/// The row of the `SineScroll` at a column, from the middle `base` row. The
+/// wave moves one radian per second.
+pub fn sine_row(base: f32, amplitude: f32, frequency: f32, col: usize, time: f32) -> i64 {
+    (base + amplitude * (frequency * col as f32 + time).sin()).round() as i64
+}
@@ -0,0 +1,91 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::error::Error;
+
+use dmo::Dmo;
+
+/// For use in a `build.rs` script. Compiles a YAML demo to bytecode at build
+/// time and writes it to `$OUT_DIR`, with the same file name and a `.dmo`
+/// extension. Include it in the crate with `include_dmo!()`.
+///
+/// The path is relative to the crate root of the package being built. Cargo
+/// is told to re-run the build script when the YAML changes, so the `.dmo`
+/// can't go stale.
+///
+/// YAML errors and the errors from `Dmo::verify()` are printed with the path
+/// of the file, and the function panics, which fails the build.
+///
+/// ```no_run
+/// // build.rs
+/// extern crate fish_in_a_jit as fj;
+///
+/// fn main() {
+///     fj::embed::compile_dmo("../examples/fish-demo.yml");
+/// }
+/// ```
+pub fn compile_dmo(yml_path: &str) -> PathBuf {
+    println!("cargo:rerun-if-changed={}", yml_path);
+
+    match try_compile_dmo(yml_path) {
+        Ok(out_path) => out_path,
+        Err(e) => panic!("\n\n{}: {}\n\n", yml_path, e),
+    }
+}
+
+/// Same as `compile_dmo()`, but returns the error instead of panicking.
+pub fn try_compile_dmo(yml_path: &str) -> Result<PathBuf, Box<Error>> {
+    let out_dir = try!(env::var("OUT_DIR"));
+    compile_dmo_to(yml_path, Path::new(&out_dir))
+}
+
+/// Same as `try_compile_dmo()`, but writes to `out_dir` instead of
+/// `$OUT_DIR`.
+pub fn compile_dmo_to(yml_path: &str, out_dir: &Path) -> Result<PathBuf, Box<Error>> {
+    let manifest_dir = try!(env::var("CARGO_MANIFEST_DIR"));
+
+    let src = Path::new(&manifest_dir).join(yml_path);
+    let file_name = match src.file_stem() {
+        Some(stem) => PathBuf::from(stem).with_extension("dmo"),
+        None => return Err(From::from(format!("not a file path: {:?}", src))),
+    };
+    let out_path = out_dir.join(file_name);
+
+    let dmo = try!(compile_yml_to_blob(&src, &out_path));
+
+    // the fonts are read with the YAML, relative to it
+    let dir = src.parent().unwrap_or(Path::new("."));
+    for font in dmo.get_context().fonts.iter() {
+        if let Some(ref file) = font.file {
+            println!("cargo:rerun-if-changed={}", dir.join(file).display());
+        }
+    }
+
+    Ok(out_path)
+}
+
+/// Reads the YAML, verifies the operators, and writes the bytecode. Returns
+/// the demo as it was written.
+pub fn compile_yml_to_blob(src: &PathBuf, dst: &PathBuf) -> Result<Dmo, Box<Error>> {
+    let dmo = try!(Dmo::new_from_yml_file(src));
+
+    if let Err(errors) = dmo.verify() {
+        let msg: Vec<String> = errors.iter().map(|e| format!("{}", e)).collect();
+        return Err(From::from(msg.join("\n")));
+    }
+
+    try!(dmo.write_to_blob(dst));
+    Ok(dmo)
+}
+
+/// Includes the bytecode compiled by `embed::compile_dmo()` in a `build.rs`,
+/// as a `&'static [u8; N]`.
+///
+/// ```ignore
+/// let bytecode: &'static [u8] = include_dmo!("fish-demo.dmo");
+/// ```
+#[macro_export]
+macro_rules! include_dmo {
+    ($name:expr) => {
+        include_bytes!(concat!(env!("OUT_DIR"), "/", $name))
+    };
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use sprite::Sprite;
+
+/// The chars which every FIGlet font has in this order, after the header and
+/// the comments: ASCII 32 to 126, then the German chars.
+const REQUIRED_CHARS: [u32; 7] = [196, 214, 220, 228, 246, 252, 223];
+
+/// A FIGlet font, read from a `.flf` file. The `Text` operator draws a
+/// `Banner` in it.
+///
+/// In the YAML, the file is read when the demo is loaded, relative to the
+/// YAML file (see `Dmo::new_from_yml_file()`):
+///
+/// ```yaml
+/// fonts:
+///   - name: "tiny"
+///     file: "fonts/tiny.flf"
+/// banners:
+///   - name: "greetings"
+///     font: "tiny"
+///     text: "GREETINGS TO ALL FISH"
+/// ```
+///
+/// The text of the file is kept in `flf`, which is how it is written to the
+/// YAML and to the bytecode, so a player doesn't need the file. The font can
+/// also be given in `flf` instead of `file`.
+///
+/// The letters are set at their full width, FIGlet's kerning and smushing
+/// are not done.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Font {
+    pub name: String,
+    /// The path of the `.flf` file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// The text of the `.flf` file
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub flf: String,
+
+    /// The parsed `flf`, `None` if it has errors. Set by `.reset()`.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub glyphs: Option<Glyphs>,
+}
+
+/// A string set in a font. It is drawn as a sprite whose spaces are
+/// transparent.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Banner {
+    pub name: String,
+    /// The name of the font
+    pub font: String,
+    /// Can have multiple lines
+    pub text: String,
+}
+
+/// The letters of a font.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Glyphs {
+    /// Lines of each letter
+    pub height: usize,
+    /// Drawn as a space
+    pub hardblank: char,
+    pub chars: HashMap<char, Vec<Vec<char>>>,
+}
+
+impl Font {
+    pub fn new(name: &str, flf: &str) -> Font {
+        let mut font = Font {
+            name: String::from(name),
+            file: None,
+            flf: String::from(flf),
+            glyphs: None,
+        };
+        font.reset();
+        font
+    }
+
+    /// Parses the `flf` into `.glyphs`.
+    pub fn reset(&mut self) {
+        self.glyphs = parse_flf(&self.flf).ok();
+    }
+}
+
+impl Banner {
+    pub fn new(name: &str, font: &str, text: &str) -> Banner {
+        Banner {
+            name: String::from(name),
+            font: String::from(font),
+            text: String::from(text),
+        }
+    }
+}
+
+impl Glyphs {
+    /// Sets the text, `height` lines for each line of the text. Chars which
+    /// are not in the font are left out.
+    pub fn render(&self, text: &str) -> Vec<String> {
+        let mut lines: Vec<String> = vec![];
+
+        for text_line in text.lines() {
+            let mut rows: Vec<String> = vec![String::new(); self.height];
+            for ch in text_line.chars() {
+                let glyph = match self.chars.get(&ch) {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                for (row, glyph_row) in rows.iter_mut().zip(glyph.iter()) {
+                    for c in glyph_row.iter() {
+                        row.push(if *c == self.hardblank { ' ' } else { *c });
+                    }
+                }
+            }
+            lines.extend(rows.into_iter());
+        }
+
+        lines
+    }
+}
+
+/// The banner set in its font, with spaces as the transparent char. Empty if
+/// the font is not in `fonts` or doesn't parse.
+pub fn banner_sprite(banner: &Banner, fonts: &[Font]) -> Sprite {
+    let glyphs = fonts.iter().find(|f| f.name == banner.font).and_then(|f| f.glyphs.as_ref());
+    let lines = match glyphs {
+        Some(glyphs) => glyphs.render(&banner.text),
+        None => vec![],
+    };
+    Sprite::new(&lines.join("\n"), Some(' '))
+}
+
+/// Parses the text of a `.flf` file. The error tells at which line it went
+/// wrong, counting from 1.
+///
+/// A font can end after any letter. After the required letters, each letter
+/// starts with a line of its char code.
+pub fn parse_flf(text: &str) -> Result<Glyphs, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let error = |n: usize, message: &str| format!("line {}: {}", n + 1, message);
+
+    let header: Vec<&str> = match lines.first() {
+        Some(line) => line.split_whitespace().collect(),
+        None => return Err(error(0, "the file is empty")),
+    };
+    if header.len() < 6 || !header[0].starts_with("flf2a") || header[0].chars().count() != 6 {
+        return Err(error(0, "expected a header: flf2a, hardblank, height, baseline, max length, old layout, comment lines"));
+    }
+    let hardblank = header[0].chars().last().unwrap_or('$');
+    let height = match header[1].parse::<usize>() {
+        Ok(height) if height > 0 => height,
+        _ => return Err(error(0, &format!("invalid height \"{}\"", header[1]))),
+    };
+    let comment_lines = match header[5].parse::<usize>() {
+        Ok(n) => n,
+        Err(_) => return Err(error(0, &format!("invalid number of comment lines \"{}\"", header[5]))),
+    };
+
+    let mut chars: HashMap<char, Vec<Vec<char>>> = HashMap::new();
+    let mut n = 1 + comment_lines;
+    let mut required = (32 .. 127).chain(REQUIRED_CHARS.iter().cloned());
+
+    while n < lines.len() {
+        if lines[n].trim().is_empty() {
+            n += 1;
+            continue;
+        }
+
+        let code = match required.next() {
+            Some(code) => code as i64,
+            None => {
+                // "code comment", the code in decimal, 0x hex or 0 octal
+                let tag = lines[n].split_whitespace().next().unwrap_or("");
+                let code = try!(parse_code(tag).ok_or(error(n, &format!("invalid char code \"{}\"", tag))));
+                n += 1;
+                code
+            },
+        };
+
+        if n + height > lines.len() {
+            return Err(error(n, "the letter has too few lines"));
+        }
+        let glyph: Vec<Vec<char>> = lines[n .. n + height].iter().map(|line| strip_endmarks(line)).collect();
+        n += height;
+
+        // negative codes are not chars
+        let ch = if code >= 0 { ::std::char::from_u32(code as u32) } else { None };
+        if let Some(ch) = ch {
+            let width = glyph.iter().map(|row| row.len()).max().unwrap_or(0);
+            let glyph = glyph.into_iter().map(|mut row| {
+                while row.len() < width {
+                    row.push(' ');
+                }
+                row
+            }).collect();
+            chars.insert(ch, glyph);
+        }
+    }
+
+    Ok(Glyphs {
+        height: height,
+        hardblank: hardblank,
+        chars: chars,
+    })
+}
+
+/// A line of a letter without the trailing spaces and the endmarks, the last
+/// char repeated at the end.
+fn strip_endmarks(line: &str) -> Vec<char> {
+    let mut chars: Vec<char> = line.trim_right().chars().collect();
+    if let Some(endmark) = chars.last().cloned() {
+        while chars.last() == Some(&endmark) {
+            chars.pop();
+        }
+    }
+    chars
+}
+
+fn parse_code(tag: &str) -> Option<i64> {
+    let (negative, digits) = if tag.starts_with('-') { (true, &tag[1..]) } else { (false, tag) };
+    let value = if digits.starts_with("0x") || digits.starts_with("0X") {
+        i64::from_str_radix(&digits[2..], 16).ok()
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8).ok()
+    } else {
+        digits.parse::<i64>().ok()
+    };
+    value.map(|v| if negative { -v } else { v })
+}
@@ -0,0 +1,474 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::error::Error;
+use std::collections::BTreeMap;
+
+use inflate::inflate_bytes_zlib;
+use serde_yaml;
+
+use sprite::Sprite;
+use color::Color;
+use effect::RAMP;
+use yml;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// The 16 colors of the terminal, `Color::Indexed(0)` to `Color::Indexed(15)`,
+/// as xterm shows them.
+const ANSI_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+    (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+    (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+
+/// A decoded image, row by row, with 8 bits for each of red, green, blue and
+/// alpha.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[u8; 4]>,
+}
+
+/// How `image_to_sprite()` turns the pixels into chars.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conversion {
+    /// Width of the sprite in chars, 0 for one char per pixel. The number of
+    /// lines follows from the `aspect`.
+    pub columns: usize,
+    /// The width of a char divided by its height.
+    pub aspect: f32,
+    /// The chars from dark to light.
+    pub ramp: String,
+    /// Use the ramp from light to dark, for a light background.
+    pub invert: bool,
+    /// Add a color map with the nearest of the 16 terminal colors.
+    pub color: bool,
+    /// The transparent char of the sprite. Cells which are mostly
+    /// transparent in the image get this char.
+    pub transparent: Option<char>,
+}
+
+impl Default for Conversion {
+    fn default() -> Conversion {
+        Conversion {
+            columns: 0,
+            aspect: 0.5,
+            ramp: RAMP.iter().cloned().collect(),
+            invert: false,
+            color: false,
+            transparent: None,
+        }
+    }
+}
+
+/// Reads a PPM, PGM or PNG file, see `decode_image()`.
+pub fn read_image(path: &PathBuf) -> Result<Image, Box<Error>> {
+    let mut data: Vec<u8> = vec![];
+    let mut f = try!(File::open(path));
+    try!(f.read_to_end(&mut data));
+    match decode_image(&data) {
+        Ok(image) => Ok(image),
+        Err(e) => Err(From::from(format!("{:?}: {}", path, e))),
+    }
+}
+
+/// Decodes a PNG, or a PPM or PGM in the plain (`P3`, `P2`) or the binary
+/// (`P6`, `P5`) form, which is told from the first bytes.
+pub fn decode_image(data: &[u8]) -> Result<Image, String> {
+    if data.starts_with(&PNG_SIGNATURE) {
+        decode_png(data)
+    } else if data.len() >= 2 && data[0] == b'P' {
+        decode_pnm(data)
+    } else {
+        Err(String::from("not a PNG, PPM or PGM image"))
+    }
+}
+
+/// Decodes a PPM or a PGM. Samples are scaled from the max value of the
+/// header to 0 - 255, and the pixels are opaque.
+pub fn decode_pnm(data: &[u8]) -> Result<Image, String> {
+    let (channels, binary) = match data.get(.. 2) {
+        Some(b"P2") => (1, false),
+        Some(b"P3") => (3, false),
+        Some(b"P5") => (1, true),
+        Some(b"P6") => (3, true),
+        _ => return Err(String::from("not a PPM or PGM image, expected P2, P3, P5 or P6")),
+    };
+
+    let mut pos = 2;
+    let width = try!(pnm_number(data, &mut pos, "width"));
+    let height = try!(pnm_number(data, &mut pos, "height"));
+    let max = try!(pnm_number(data, &mut pos, "max value"));
+    if max == 0 || max > 65535 {
+        return Err(format!("invalid max value {}", max));
+    }
+    // a single whitespace byte ends the header of the binary forms
+    pos += 1;
+
+    // every sample takes at least a byte
+    let size = if binary && max > 255 { 2 } else { 1 };
+    let n_samples = width.checked_mul(height).and_then(|n| n.checked_mul(channels * size));
+    let n_samples = match n_samples {
+        Some(n) if pos + n <= data.len() => n / size,
+        _ => return Err(String::from("the image data is too short")),
+    };
+
+    let mut samples: Vec<usize> = Vec::with_capacity(n_samples);
+    if binary {
+        for i in 0 .. n_samples {
+            let at = pos + i * size;
+            samples.push(if size == 2 { (data[at] as usize) << 8 | data[at + 1] as usize } else { data[at] as usize });
+        }
+    } else {
+        for _ in 0 .. n_samples {
+            samples.push(try!(pnm_number(data, &mut pos, "sample")));
+        }
+    }
+
+    let scale = |v: usize| ((v.min(max) * 255 + max / 2) / max) as u8;
+    let pixels = samples.chunks(channels).map(|s| {
+        if channels == 1 {
+            [scale(s[0]), scale(s[0]), scale(s[0]), 255]
+        } else {
+            [scale(s[0]), scale(s[1]), scale(s[2]), 255]
+        }
+    }).collect();
+
+    Ok(Image {
+        width: width,
+        height: height,
+        pixels: pixels,
+    })
+}
+
+/// The next decimal number, after whitespace and `#` comments.
+fn pnm_number(data: &[u8], pos: &mut usize, what: &str) -> Result<usize, String> {
+    loop {
+        match data.get(*pos) {
+            Some(&b'#') => {
+                while *pos < data.len() && data[*pos] != b'\n' {
+                    *pos += 1;
+                }
+            },
+            Some(b) if (*b as char).is_whitespace() => *pos += 1,
+            _ => break,
+        }
+    }
+
+    let start = *pos;
+    while *pos < data.len() && (data[*pos] as char).is_digit(10) {
+        *pos += 1;
+    }
+    let digits = String::from_utf8_lossy(&data[start .. *pos]);
+    match digits.parse::<usize>() {
+        Ok(n) => Ok(n),
+        Err(_) => Err(format!("expected the {} at byte {}", what, start)),
+    }
+}
+
+/// Decodes a PNG of any color type and bit depth which is not interlaced.
+/// 16-bit samples are cut to 8 bits, and `tRNS` sets the alpha. The other
+/// ancillary chunks are skipped.
+pub fn decode_png(data: &[u8]) -> Result<Image, String> {
+    let mut pos = PNG_SIGNATURE.len();
+    let mut header: Option<(usize, usize, u8, u8)> = None;
+    let mut palette: Vec<[u8; 4]> = vec![];
+    let mut key: Option<Vec<u16>> = None;
+    let mut compressed: Vec<u8> = vec![];
+
+    loop {
+        if pos + 12 > data.len() {
+            return Err(String::from("the image ends before IEND"));
+        }
+        let len = be_u32(&data[pos ..]) as usize;
+        if pos + 12 + len > data.len() {
+            return Err(String::from("a chunk is longer than the image"));
+        }
+        let kind = &data[pos + 4 .. pos + 8];
+        let chunk = &data[pos + 8 .. pos + 8 + len];
+        if crc32(&data[pos + 4 .. pos + 8 + len]) != be_u32(&data[pos + 8 + len ..]) {
+            return Err(format!("bad CRC in the {} chunk", String::from_utf8_lossy(kind)));
+        }
+        pos += 12 + len;
+
+        match kind {
+            b"IHDR" => {
+                if len != 13 {
+                    return Err(String::from("invalid IHDR"));
+                }
+                if chunk[12] != 0 {
+                    return Err(String::from("interlaced PNGs are not supported"));
+                }
+                let (width, height) = (be_u32(chunk) as usize, be_u32(&chunk[4 ..]) as usize);
+                if width == 0 || height == 0 {
+                    return Err(format!("invalid size {}x{}", width, height));
+                }
+                header = Some((width, height, chunk[8], chunk[9]));
+            },
+            b"PLTE" => {
+                palette = chunk.chunks(3).filter(|c| c.len() == 3).map(|c| [c[0], c[1], c[2], 255]).collect();
+            },
+            b"tRNS" => {
+                match header {
+                    Some((_, _, _, 3)) => {
+                        for (entry, alpha) in palette.iter_mut().zip(chunk.iter()) {
+                            entry[3] = *alpha;
+                        }
+                    },
+                    _ => key = Some(chunk.chunks(2).filter(|c| c.len() == 2).map(|c| (c[0] as u16) << 8 | c[1] as u16).collect()),
+                }
+            },
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => {},
+        }
+    }
+
+    let (width, height, depth, color_type) = match header {
+        Some(header) => header,
+        None => return Err(String::from("no IHDR chunk")),
+    };
+    let channels = match (color_type, depth) {
+        (0, 1) | (0, 2) | (0, 4) | (0, 8) | (0, 16) => 1,
+        (2, 8) | (2, 16) => 3,
+        (3, 1) | (3, 2) | (3, 4) | (3, 8) => 1,
+        (4, 8) | (4, 16) => 2,
+        (6, 8) | (6, 16) => 4,
+        _ => return Err(format!("invalid color type {} with bit depth {}", color_type, depth)),
+    };
+
+    let raw = try!(inflate_bytes_zlib(&compressed));
+    let bits_per_pixel = channels * depth as usize;
+    let (stride, n_pixels) = match (width.checked_mul(bits_per_pixel), width.checked_mul(height)) {
+        (Some(bits), Some(n_pixels)) => ((bits + 7) / 8, n_pixels),
+        _ => return Err(format!("the image is too large: {}x{}", width, height)),
+    };
+    let bytes = try!(unfilter(&raw, stride, height, (bits_per_pixel + 7) / 8));
+
+    let max = (1u32 << depth) - 1;
+    let mut pixels: Vec<[u8; 4]> = Vec::with_capacity(n_pixels);
+    for row in bytes.chunks(stride) {
+        for x in 0 .. width {
+            // the samples as they are stored, for the tRNS key
+            let samples: Vec<u16> = (0 .. channels).map(|c| sample(row, x * channels + c, depth)).collect();
+            let to_u8 = |s: u16| (s as u32 * 255 / max) as u8;
+
+            let pixel = match color_type {
+                0 => [to_u8(samples[0]), to_u8(samples[0]), to_u8(samples[0]), 255],
+                2 => [to_u8(samples[0]), to_u8(samples[1]), to_u8(samples[2]), 255],
+                3 => match palette.get(samples[0] as usize) {
+                    Some(entry) => *entry,
+                    None => return Err(format!("palette index {} is out of range", samples[0])),
+                },
+                4 => [to_u8(samples[0]), to_u8(samples[0]), to_u8(samples[0]), to_u8(samples[1])],
+                _ => [to_u8(samples[0]), to_u8(samples[1]), to_u8(samples[2]), to_u8(samples[3])],
+            };
+
+            let keyed = match key {
+                Some(ref key) => color_type != 3 && *key == samples,
+                None => false,
+            };
+            pixels.push(if keyed { [pixel[0], pixel[1], pixel[2], 0] } else { pixel });
+        }
+    }
+
+    Ok(Image {
+        width: width,
+        height: height,
+        pixels: pixels,
+    })
+}
+
+/// Reverses the filter of each row, which is in its first byte. `bpp` is
+/// the number of bytes of a pixel, at least 1.
+fn unfilter(raw: &[u8], stride: usize, height: usize, bpp: usize) -> Result<Vec<u8>, String> {
+    // the rows fit in `raw`, so the offsets below don't overflow
+    match (stride + 1).checked_mul(height) {
+        Some(len) if len <= raw.len() => {},
+        _ => return Err(String::from("the image data is too short")),
+    }
+
+    let mut out: Vec<u8> = vec![0; stride * height];
+    for y in 0 .. height {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1 .. (y + 1) * (stride + 1)];
+        for x in 0 .. stride {
+            let a = if x >= bpp { out[y * stride + x - bpp] } else { 0 };
+            let b = if y > 0 { out[(y - 1) * stride + x] } else { 0 };
+            let c = if x >= bpp && y > 0 { out[(y - 1) * stride + x - bpp] } else { 0 };
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(format!("invalid filter {} in row {}", filter, y)),
+            };
+            out[y * stride + x] = line[x].wrapping_add(predicted);
+        }
+    }
+    Ok(out)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// The `idx`th sample of a row, samples of less than 8 bits are packed from
+/// the high bits, 16-bit samples are big-endian.
+fn sample(row: &[u8], idx: usize, depth: u8) -> u16 {
+    match depth {
+        16 => (row[idx * 2] as u16) << 8 | row[idx * 2 + 1] as u16,
+        8 => row[idx] as u16,
+        _ => {
+            let bit = idx * depth as usize;
+            let shift = 8 - depth as usize - bit % 8;
+            ((row[bit / 8] >> shift) & ((1 << depth) - 1)) as u16
+        },
+    }
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+/// The CRC-32 of the PNG chunks, over the type and the data.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data.iter() {
+        crc ^= *byte as u32;
+        for _ in 0 .. 8 {
+            crc = if crc & 1 != 0 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Draws the image with the chars of the ramp, from the luminance of the
+/// pixels. Each char is the average of the pixels it covers.
+pub fn image_to_sprite(image: &Image, conversion: &Conversion) -> Sprite {
+    let mut ramp: Vec<char> = conversion.ramp.chars().collect();
+    if ramp.is_empty() || image.width == 0 || image.height == 0 {
+        return Sprite::new("", conversion.transparent);
+    }
+    if conversion.invert {
+        ramp.reverse();
+    }
+
+    let columns = if conversion.columns == 0 { image.width } else { conversion.columns };
+    let rows = (image.height as f32 * columns as f32 / image.width as f32 * conversion.aspect).round().max(1.0) as usize;
+
+    // the char and the terminal color of each cell, `None` if transparent,
+    // in the image or because the ramp gives the transparent char
+    let mut cells: Vec<Vec<Option<(char, usize)>>> = vec![];
+    let mut used = [false; 16];
+    for row in 0 .. rows {
+        cells.push((0 .. columns).map(|col| {
+            let (r, g, b) = match cell_color(image, col, row, columns, rows) {
+                Some(color) => color,
+                None => return None,
+            };
+            let luminance = (0.2126 * r + 0.7152 * g + 0.0722 * b) / 255.0;
+            let idx = ((luminance * (ramp.len() - 1) as f32).round() as usize).min(ramp.len() - 1);
+            if Some(ramp[idx]) == conversion.transparent {
+                return None;
+            }
+            let ansi = nearest_ansi(r, g, b);
+            used[ansi] = true;
+            Some((ramp[idx], ansi))
+        }).collect());
+    }
+
+    let blank = conversion.transparent.unwrap_or(' ');
+    let lines: Vec<String> = cells.iter().map(|line| {
+        line.iter().map(|cell| cell.map(|c| c.0).unwrap_or(blank)).collect()
+    }).collect();
+    let sprite = Sprite::new(&lines.join("\n"), conversion.transparent);
+    if !conversion.color {
+        return sprite;
+    }
+
+    // the palette has the used colors in their order, each color is the hex
+    // digit of its place in the palette
+    let palette_idx: Vec<usize> = (0 .. 16).filter(|i| used[*i]).collect();
+    let colors: Vec<String> = cells.iter().map(|line| {
+        line.iter().map(|cell| match *cell {
+            Some((_, ansi)) => {
+                let digit = palette_idx.iter().position(|i| *i == ansi).unwrap_or(0);
+                ::std::char::from_digit(digit as u32, 16).unwrap_or(' ')
+            },
+            None => ' ',
+        }).collect()
+    }).collect();
+    let palette: Vec<Color> = palette_idx.iter().map(|i| Color::Indexed(*i as u8)).collect();
+
+    sprite.with_colors(&colors.join("\n"), palette)
+}
+
+/// The average color of the pixels under a char, `None` if less than half
+/// of them are opaque.
+fn cell_color(image: &Image, col: usize, row: usize, columns: usize, rows: usize) -> Option<(f32, f32, f32)> {
+    let x0 = col * image.width / columns;
+    let x1 = ((col + 1) * image.width / columns).max(x0 + 1);
+    let y0 = row * image.height / rows;
+    let y1 = ((row + 1) * image.height / rows).max(y0 + 1);
+
+    let (mut r, mut g, mut b) = (0.0, 0.0, 0.0);
+    let mut opaque = 0;
+    for y in y0 .. y1 {
+        for x in x0 .. x1 {
+            let p = image.pixels[y * image.width + x];
+            if p[3] >= 128 {
+                r += p[0] as f32;
+                g += p[1] as f32;
+                b += p[2] as f32;
+                opaque += 1;
+            }
+        }
+    }
+
+    let n = (x1 - x0) * (y1 - y0);
+    if opaque * 2 < n || opaque == 0 {
+        return None;
+    }
+    Some((r / opaque as f32, g / opaque as f32, b / opaque as f32))
+}
+
+fn nearest_ansi(r: f32, g: f32, b: f32) -> usize {
+    let distance = |c: &(u8, u8, u8)| {
+        let (dr, dg, db) = (c.0 as f32 - r, c.1 as f32 - g, c.2 as f32 - b);
+        dr * dr + dg * dg + db * db
+    };
+    let mut best = 0;
+    for (i, c) in ANSI_COLORS.iter().enumerate() {
+        if distance(c) < distance(&ANSI_COLORS[best]) {
+            best = i;
+        }
+    }
+    best
+}
+
+#[derive(Serialize)]
+struct SpritesYml<'a> {
+    sprites: BTreeMap<&'a str, &'a Sprite>,
+}
+
+/// A `sprites` map of the YAML with a single sprite, to paste into the
+/// `context` of a demo.
+pub fn sprite_yml_snippet(name: &str, sprite: &Sprite) -> Result<String, Box<Error>> {
+    let mut sprites = BTreeMap::new();
+    sprites.insert(name, sprite);
+    let value = try!(serde_yaml::to_value(&SpritesYml { sprites: sprites }));
+    yml::to_yml_string(&value)
+}
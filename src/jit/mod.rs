@@ -13,9 +13,12 @@ use winapi;
 use kernel32;
 
 pub mod ops;
+mod shader;
+mod var;
 
 use dmo::Operator as Op;
-use dmo::Context;
+use dmo::{Context, anim_mode_to_code, facing_to_code, edge_to_code, blend_to_code, overlap_to_code};
+use track::Param;
 
 use self::ops::Ops;
 
@@ -28,6 +31,11 @@ extern {
 // Allocate memory sizes as multiples of 4k page.
 const PAGE_SIZE: usize = 4096;
 
+// Upper bounds of the code size, for `pages_for()`
+const MAX_OP_SIZE: usize = 128;
+// a `sin` or `cos` call which keeps the xmm registers is the largest
+const MAX_SHADER_OP_SIZE: usize = 128;
+
 /// An executable memory buffer filled with `x86` instructions.
 pub struct JitFn {
     addr: *mut u8,
@@ -75,6 +83,19 @@ impl JitFn {
     }
 }
 
+/// Enough pages for the JIT fn of the operators.
+pub fn pages_for(context: &Context, operators: &Vec<Op>) -> usize {
+    let mut size = MAX_OP_SIZE * (operators.len() + 2);
+    for op in operators.iter() {
+        if let Op::Shade(idx) = *op {
+            if let Some(expr) = context.shaders.get(idx as usize).and_then(|s| s.expr.as_ref()) {
+                size += MAX_SHADER_OP_SIZE * expr.size();
+            }
+        }
+    }
+    size / PAGE_SIZE + 1
+}
+
 pub trait JitAssembler {
 
     /// Marks the memory block as executable and returns a `JitFn` containing
@@ -231,14 +252,30 @@ impl JitAssembler for JitMemory {
         self.push_rbp();
         self.mov_rbp_rsp();
 
-        for op in operators.iter() {
+        // evaluate the tracks for this frame, the ops read them from
+        // context.track_values
+        if !context.tracks.is_empty() {
+            self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+            self.movabs_rax_u64( unsafe { mem::transmute(
+                Ops::op_update_tracks as extern "sysv64" fn(&mut Context)
+            )});
+            self.call_rax();
+        }
+
+        // the branches which jump forward to an operator: operator index,
+        // where the jump offset goes
+        let mut jumps: Vec<(usize, usize)> = vec![];
+
+        for (op_idx, op) in operators.iter().enumerate() {
+            self.land_jumps(&mut jumps, op_idx);
+
             match *op {
                 Op::NOOP => (),
 
                 Op::Exit(limit) => {
                     // FIXME windows JIT craches without this println!()
                     #[cfg(target_os = "windows")]
-                    println!("Will exit after {} seconds", limit);
+                    println!("Will exit after {:?} seconds", limit);
 
                     // x86_64 ABI is sysv64, arguments are passed in registers, and
                     // remaining ones are passed on the stack.
@@ -251,7 +288,7 @@ impl JitAssembler for JitMemory {
                     self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
 
                     // xmm0: limit argument (floating point)
-                    self.movss_xmm_n_f32(0, limit);
+                    self.movss_xmm_n_param(0, limit, context);
 
                     // put the address of the function in rax
                     self.movabs_rax_u64( unsafe { mem::transmute(
@@ -284,18 +321,24 @@ impl JitAssembler for JitMemory {
                     self.call_rax();
                 },
 
-                Op::Draw(sprite_idx, offset, speed) => {
+                Op::Draw(sprite_idx, x, y, speed, facing, edge) => {
                     // rdi: pointer to Context (pointer is an integer value)
                     self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
                     // rsi: sprite_idx arg. (interger)
                     self.movabs_rsi_u64(sprite_idx as u64);
-                    // rdx: offset arg. (interger)
-                    self.movabs_rdx_u64(offset as u64);
-                    // xmm0: speed arg. (floating point)
-                    self.movss_xmm_n_f32(0, speed);
+                    // rdx: facing arg. (interger)
+                    self.movabs_rdx_u64(facing_to_code(facing) as u64);
+                    // rcx: edge mode arg. (interger)
+                    self.movabs_rcx_u64(edge_to_code(edge) as u64);
+                    // xmm0: x arg. (floating point)
+                    self.movss_xmm_n_param(0, x, context);
+                    // xmm1: y arg. (floating point)
+                    self.movss_xmm_n_param(1, y, context);
+                    // xmm2: speed arg. (floating point)
+                    self.movss_xmm_n_param(2, speed, context);
 
                     self.movabs_rax_u64( unsafe { mem::transmute(
-                        Ops::op_draw as extern "sysv64" fn(&mut Context, u8, u8, f32)
+                        Ops::op_draw as extern "sysv64" fn(&mut Context, u8, u8, u8, f32, f32, f32)
                     )});
                     self.call_rax();
                 },
@@ -311,9 +354,271 @@ impl JitAssembler for JitMemory {
                     )});
                     self.call_rax();
                 },
+
+                Op::SetColor(fg, bg, attrs) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: foreground color, packed (interger)
+                    self.movabs_rsi_u64(fg.to_u32() as u64);
+                    // rdx: background color, packed (interger)
+                    self.movabs_rdx_u64(bg.to_u32() as u64);
+                    // rcx: attribute bits (interger)
+                    self.movabs_rcx_u64(attrs as u64);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_set_color as extern "sysv64" fn(&mut Context, u32, u32, u8)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::Animate(sprite_idx, fps, mode) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: sprite_idx arg. (interger)
+                    self.movabs_rsi_u64(sprite_idx as u64);
+                    // rdx: mode arg. (interger)
+                    self.movabs_rdx_u64(anim_mode_to_code(mode) as u64);
+                    // xmm0: frames per second (floating point)
+                    self.movss_xmm_n_f32(0, fps);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_animate as extern "sysv64" fn(&mut Context, u8, u8, f32)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::SetLayer(layer) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: layer number (interger)
+                    self.movabs_rsi_u64(layer as u64);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_set_layer as extern "sysv64" fn(&mut Context, u8)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::ClearLayer(layer) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: layer number (interger)
+                    self.movabs_rsi_u64(layer as u64);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_clear_layer as extern "sysv64" fn(&mut Context, u8)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::MoveLayer(layer, speed_x, speed_y) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: layer number (interger)
+                    self.movabs_rsi_u64(layer as u64);
+                    // xmm0: x speed (floating point)
+                    self.movss_xmm_n_f32(0, speed_x);
+                    // xmm1: y speed (floating point)
+                    self.movss_xmm_n_f32(1, speed_y);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_move_layer as extern "sysv64" fn(&mut Context, u8, f32, f32)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::Transition(blend, duration, seed) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: blend arg. (interger)
+                    self.movabs_rsi_u64(blend_to_code(blend) as u64);
+                    // rdx: seed arg. (interger)
+                    self.movabs_rdx_u64(seed as u64);
+                    // xmm0: duration (floating point)
+                    self.movss_xmm_n_param(0, duration, context);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_transition as extern "sysv64" fn(&mut Context, u8, u32, f32)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::Emit(emitter, x, y) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: emitter idx (interger)
+                    self.movabs_rsi_u64(emitter as u64);
+                    // xmm0: x (floating point)
+                    self.movss_xmm_n_param(0, x, context);
+                    // xmm1: y (floating point)
+                    self.movss_xmm_n_param(1, y, context);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_emit as extern "sysv64" fn(&mut Context, u8, f32, f32)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::Plasma(scale, speed) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // xmm0: scale (floating point)
+                    self.movss_xmm_n_param(0, scale, context);
+                    // xmm1: speed (floating point)
+                    self.movss_xmm_n_param(1, speed, context);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_plasma as extern "sysv64" fn(&mut Context, f32, f32)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::Fire(cooling) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: cooling (interger)
+                    self.movabs_rsi_u64(cooling as u64);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_fire as extern "sysv64" fn(&mut Context, u8)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::Starfield(count, speed) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: number of stars (interger)
+                    self.movabs_rsi_u64(count as u64);
+                    // xmm0: speed (floating point)
+                    self.movss_xmm_n_param(0, speed, context);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_starfield as extern "sysv64" fn(&mut Context, u16, f32)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::SineScroll(sprite_idx, speed, amplitude, frequency) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: sprite_idx arg. (interger)
+                    self.movabs_rsi_u64(sprite_idx as u64);
+                    // xmm0: speed (floating point)
+                    self.movss_xmm_n_param(0, speed, context);
+                    // xmm1: amplitude (floating point)
+                    self.movss_xmm_n_f32(1, amplitude);
+                    // xmm2: frequency (floating point)
+                    self.movss_xmm_n_f32(2, frequency);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_sine_scroll as extern "sysv64" fn(&mut Context, u8, f32, f32, f32)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::Shade(idx) => {
+                    // the values of the cells, see shader.rs
+                    let expr = context.shaders.get(idx as usize).and_then(|s| s.expr.clone());
+                    if let Some(expr) = expr {
+                        self.shade_loop(&expr, context);
+                    }
+
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: shader idx (interger)
+                    self.movabs_rsi_u64(idx as u64);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_shade as extern "sysv64" fn(&mut Context, u8)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::Set(var, value) | Op::Add(var, value) | Op::Mul(var, value) | Op::Sin(var, value) => {
+                    // no call, the variable is at its offset from rdi, see
+                    // var.rs
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    self.update_var(op, var, value, context);
+                },
+
+                Op::Branch(var, compare, value, skip) => {
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    let target = op_idx + 1 + skip as usize;
+                    for at in self.branch(var, compare, value, context) {
+                        jumps.push((target, at));
+                    }
+                },
+
+                Op::Text(banner, x, y, speed, edge) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: banner idx (interger)
+                    self.movabs_rsi_u64(banner as u64);
+                    // rdx: edge mode arg. (interger)
+                    self.movabs_rdx_u64(edge_to_code(edge) as u64);
+                    // xmm0: x arg. (floating point)
+                    self.movss_xmm_n_param(0, x, context);
+                    // xmm1: y arg. (floating point)
+                    self.movss_xmm_n_param(1, y, context);
+                    // xmm2: speed arg. (floating point)
+                    self.movss_xmm_n_param(2, speed, context);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_text as extern "sysv64" fn(&mut Context, u8, u8, f32, f32, f32)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::Camera(x, y) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // xmm0: x (floating point)
+                    self.movss_xmm_n_param(0, x, context);
+                    // xmm1: y (floating point)
+                    self.movss_xmm_n_param(1, y, context);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_camera as extern "sysv64" fn(&mut Context, f32, f32)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::Follow(sprite_idx) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: sprite idx (interger)
+                    self.movabs_rsi_u64(sprite_idx as u64);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_follow as extern "sysv64" fn(&mut Context, u8)
+                    )});
+                    self.call_rax();
+                },
+
+                Op::Collide(a, b, overlap, var) => {
+                    // rdi: pointer to Context (pointer is an integer value)
+                    self.movabs_rdi_u64( unsafe { mem::transmute(context as *mut _) });
+                    // rsi: first sprite idx (interger)
+                    self.movabs_rsi_u64(a as u64);
+                    // rdx: second sprite idx (interger)
+                    self.movabs_rdx_u64(b as u64);
+                    // rcx: overlap arg. (interger)
+                    self.movabs_rcx_u64(overlap_to_code(overlap) as u64);
+                    // r8: var idx (interger)
+                    self.movabs_r8_u64(var as u64);
+
+                    self.movabs_rax_u64( unsafe { mem::transmute(
+                        Ops::op_collide as extern "sysv64" fn(&mut Context, u8, u8, u8, u8)
+                    )});
+                    self.call_rax();
+                },
             }
         }
 
+        // the jumps past the last operator
+        self.land_jumps(&mut jumps, usize::max_value());
+
         // epilogue
         self.mov_rsp_rbp();
         self.pop_rbp();
@@ -321,6 +626,7 @@ impl JitAssembler for JitMemory {
     }
 
     fn push_u8(&mut self, value: u8) {
+        assert!(self.offset < self.size, "the JIT fn doesn't fit in the memory");
         unsafe { *self.addr.offset(self.offset as _) = value };
         self.offset += 1;
     }
@@ -407,7 +713,7 @@ impl JitMemory {
 
         // pushq x
         self.push_u8(0x68);
-        self.push_u32(unsafe { mem::transmute(value) });
+        self.push_u32(value.to_bits());
 
         // movss xmm0, [rsp]
         self.push_u8(0xf3);// movss: 0xf3, movsd: 0xf2
@@ -419,6 +725,39 @@ impl JitMemory {
         self.add_rsp_u8(8);
     }
 
+    /// Loads a float from memory, such as a track value in the `Context`.
+    pub fn movss_xmm_n_ptr(&mut self, xmm_n: usize, addr: *const f32) {
+        // xmm0 - xmm7 are used to pass floating point arguments
+        if xmm_n > 7 {
+            return;
+        }
+
+        self.movabs_rax_u64(addr as u64);
+
+        // movss xmm0, [rax]
+        self.push_u8(0xf3);
+        self.push_u8(0x0f);
+        self.push_u8(0x10);
+        // ModRM: mod 00, reg xmm_n, r/m rax
+        self.push_u8((xmm_n as u8) << 3);
+    }
+
+    /// Loads an operator argument: the number, the track value which
+    /// `op_update_tracks()` writes at the start of the JIT fn, or the
+    /// variable. For a variable rdi must point to the `Context`.
+    pub fn movss_xmm_n_param(&mut self, xmm_n: usize, param: Param, context: &Context) {
+        match param {
+            Param::Value(value) => self.movss_xmm_n_f32(xmm_n, value),
+            Param::Track(arg) => {
+                match context.track_values.get(arg.track as usize) {
+                    Some(value) => self.movss_xmm_n_ptr(xmm_n, value as *const f32),
+                    None => self.movss_xmm_n_f32(xmm_n, 0.0),
+                }
+            },
+            Param::Var(arg) => self.movss_xmm_n_var(xmm_n, arg.var, context),
+        }
+    }
+
     pub fn movss_xmm_n_f64(&mut self, xmm_n: usize, value: f64) {
         // xmm0 - xmm7 are used to pass floating point arguments
         if xmm_n > 7 {
@@ -426,7 +765,7 @@ impl JitMemory {
         }
 
         // there is no push imm64, so push the value from a register
-        self.movabs_rax_u64(unsafe { mem::transmute(value) });
+        self.movabs_rax_u64(value.to_bits());
         self.push_rax();
 
         // movsd xmm0, QWORD PTR [rsp]
@@ -2,9 +2,26 @@ use dmo::Context;
 
 pub trait Ops {
     extern "sysv64" fn op_print(&self);
+    extern "sysv64" fn op_update_tracks(&mut self);
     extern "sysv64" fn op_exit(&mut self, limit: f32);
-    extern "sysv64" fn op_draw(&mut self, sprite_idx: u8, offset: u8, speed: f32);
+    extern "sysv64" fn op_draw(&mut self, sprite_idx: u8, facing: u8, edge: u8, x: f32, y: f32, speed: f32);
     extern "sysv64" fn op_clear(&mut self, charcode: u32);
+    extern "sysv64" fn op_set_color(&mut self, fg: u32, bg: u32, attrs: u8);
+    extern "sysv64" fn op_animate(&mut self, sprite_idx: u8, mode: u8, fps: f32);
+    extern "sysv64" fn op_set_layer(&mut self, layer: u8);
+    extern "sysv64" fn op_clear_layer(&mut self, layer: u8);
+    extern "sysv64" fn op_move_layer(&mut self, layer: u8, speed_x: f32, speed_y: f32);
+    extern "sysv64" fn op_transition(&mut self, blend: u8, seed: u32, duration: f32);
+    extern "sysv64" fn op_emit(&mut self, emitter: u8, x: f32, y: f32);
+    extern "sysv64" fn op_plasma(&mut self, scale: f32, speed: f32);
+    extern "sysv64" fn op_fire(&mut self, cooling: u8);
+    extern "sysv64" fn op_starfield(&mut self, count: u16, speed: f32);
+    extern "sysv64" fn op_sine_scroll(&mut self, sprite_idx: u8, speed: f32, amplitude: f32, frequency: f32);
+    extern "sysv64" fn op_shade(&mut self, shader: u8);
+    extern "sysv64" fn op_text(&mut self, banner: u8, edge: u8, x: f32, y: f32, speed: f32);
+    extern "sysv64" fn op_camera(&mut self, x: f32, y: f32);
+    extern "sysv64" fn op_follow(&mut self, sprite_idx: u8);
+    extern "sysv64" fn op_collide(&mut self, a: u8, b: u8, overlap: u8, var: u8);
 }
 
 impl Ops for Context {
@@ -12,15 +29,83 @@ impl Ops for Context {
         self.impl_print();
     }
 
+    extern "sysv64" fn op_update_tracks(&mut self) {
+        self.update_tracks();
+    }
+
     extern "sysv64" fn op_exit(&mut self, limit: f32) {
         self.impl_exit(limit);
     }
 
-    extern "sysv64" fn op_draw(&mut self, sprite_idx: u8, offset: u8, speed: f32) {
-        self.impl_draw(sprite_idx, offset, speed);
+    extern "sysv64" fn op_draw(&mut self, sprite_idx: u8, facing: u8, edge: u8, x: f32, y: f32, speed: f32) {
+        self.impl_draw(sprite_idx, x, y.floor() as i32, facing, edge, speed);
     }
 
     extern "sysv64" fn op_clear(&mut self, charcode: u32) {
         self.impl_clear(charcode);
     }
+
+    extern "sysv64" fn op_set_color(&mut self, fg: u32, bg: u32, attrs: u8) {
+        self.impl_set_color(fg, bg, attrs);
+    }
+
+    extern "sysv64" fn op_animate(&mut self, sprite_idx: u8, mode: u8, fps: f32) {
+        self.impl_animate(sprite_idx, mode, fps);
+    }
+
+    extern "sysv64" fn op_set_layer(&mut self, layer: u8) {
+        self.impl_set_layer(layer);
+    }
+
+    extern "sysv64" fn op_clear_layer(&mut self, layer: u8) {
+        self.impl_clear_layer(layer);
+    }
+
+    extern "sysv64" fn op_move_layer(&mut self, layer: u8, speed_x: f32, speed_y: f32) {
+        self.impl_move_layer(layer, speed_x, speed_y);
+    }
+
+    extern "sysv64" fn op_transition(&mut self, blend: u8, seed: u32, duration: f32) {
+        self.impl_transition(blend, seed, duration);
+    }
+
+    extern "sysv64" fn op_emit(&mut self, emitter: u8, x: f32, y: f32) {
+        self.impl_emit(emitter, x, y);
+    }
+
+    extern "sysv64" fn op_plasma(&mut self, scale: f32, speed: f32) {
+        self.impl_plasma(scale, speed);
+    }
+
+    extern "sysv64" fn op_fire(&mut self, cooling: u8) {
+        self.impl_fire(cooling);
+    }
+
+    extern "sysv64" fn op_starfield(&mut self, count: u16, speed: f32) {
+        self.impl_starfield(count, speed);
+    }
+
+    extern "sysv64" fn op_sine_scroll(&mut self, sprite_idx: u8, speed: f32, amplitude: f32, frequency: f32) {
+        self.impl_sine_scroll(sprite_idx, speed, amplitude, frequency);
+    }
+
+    extern "sysv64" fn op_shade(&mut self, shader: u8) {
+        self.impl_shade(shader);
+    }
+
+    extern "sysv64" fn op_text(&mut self, banner: u8, edge: u8, x: f32, y: f32, speed: f32) {
+        self.impl_text(banner, x, y.floor() as i32, edge, speed);
+    }
+
+    extern "sysv64" fn op_camera(&mut self, x: f32, y: f32) {
+        self.impl_camera(x, y);
+    }
+
+    extern "sysv64" fn op_follow(&mut self, sprite_idx: u8) {
+        self.impl_follow(sprite_idx);
+    }
+
+    extern "sysv64" fn op_collide(&mut self, a: u8, b: u8, overlap: u8, var: u8) {
+        self.impl_collide(a, b, overlap, var);
+    }
 }
@@ -0,0 +1,260 @@
+use std::mem;
+
+use dmo::Context;
+use shader::{self, Expr, Func};
+
+use super::{JitMemory, JitAssembler};
+
+// SSE scalar single precision ops, F3 0F xx
+pub const MOVSS: u8 = 0x10;
+pub const MOVSS_STORE: u8 = 0x11;
+pub const ADDSS: u8 = 0x58;
+pub const MULSS: u8 = 0x59;
+pub const SUBSS: u8 = 0x5c;
+pub const DIVSS: u8 = 0x5e;
+pub const CVTSI2SS: u8 = 0x2a;
+pub const CVTTSS2SI: u8 = 0x2c;
+
+// general purpose register numbers in ModRM
+pub const EAX: u8 = 0;
+const ECX: u8 = 1;
+const EDX: u8 = 2;
+
+impl JitMemory {
+    /// Evaluates the expression for every cell into `context.shade_values`,
+    /// row by row.
+    ///
+    /// The address of the buffer and the canvas size are in the code, so the
+    /// buffer must have `width * height` values and must not move while the
+    /// JIT fn is used, see `Context::update_shade_values()`.
+    ///
+    /// The column is in ecx and the row in edx, rdi points to the value of
+    /// the cell. The expression goes in xmm0 - xmm7, `sin` and `cos` are
+    /// calls, see `.call_f32_fn()`.
+    pub fn shade_loop(&mut self, expr: &Expr, context: &Context) {
+        let (width, height) = (context.width as u32, context.height as u32);
+        assert_eq!(context.shade_values.len(), (width * height) as usize,
+                   "the shade values don't match the canvas size");
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        self.movabs_rdi_u64(context.shade_values.as_ptr() as u64);
+
+        // xor edx, edx
+        self.push_u8(0x31);
+        self.push_u8(0xd2);
+        let row_loop = self.get_offset();
+
+        // xor ecx, ecx
+        self.push_u8(0x31);
+        self.push_u8(0xc9);
+        let col_loop = self.get_offset();
+
+        self.shade_expr(expr, 0, context);
+
+        // movss [rdi], xmm0
+        self.push_u8(0xf3);
+        self.push_u8(0x0f);
+        self.push_u8(MOVSS_STORE);
+        self.push_u8(0x07);
+        // add rdi, 4
+        self.push_u8(0x48);
+        self.push_u8(0x83);
+        self.push_u8(0xc7);
+        self.push_u8(0x04);
+
+        // inc ecx
+        self.push_u8(0xff);
+        self.push_u8(0xc1);
+        // cmp ecx, width
+        self.push_u8(0x81);
+        self.push_u8(0xf9);
+        self.push_u32(width);
+        self.jl(col_loop);
+
+        // inc edx
+        self.push_u8(0xff);
+        self.push_u8(0xc2);
+        // cmp edx, height
+        self.push_u8(0x81);
+        self.push_u8(0xfa);
+        self.push_u32(height);
+        self.jl(row_loop);
+    }
+
+    /// Evaluates the expression into xmm `r`, using the registers after it
+    /// for the operands. See `Expr::registers()`.
+    fn shade_expr(&mut self, expr: &Expr, r: u8, context: &Context) {
+        match *expr {
+            Expr::Num(value) => self.movss_xmm_n_f32(r as usize, value),
+            Expr::X => self.sse(CVTSI2SS, r, ECX),
+            Expr::Y => self.sse(CVTSI2SS, r, EDX),
+            Expr::T => self.movss_xmm_n_ptr(r as usize, &context.time as *const f32),
+
+            Expr::Neg(ref a) => {
+                self.shade_expr(a, r, context);
+                // flip the sign bit
+                self.on_stack(r, &[0x81, 0x34, 0x24, 0x00, 0x00, 0x00, 0x80]);
+            },
+
+            Expr::Add(ref a, ref b) => self.shade_binary(ADDSS, a, b, r, context),
+            Expr::Sub(ref a, ref b) => self.shade_binary(SUBSS, a, b, r, context),
+            Expr::Mul(ref a, ref b) => self.shade_binary(MULSS, a, b, r, context),
+            Expr::Div(ref a, ref b) => self.shade_binary(DIVSS, a, b, r, context),
+
+            Expr::Call(func, ref args) => {
+                self.shade_expr(&args[0], r, context);
+                match func {
+                    Func::Sin => self.call_f32_fn(r, shader::sin),
+                    Func::Cos => self.call_f32_fn(r, shader::cos),
+                    // and dword [rsp], 0x7fffffff
+                    Func::Abs => self.on_stack(r, &[0x81, 0x24, 0x24, 0xff, 0xff, 0xff, 0x7f]),
+                    Func::Floor => self.floor(r),
+                    Func::Mod => {
+                        self.shade_expr(&args[1], r + 1, context);
+                        // a - b * floor(a / b)
+                        self.sse(MOVSS, r + 2, r);
+                        self.sse(DIVSS, r + 2, r + 1);
+                        self.floor(r + 2);
+                        self.sse(MULSS, r + 2, r + 1);
+                        self.sse(SUBSS, r, r + 2);
+                    },
+                    Func::Ramp => {
+                        // (v + 1) * RAMP_SCALE
+                        self.movss_xmm_n_f32(r as usize + 1, 1.0);
+                        self.sse(ADDSS, r, r + 1);
+                        self.movss_xmm_n_f32(r as usize + 1, shader::RAMP_SCALE);
+                        self.sse(MULSS, r, r + 1);
+                    },
+                }
+            },
+        }
+    }
+
+    fn shade_binary(&mut self, opcode: u8, a: &Expr, b: &Expr, r: u8, context: &Context) {
+        self.shade_expr(a, r, context);
+        self.shade_expr(b, r + 1, context);
+        self.sse(opcode, r, r + 1);
+    }
+
+    /// An SSE scalar op between registers, `reg` is the destination.
+    pub fn sse(&mut self, opcode: u8, reg: u8, rm: u8) {
+        self.push_u8(0xf3);
+        self.push_u8(0x0f);
+        self.push_u8(opcode);
+        // ModRM: mod 11, register to register
+        self.push_u8(0xc0 | reg << 3 | rm);
+    }
+
+    /// Moves xmm `r` to the stack, runs `code` on `[rsp]`, and moves it back.
+    pub fn on_stack(&mut self, r: u8, code: &[u8]) {
+        self.sub_rsp_u8(8);
+        // movss [rsp], xmm_r
+        self.push_u8(0xf3);
+        self.push_u8(0x0f);
+        self.push_u8(0x11);
+        self.select_xmm_n(r as usize);
+
+        for byte in code.iter() {
+            self.push_u8(*byte);
+        }
+
+        // movss xmm_r, [rsp]
+        self.push_u8(0xf3);
+        self.push_u8(0x0f);
+        self.push_u8(0x10);
+        self.select_xmm_n(r as usize);
+        self.add_rsp_u8(8);
+    }
+
+    /// Replaces xmm `r` with `f` of it. rdi, rcx, rdx and xmm0 up to `r` are
+    /// kept, the call can change the others. rsp must be aligned on 16 bytes.
+    pub fn call_f32_fn(&mut self, r: u8, f: extern "sysv64" fn(f32) -> f32) {
+        // push rdi; push rcx; push rdx
+        self.push_u8(0x57);
+        self.push_u8(0x51);
+        self.push_u8(0x52);
+        // room for xmm0 up to r, keeping rsp aligned
+        let room = 8 * r + if r % 2 == 0 { 8 } else { 0 };
+        self.sub_rsp_u8(room);
+        for n in 0 .. r {
+            // movss [rsp + 8n], xmm_n
+            self.push_u8(0xf3);
+            self.push_u8(0x0f);
+            self.push_u8(MOVSS_STORE);
+            self.push_u8(0x44 | n << 3);
+            self.push_u8(0x24);
+            self.push_u8(8 * n);
+        }
+        if r != 0 {
+            self.sse(MOVSS, 0, r);
+        }
+
+        self.movabs_rax_u64(unsafe { mem::transmute(f) });
+        self.call_rax();
+
+        if r != 0 {
+            self.sse(MOVSS, r, 0);
+        }
+        for n in 0 .. r {
+            // movss xmm_n, [rsp + 8n]
+            self.push_u8(0xf3);
+            self.push_u8(0x0f);
+            self.push_u8(MOVSS);
+            self.push_u8(0x44 | n << 3);
+            self.push_u8(0x24);
+            self.push_u8(8 * n);
+        }
+        self.add_rsp_u8(room);
+        // pop rdx; pop rcx; pop rdi
+        self.push_u8(0x5a);
+        self.push_u8(0x59);
+        self.push_u8(0x5f);
+    }
+
+    /// Rounds xmm `r` down with SSE2, using xmm `r + 1` and eax: truncate,
+    /// and take one off if that went up. Like `f32::floor()`, values from
+    /// 2^23 up, which have no fraction, infinities and NaN stay as they are.
+    fn floor(&mut self, r: u8) {
+        // movd eax, xmm_r
+        self.push_u8(0x66);
+        self.push_u8(0x0f);
+        self.push_u8(0x7e);
+        self.push_u8(0xc0 | r << 3 | EAX);
+        // and eax, 0x7fffffff; cmp eax, 2^23 as f32 bits
+        self.push_u8(0x25);
+        self.push_u32(0x7fff_ffff);
+        self.push_u8(0x3d);
+        self.push_u32(8388608.0f32.to_bits());
+        // jae over the rounding, NaN is above the infinities
+        self.push_u8(0x73);
+        let jump = self.get_offset();
+        self.push_u8(0x00);
+
+        self.sse(CVTTSS2SI, EAX, r);
+        self.sse(CVTSI2SS, r + 1, EAX);
+        // comiss xmm_r, xmm_r+1, sets the carry if the value is below
+        self.push_u8(0x0f);
+        self.push_u8(0x2f);
+        self.push_u8(0xc0 | r << 3 | (r + 1));
+        // sbb eax, 0
+        self.push_u8(0x83);
+        self.push_u8(0xd8);
+        self.push_u8(0x00);
+        self.sse(CVTSI2SS, r, EAX);
+
+        // relative to the end of the jae
+        let rel = self.get_offset() - (jump + 1);
+        unsafe { *self.addr.offset(jump as _) = rel as u8 };
+    }
+
+    /// jl back to `target`.
+    fn jl(&mut self, target: usize) {
+        self.push_u8(0x0f);
+        self.push_u8(0x8c);
+        // relative to the end of the instruction
+        let rel = target as i64 - (self.get_offset() as i64 + 4);
+        self.push_u32(rel as i32 as u32);
+    }
+}
@@ -0,0 +1,136 @@
+use dmo::Context;
+use dmo::Operator as Op;
+use track::Param;
+use var::{Compare, VarKind};
+use shader;
+
+use super::{JitMemory, JitAssembler};
+use super::shader::{MOVSS, MOVSS_STORE, ADDSS, MULSS, CVTSI2SS, CVTTSS2SI, EAX};
+
+// jcc rel32, 0F xx
+const JA: u8 = 0x87;
+const JAE: u8 = 0x83;
+const JE: u8 = 0x84;
+const JNE: u8 = 0x85;
+const JP: u8 = 0x8a;
+
+impl JitMemory {
+    /// Loads a variable from its offset in the context, rdi must point to
+    /// the `Context`. Variables out of range are 0.
+    pub fn movss_xmm_n_var(&mut self, xmm_n: usize, var: u8, context: &Context) {
+        match context.var_offset(var) {
+            Some(offset) => self.sse_rdi(MOVSS, xmm_n as u8, offset),
+            None => self.movss_xmm_n_f32(xmm_n, 0.0),
+        }
+    }
+
+    /// `Set`, `Add`, `Mul` and `Sin`: the new value is made in xmm0 and
+    /// written back to the variable. rdi must point to the `Context`.
+    pub fn update_var(&mut self, op: &Op, var: u8, value: Param, context: &Context) {
+        let offset = match context.var_offset(var) {
+            Some(offset) => offset,
+            None => return,
+        };
+
+        self.movss_xmm_n_param(0, value, context);
+        match *op {
+            Op::Add(_, _) => self.sse_rdi(ADDSS, 0, offset),
+            Op::Mul(_, _) => self.sse_rdi(MULSS, 0, offset),
+            Op::Sin(_, _) => self.call_f32_fn(0, shader::sin),
+            _ => {},
+        }
+
+        let kind = context.vars.get(var as usize).map(|v| v.kind).unwrap_or_default();
+        if kind == VarKind::Int {
+            self.sse(CVTTSS2SI, EAX, 0);
+            self.sse(CVTSI2SS, 0, EAX);
+        }
+
+        self.sse_rdi(MOVSS_STORE, 0, offset);
+    }
+
+    /// `Branch`: compares the variable in xmm0 with the value in xmm1, and
+    /// jumps if the comparison holds. rdi must point to the `Context`.
+    ///
+    /// Returns where the offsets of the jumps go, see `.land_jumps()`. The
+    /// jumps are for unsigned flags, which `comiss` sets, and they don't jump
+    /// when the comparison is unordered, except for `NotEqual`.
+    pub fn branch(&mut self, var: u8, compare: Compare, value: Param, context: &Context) -> Vec<usize> {
+        self.movss_xmm_n_var(0, var, context);
+        self.movss_xmm_n_param(1, value, context);
+
+        match compare {
+            Compare::Less => {
+                self.comiss(1, 0);
+                vec![self.jcc(JA)]
+            },
+            Compare::LessOrEqual => {
+                self.comiss(1, 0);
+                vec![self.jcc(JAE)]
+            },
+            Compare::Greater => {
+                self.comiss(0, 1);
+                vec![self.jcc(JA)]
+            },
+            Compare::GreaterOrEqual => {
+                self.comiss(0, 1);
+                vec![self.jcc(JAE)]
+            },
+            Compare::Equal => {
+                self.comiss(0, 1);
+                // jp over the je, unordered is not equal
+                self.push_u8(0x7a);
+                self.push_u8(0x06);
+                vec![self.jcc(JE)]
+            },
+            Compare::NotEqual => {
+                self.comiss(0, 1);
+                let unordered = self.jcc(JP);
+                vec![unordered, self.jcc(JNE)]
+            },
+        }
+    }
+
+    /// Points the jumps to operators up to `op_idx` to the current offset.
+    pub fn land_jumps(&mut self, jumps: &mut Vec<(usize, usize)>, op_idx: usize) {
+        let here = self.get_offset();
+        for &(target, at) in jumps.iter() {
+            if target <= op_idx {
+                // relative to the end of the instruction
+                let rel = here as i64 - (at as i64 + 4);
+                let bytes = rel as i32 as u32;
+                for i in 0 .. 4 {
+                    unsafe { *self.addr.offset((at + i) as _) = (bytes >> (i * 8)) as u8 };
+                }
+            }
+        }
+        jumps.retain(|&(target, _)| target > op_idx);
+    }
+
+    /// A jcc with a 4-byte offset, which is filled in later. Returns where
+    /// the offset is.
+    fn jcc(&mut self, opcode: u8) -> usize {
+        self.push_u8(0x0f);
+        self.push_u8(opcode);
+        let at = self.get_offset();
+        self.push_u32(0);
+        at
+    }
+
+    /// comiss xmm_a, xmm_b
+    fn comiss(&mut self, a: u8, b: u8) {
+        self.push_u8(0x0f);
+        self.push_u8(0x2f);
+        self.push_u8(0xc0 | a << 3 | b);
+    }
+
+    /// An SSE scalar op between xmm `reg` and the float at `[rdi + offset]`.
+    fn sse_rdi(&mut self, opcode: u8, reg: u8, offset: u32) {
+        self.push_u8(0xf3);
+        self.push_u8(0x0f);
+        self.push_u8(opcode);
+        // ModRM: mod 10, 4-byte displacement, r/m rdi
+        self.push_u8(0x80 | reg << 3 | 0x07);
+        self.push_u32(offset);
+    }
+}
@@ -0,0 +1,85 @@
+use color::Cell;
+
+/// A buffer over the canvas, the size of the canvas, where cells are
+/// transparent until something is drawn there. Layers are composited over the
+/// canvas at `Print` time, lowest `z` first.
+///
+/// In the YAML:
+///
+/// ```yaml
+/// layers:
+///   - name: "background"
+///     z: -1
+///     scroll: [ 0, 0 ]
+///   - name: "foreground"
+///     z: 1
+///     visible: false
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Layer {
+    pub name: String,
+    /// Stacking order, higher is in front. Layers with the same `z` stack in
+    /// the order of the list.
+    #[serde(default)]
+    pub z: i16,
+    /// Hidden layers are not composited, but can be drawn on.
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+    /// Offset of the layer on the canvas in chars: x, y. The layer wraps
+    /// around, so a scrolled layer is still full.
+    #[serde(default)]
+    pub scroll: (i16, i16),
+
+    /// `width * height` cells, row by row. `None` is transparent.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub cells: Vec<Option<Cell>>,
+    /// Added to `.scroll`, set by `MoveLayer`
+    #[serde(skip_serializing, skip_deserializing)]
+    pub offset: (i64, i64),
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+impl Layer {
+    pub fn new(name: &str) -> Layer {
+        Layer {
+            name: String::from(name),
+            z: 0,
+            visible: true,
+            scroll: (0, 0),
+            cells: vec![],
+            offset: (0, 0),
+        }
+    }
+
+    /// Allocates the cells for the canvas size, all transparent.
+    pub fn reset(&mut self, width: u16, height: u16) {
+        self.cells = vec![None; width as usize * height as usize];
+        self.offset = (0, 0);
+    }
+
+    /// Makes every cell transparent.
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = None;
+        }
+    }
+
+    /// The cell which shows at `row`, `col` of the canvas, taking the scroll
+    /// into account.
+    pub fn cell_at(&self, row: usize, col: usize, width: u16, height: u16) -> Option<Cell> {
+        let (w, h) = (width as i64, height as i64);
+        if w == 0 || h == 0 {
+            return None;
+        }
+
+        let dx = self.scroll.0 as i64 + self.offset.0;
+        let dy = self.scroll.1 as i64 + self.offset.1;
+        let x = (((col as i64 - dx) % w) + w) % w;
+        let y = (((row as i64 - dy) % h) + h) % h;
+
+        self.cells.get((y * w + x) as usize).cloned().unwrap_or(None)
+    }
+}
@@ -12,6 +12,7 @@ extern crate env_logger;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_yaml;
+extern crate inflate;
 
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 extern crate libc;
@@ -22,8 +23,25 @@ extern crate winapi;
 extern crate kernel32;
 
 pub mod dmo;
+pub mod sprite;
+pub mod color;
+pub mod layer;
+pub mod track;
+pub mod var;
+pub mod scene;
+pub mod transition;
+pub mod random;
+pub mod particle;
+pub mod effect;
+pub mod shader;
+pub mod font;
+pub mod post;
+pub mod collision;
+pub mod image;
 pub mod bytecode;
+pub mod yml;
 pub mod jit;
 pub mod utils;
+pub mod embed;
 
 pub mod tests;
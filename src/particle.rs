@@ -0,0 +1,134 @@
+use random::Rng;
+
+/// At most this many particles of an emitter are alive.
+pub const MAX_PARTICLES: usize = 1024;
+
+/// Spawns particles at a steady rate, which move in a straight line with a
+/// random speed and disappear after their lifetime. The `Emit` operator
+/// updates and draws the particles each frame, at a position which can follow
+/// a track.
+///
+/// In the YAML, with the speeds in chars per second:
+///
+/// ```yaml
+/// seed: 7
+/// emitters:
+///   - name: "bubbles"
+///     rate: 3.0
+///     lifetime: 2.0
+///     speed_x: [ -0.5, 0.5 ]
+///     speed_y: [ -2.0, -1.0 ]
+///     glyphs: "oO°."
+/// ```
+///
+/// The speeds and glyphs come from `Context.rng`, so the same `seed` and time
+/// steps give the same frames.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Emitter {
+    pub name: String,
+    /// Particles per second
+    pub rate: f32,
+    /// Seconds
+    pub lifetime: f32,
+    /// Range of the x speed: min, max
+    #[serde(default)]
+    pub speed_x: (f32, f32),
+    /// Range of the y speed: min, max. Negative is up.
+    #[serde(default)]
+    pub speed_y: (f32, f32),
+    /// A particle is one of these chars
+    pub glyphs: String,
+
+    #[serde(skip_serializing, skip_deserializing)]
+    pub particles: Vec<Particle>,
+    /// The time of the last update
+    #[serde(skip_serializing, skip_deserializing)]
+    pub last_time: Option<f32>,
+    /// Particles due but not spawned yet, less than one after an update
+    #[serde(skip_serializing, skip_deserializing)]
+    pub pending: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+    pub x: f32,
+    pub y: f32,
+    pub speed_x: f32,
+    pub speed_y: f32,
+    /// The time it was spawned
+    pub born: f32,
+    pub glyph: char,
+}
+
+impl Particle {
+    /// Where the particle is at `time`: x, y.
+    pub fn position(&self, time: f32) -> (f32, f32) {
+        let age = time - self.born;
+        (self.x + self.speed_x * age, self.y + self.speed_y * age)
+    }
+}
+
+impl Emitter {
+    pub fn new(name: &str, rate: f32, lifetime: f32, glyphs: &str) -> Emitter {
+        Emitter {
+            name: String::from(name),
+            rate: rate,
+            lifetime: lifetime,
+            speed_x: (0.0, 0.0),
+            speed_y: (0.0, 0.0),
+            glyphs: String::from(glyphs),
+            particles: vec![],
+            last_time: None,
+            pending: 0.0,
+        }
+    }
+
+    /// Removes the particles.
+    pub fn reset(&mut self) {
+        self.particles = vec![];
+        self.last_time = None;
+        self.pending = 0.0;
+    }
+
+    /// Spawns the particles which are due since the last update at `x`, `y`,
+    /// and removes the ones which are older than the lifetime. When the time
+    /// goes back, as when a scene starts, the emitter starts over.
+    pub fn update(&mut self, time: f32, x: f32, y: f32, rng: &mut Rng) {
+        let last = match self.last_time {
+            Some(last) if last <= time => last,
+            _ => {
+                self.reset();
+                time
+            },
+        };
+        self.last_time = Some(time);
+
+        let lifetime = self.lifetime;
+        self.particles.retain(|p| time - p.born < lifetime);
+
+        let glyphs: Vec<char> = self.glyphs.chars().collect();
+        if glyphs.is_empty() {
+            return;
+        }
+
+        self.pending += (time - last) * self.rate;
+        if self.pending > MAX_PARTICLES as f32 {
+            self.pending = MAX_PARTICLES as f32;
+        }
+        while self.pending >= 1.0 {
+            self.pending -= 1.0;
+            if self.particles.len() >= MAX_PARTICLES {
+                continue;
+            }
+            let particle = Particle {
+                x: x,
+                y: y,
+                speed_x: rng.range(self.speed_x.0, self.speed_x.1),
+                speed_y: rng.range(self.speed_y.0, self.speed_y.1),
+                born: time,
+                glyph: glyphs[rng.below(glyphs.len() as u32) as usize],
+            };
+            self.particles.push(particle);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::cmp::{min, max};
+
+use color::{Cell, Style};
+use sprite::mirror_char;
+
+/// Chars which are replaced by `Ascii`, the others out of ASCII become `?`.
+pub const ASCII_FALLBACK: [(char, char); 36] = [
+    ('°', 'o'), ('ø', 'o'), ('Ø', 'O'), ('¸', ','), ('´', '\''), ('¨', '"'),
+    ('·', '.'), ('•', '*'), ('×', 'x'), ('÷', '/'), ('«', '<'), ('»', '>'),
+    ('¦', '|'), ('¯', '-'), ('ä', 'a'), ('ö', 'o'), ('ü', 'u'), ('Ä', 'A'),
+    ('Ö', 'O'), ('Ü', 'U'), ('ß', 's'), ('é', 'e'), ('è', 'e'), ('á', 'a'),
+    ('─', '-'), ('│', '|'), ('┌', '+'), ('┐', '+'), ('└', '+'), ('┘', '+'),
+    ('█', '#'), ('▓', '#'), ('▒', '%'), ('░', ':'), ('▀', '"'), ('▄', '_'),
+];
+
+/// A full-frame effect, which is applied to the canvas and the layers when
+/// the frame is printed, after all the operators have drawn. The effects of
+/// the `post` list of the context run in order:
+///
+/// ```yaml
+/// post:
+///   - Wave: [ Horizontal, 2.0, 0.4, 3.0 ]
+///   - Mirror: Vertical
+///   - Invert: " .:-=+*#%@"
+///   - Remap: [ "~", "-" ]
+///   - Ascii
+/// ```
+///
+/// The frame in the buffers is not changed, so the effects don't build up
+/// from one frame to the next.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum PostFx {
+    /// Move the rows sideways (`Horizontal`) or the columns up and down
+    /// (`Vertical`) along a sine wave: axis, amplitude in cells, frequency in
+    /// radians per row or column, speed in radians per second. The cells
+    /// which are uncovered are blank.
+    Wave(Axis, f32, f32, f32),
+    /// Flip the frame left to right (`Horizontal`), swapping the chars of
+    /// the mirror table of the context, or upside down (`Vertical`).
+    Mirror(Axis),
+    /// Swap the chars of a density ramp, from empty to dense, end for end.
+    /// Other chars are unchanged.
+    Invert(String),
+    /// Replace each char of the first string by the char at the same place
+    /// in the second.
+    Remap(String, String),
+    /// Replace the chars out of ASCII, see `ASCII_FALLBACK`.
+    Ascii,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+impl PostFx {
+    /// Applies the effect to the `width * height` cells at `time`.
+    pub fn apply(&self, cells: &[Cell], width: usize, height: usize, time: f32, mirror: &[String]) -> Vec<Cell> {
+        let blank = Cell::new(' ', Style::default());
+        let at = |col: usize, row: usize| cells[row * width + col];
+
+        match *self {
+            PostFx::Wave(axis, amplitude, frequency, speed) => {
+                // past the canvas every cell is blank, so the shift stops
+                // there and a huge amplitude can't overflow
+                let extent = match axis {
+                    Axis::Horizontal => width as i64,
+                    Axis::Vertical => height as i64,
+                };
+                let shift = |n: usize| {
+                    let shift = (amplitude * (frequency * n as f32 + speed * time).sin()).round() as i64;
+                    max(-extent, min(extent, shift))
+                };
+                let mut out: Vec<Cell> = Vec::with_capacity(cells.len());
+                for row in 0 .. height {
+                    for col in 0 .. width {
+                        // where the cell comes from
+                        let (c, r) = match axis {
+                            Axis::Horizontal => (col as i64 - shift(row), row as i64),
+                            Axis::Vertical => (col as i64, row as i64 - shift(col)),
+                        };
+                        let inside = c >= 0 && c < width as i64 && r >= 0 && r < height as i64;
+                        out.push(if inside { at(c as usize, r as usize) } else { blank });
+                    }
+                }
+                out
+            },
+
+            PostFx::Mirror(axis) => {
+                let mut out: Vec<Cell> = Vec::with_capacity(cells.len());
+                for row in 0 .. height {
+                    for col in 0 .. width {
+                        out.push(match axis {
+                            Axis::Horizontal => {
+                                let mut cell = at(width - 1 - col, row);
+                                cell.ch = mirror_char(cell.ch, mirror);
+                                cell
+                            },
+                            Axis::Vertical => at(col, height - 1 - row),
+                        });
+                    }
+                }
+                out
+            },
+
+            PostFx::Invert(ref ramp) => {
+                let ramp: Vec<char> = ramp.chars().collect();
+                cells.iter().map(|cell| {
+                    let mut cell = *cell;
+                    if let Some(idx) = ramp.iter().position(|c| *c == cell.ch) {
+                        cell.ch = ramp[ramp.len() - 1 - idx];
+                    }
+                    cell
+                }).collect()
+            },
+
+            PostFx::Remap(ref from, ref to) => {
+                let pairs: Vec<(char, char)> = from.chars().zip(to.chars()).collect();
+                cells.iter().map(|cell| {
+                    let mut cell = *cell;
+                    if let Some(&(_, ch)) = pairs.iter().find(|p| p.0 == cell.ch) {
+                        cell.ch = ch;
+                    }
+                    cell
+                }).collect()
+            },
+
+            PostFx::Ascii => {
+                cells.iter().map(|cell| {
+                    let mut cell = *cell;
+                    cell.ch = ascii_char(cell.ch);
+                    cell
+                }).collect()
+            },
+        }
+    }
+
+    /// The numbers are finite, and `Remap` has a char for each char.
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            PostFx::Wave(_, amplitude, frequency, speed) =>
+                amplitude.is_finite() && frequency.is_finite() && speed.is_finite(),
+            PostFx::Remap(ref from, ref to) => from.chars().count() == to.chars().count(),
+            _ => true,
+        }
+    }
+}
+
+/// The char if it is ASCII, else its replacement from `ASCII_FALLBACK`, or
+/// `?`.
+pub fn ascii_char(ch: char) -> char {
+    if (ch as u32) < 0x80 {
+        return ch;
+    }
+    match ASCII_FALLBACK.iter().find(|p| p.0 == ch) {
+        Some(&(_, ascii)) => ascii,
+        None => '?',
+    }
+}
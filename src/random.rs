@@ -0,0 +1,51 @@
+/// Random numbers for the demo effects, xorshift32. The same seed gives the
+/// same numbers on every platform, so the frames can be tested.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rng {
+    state: u32,
+}
+
+impl Default for Rng {
+    fn default() -> Rng {
+        Rng::new(0)
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Rng {
+        // scramble the seed, as small seeds start with small numbers, and
+        // xorshift is stuck at 0
+        let state = seed.wrapping_mul(0x9E37_79B9) ^ 0x2545_F491;
+        Rng {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// From 0 to 1, not including 1.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// From `min` to `max`, not including `max`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// From 0 to `n - 1`, or 0 if `n` is 0.
+    pub fn below(&mut self, n: u32) -> u32 {
+        if n == 0 {
+            0
+        } else {
+            self.next_u32() % n
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::f32;
+
+use dmo::Operator;
+
+/// A part of the timeline with its own operators. While a scene is running,
+/// `Context.time` is the local time, counting from the `start` of the scene.
+///
+/// In the YAML:
+///
+/// ```yaml
+/// scenes:
+///   - name: "intro"
+///     start: 0.0
+///     end: 5.0
+///     operators:
+///       - Clear: 32
+///       - Print
+///   - name: "fish"
+///     start: 5.0
+///     operators:
+///       - Print
+///       - Exit: 10.0
+/// end: 30.0
+/// ```
+///
+/// A scene without an `end` lasts until the next scene starts. `Exit` in a
+/// scene compares the local time, and ends only the scene: the timeline skips
+/// to the end of the scene.
+///
+/// `S` is how sprites and tracks are referred to, see `dmo::Operator`.
+#[derive(Serialize, Deserialize)]
+pub struct Scene<S = u8> {
+    pub name: String,
+    /// Global time in seconds
+    pub start: f32,
+    /// Global time in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<f32>,
+    pub operators: Vec<Operator<S>>,
+}
+
+impl Scene {
+    pub fn new(name: &str, start: f32, end: Option<f32>, operators: Vec<Operator>) -> Scene {
+        Scene {
+            name: String::from(name),
+            start: start,
+            end: end,
+            operators: operators,
+        }
+    }
+}
+
+/// When the scene at `idx` ends: its `end`, or else the `start` of the next
+/// scene. The last scene without an `end` doesn't end, that is infinity.
+pub fn scene_end<S>(scenes: &[Scene<S>], idx: usize) -> f32 {
+    match scenes[idx].end {
+        Some(end) => end,
+        None => match scenes.get(idx + 1) {
+            Some(next) => next.start,
+            None => f32::INFINITY,
+        },
+    }
+}
+
+/// The first scene which is running at the global `time`, if there is one.
+pub fn active_scene<S>(scenes: &[Scene<S>], time: f32) -> Option<usize> {
+    (0 .. scenes.len()).find(|&idx| scenes[idx].start <= time && time < scene_end(scenes, idx))
+}
+
+/// The time when every scene has ended, infinity if a scene doesn't end.
+pub fn timeline_end<S>(scenes: &[Scene<S>]) -> f32 {
+    (0 .. scenes.len()).map(|idx| scene_end(scenes, idx)).fold(0.0, |a, b| a.max(b))
+}
@@ -0,0 +1,357 @@
+use effect::RAMP;
+
+/// The JIT evaluates an expression in the xmm0 - xmm7 registers, see
+/// `Expr::registers()`.
+pub const MAX_REGISTERS: usize = 8;
+
+/// `ramp` multiplies by this after adding 1, so that -1 to 1 covers the
+/// chars of `effect::RAMP`.
+pub const RAMP_SCALE: f32 = RAMP.len() as f32 / 2.0;
+
+/// `sin` of the interpreter and of the JIT code, which calls it rather than
+/// using `fsin`, so that they agree for any value.
+pub extern "sysv64" fn sin(a: f32) -> f32 {
+    a.sin()
+}
+
+/// `cos`, see `sin()`.
+pub extern "sysv64" fn cos(a: f32) -> f32 {
+    a.cos()
+}
+
+/// An expression which is evaluated for every cell of the canvas, and gives
+/// the char of the cell from `effect::RAMP`, see `shade_char()`. The JIT
+/// compiles it to SSE code which loops over the cells.
+///
+/// In the YAML:
+///
+/// ```yaml
+/// shaders:
+///   - name: "waves"
+///     fill: "ramp(sin(x*0.3 + t) + cos(t*2))"
+/// ```
+///
+/// The variables are `x` and `y`, the column and the row of the cell, and
+/// `t`, the time. There are numbers, `pi`, `+ - * /`, parentheses and the
+/// functions `sin`, `cos`, `abs`, `floor`, `mod(a, b)` and `ramp`, which
+/// spreads -1 to 1 over the chars of the ramp.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Shader {
+    pub name: String,
+    pub fill: String,
+
+    /// The parsed `fill`, `None` if it has errors. Set by `.reset()`.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub expr: Option<Expr>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Num(f32),
+    X,
+    Y,
+    T,
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Func {
+    Sin,
+    Cos,
+    Abs,
+    Floor,
+    /// `a - b * floor(a / b)`
+    Mod,
+    /// `(v + 1) * 5`
+    Ramp,
+}
+
+impl Shader {
+    pub fn new(name: &str, fill: &str) -> Shader {
+        let mut shader = Shader {
+            name: String::from(name),
+            fill: String::from(fill),
+            expr: None,
+        };
+        shader.reset();
+        shader
+    }
+
+    /// Parses the `fill` into `.expr`.
+    pub fn reset(&mut self) {
+        self.expr = parse(&self.fill).ok();
+    }
+}
+
+impl Func {
+    fn from_name(name: &str) -> Option<Func> {
+        match name {
+            "sin" => Some(Func::Sin),
+            "cos" => Some(Func::Cos),
+            "abs" => Some(Func::Abs),
+            "floor" => Some(Func::Floor),
+            "mod" => Some(Func::Mod),
+            "ramp" => Some(Func::Ramp),
+            _ => None,
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match *self {
+            Func::Mod => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl Expr {
+    /// The value at a cell. The operations are in the same order as in the
+    /// JIT code.
+    pub fn eval(&self, x: f32, y: f32, t: f32) -> f32 {
+        use self::Expr::*;
+        match *self {
+            Num(value) => value,
+            X => x,
+            Y => y,
+            T => t,
+            Neg(ref a) => -a.eval(x, y, t),
+            Add(ref a, ref b) => a.eval(x, y, t) + b.eval(x, y, t),
+            Sub(ref a, ref b) => a.eval(x, y, t) - b.eval(x, y, t),
+            Mul(ref a, ref b) => a.eval(x, y, t) * b.eval(x, y, t),
+            Div(ref a, ref b) => a.eval(x, y, t) / b.eval(x, y, t),
+            Call(func, ref args) => {
+                let a = args[0].eval(x, y, t);
+                match func {
+                    Func::Sin => sin(a),
+                    Func::Cos => cos(a),
+                    Func::Abs => a.abs(),
+                    Func::Floor => a.floor(),
+                    Func::Mod => {
+                        let b = args[1].eval(x, y, t);
+                        a - (a / b).floor() * b
+                    },
+                    Func::Ramp => (a + 1.0) * RAMP_SCALE,
+                }
+            },
+        }
+    }
+
+    /// How many xmm registers the JIT code uses. The result goes in the first
+    /// one, and the operands of an operation in the next ones.
+    pub fn registers(&self) -> usize {
+        use self::Expr::*;
+        match *self {
+            Num(_) | X | Y | T => 1,
+            Neg(ref a) => a.registers(),
+            Add(ref a, ref b) | Sub(ref a, ref b) | Mul(ref a, ref b) | Div(ref a, ref b) =>
+                max(a.registers(), 1 + b.registers()),
+            Call(func, ref args) => {
+                let a = args[0].registers();
+                match func {
+                    Func::Sin | Func::Cos | Func::Abs => a,
+                    Func::Floor | Func::Ramp => max(a, 2),
+                    Func::Mod => max(max(a, 1 + args[1].registers()), 4),
+                }
+            },
+        }
+    }
+
+    /// Number of operations, for the size of the JIT code.
+    pub fn size(&self) -> usize {
+        use self::Expr::*;
+        match *self {
+            Num(_) | X | Y | T => 1,
+            Neg(ref a) => 1 + a.size(),
+            Add(ref a, ref b) | Sub(ref a, ref b) | Mul(ref a, ref b) | Div(ref a, ref b) =>
+                1 + a.size() + b.size(),
+            Call(_, ref args) => 1 + args.iter().map(|a| a.size()).sum::<usize>(),
+        }
+    }
+}
+
+fn max(a: usize, b: usize) -> usize {
+    if a > b { a } else { b }
+}
+
+/// The char of a cell from the value of the shader, the value rounded down
+/// is the index in `effect::RAMP`. Values out of the ramp are clamped, NaN is
+/// blank.
+pub fn shade_char(value: f32) -> char {
+    let last = RAMP.len() - 1;
+    if !(value >= 0.0) {
+        RAMP[0]
+    } else if value >= last as f32 {
+        RAMP[last]
+    } else {
+        RAMP[value as usize]
+    }
+}
+
+/// Parses a shader expression. The error tells at which char it went wrong,
+/// counting from 0.
+pub fn parse(text: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    let expr = try!(parser.expr());
+    parser.skip_space();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("unexpected char"));
+    }
+    Ok(expr)
+}
+
+/// Recursive descent:
+///
+/// ```text
+/// expr    = term (("+" | "-") term)*
+/// term    = unary (("*" | "/") unary)*
+/// unary   = "-" unary | primary
+/// primary = number | name | name "(" expr ("," expr)* ")" | "(" expr ")"
+/// ```
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> String {
+        format!("char {}: {}", self.pos, message)
+    }
+
+    fn skip_space(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    /// The next char which is not a space, without taking it.
+    fn peek(&mut self) -> Option<char> {
+        self.skip_space();
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn expect(&mut self, ch: char) -> Result<(), String> {
+        if self.peek() == Some(ch) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", ch)))
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut left = try!(self.term());
+        loop {
+            match self.peek() {
+                Some('+') => {
+                    self.pos += 1;
+                    left = Expr::Add(Box::new(left), Box::new(try!(self.term())));
+                },
+                Some('-') => {
+                    self.pos += 1;
+                    left = Expr::Sub(Box::new(left), Box::new(try!(self.term())));
+                },
+                _ => return Ok(left),
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut left = try!(self.unary());
+        loop {
+            match self.peek() {
+                Some('*') => {
+                    self.pos += 1;
+                    left = Expr::Mul(Box::new(left), Box::new(try!(self.unary())));
+                },
+                Some('/') => {
+                    self.pos += 1;
+                    left = Expr::Div(Box::new(left), Box::new(try!(self.unary())));
+                },
+                _ => return Ok(left),
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some('-') {
+            self.pos += 1;
+            return Ok(Expr::Neg(Box::new(try!(self.unary()))));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let expr = try!(self.expr());
+                try!(self.expect(')'));
+                Ok(expr)
+            },
+            Some(ch) if ch.is_digit(10) || ch == '.' => self.number(),
+            Some(ch) if ch.is_alphabetic() => self.name(),
+            Some(_) => Err(self.error("unexpected char")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Expr, String> {
+        let start = self.pos;
+        while self.pos < self.chars.len() && (self.chars[self.pos].is_digit(10) || self.chars[self.pos] == '.') {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start .. self.pos].iter().cloned().collect();
+        match text.parse::<f32>() {
+            Ok(value) => Ok(Expr::Num(value)),
+            Err(_) => {
+                self.pos = start;
+                Err(self.error(&format!("invalid number \"{}\"", text)))
+            },
+        }
+    }
+
+    fn name(&mut self) -> Result<Expr, String> {
+        let start = self.pos;
+        while self.pos < self.chars.len() && self.chars[self.pos].is_alphanumeric() {
+            self.pos += 1;
+        }
+        let name: String = self.chars[start .. self.pos].iter().cloned().collect();
+
+        match name.as_str() {
+            "x" => return Ok(Expr::X),
+            "y" => return Ok(Expr::Y),
+            "t" => return Ok(Expr::T),
+            "pi" => return Ok(Expr::Num(::std::f32::consts::PI)),
+            _ => {},
+        }
+
+        let func = match Func::from_name(&name) {
+            Some(func) => func,
+            None => {
+                self.pos = start;
+                return Err(self.error(&format!("unknown name \"{}\"", name)));
+            },
+        };
+
+        try!(self.expect('('));
+        let mut args = vec![try!(self.expr())];
+        while self.peek() == Some(',') {
+            self.pos += 1;
+            args.push(try!(self.expr()));
+        }
+        try!(self.expect(')'));
+
+        if args.len() != func.arity() {
+            return Err(self.error(&format!("{} takes {} arguments", name, func.arity())));
+        }
+        Ok(Expr::Call(func, args))
+    }
+}
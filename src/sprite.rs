@@ -0,0 +1,339 @@
+use std::collections::BTreeMap;
+
+use color::Color;
+
+/// A text sprite. In the YAML it is either a plain string, or a map with the
+/// text and the options:
+///
+/// ```yaml
+/// sprites:
+///   - " ><> "
+///   - text: |
+///       .  o
+///        ><>
+///     transparent: " "
+///   - text: " ><(([°> "
+///     colors: " 0011123 "
+///     palette: [ { Indexed: 208 }, { Indexed: 214 }, Default, { Rgb: [ 0, 255, 255 ] } ]
+///   - [ " ><> ", " >=> " ]
+///   - frames: [ " o ", " O ", " * " ]
+///     transparent: " "
+/// ```
+///
+/// The text can have multiple lines. Chars equal to the `transparent` char
+/// are not drawn, so the background stays visible.
+///
+/// The `colors` map has the same shape as the text. Each hex digit selects
+/// the foreground color from the `palette`, other chars (and digits without
+/// a palette entry) are drawn with the current color of the `Context`.
+///
+/// A list of strings, or `frames` instead of `text`, is an animated sprite.
+/// The `Animate` operator selects the frame which `Draw` uses. The color map
+/// applies to every frame.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Sprite {
+    Text(String),
+    Frames(Vec<String>),
+    Def(SpriteDef),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SpriteDef {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frames: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transparent: Option<char>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub colors: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub palette: Vec<Color>,
+}
+
+/// The `sprites` of the YAML, a list or a map from names to sprites:
+///
+/// ```yaml
+/// sprites:
+///   fish: " ><> "
+///   shark: " ><(([°> "
+/// ```
+///
+/// Named sprites are numbered in the order of their names, which is how they
+/// are stored in the bytecode.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum SpriteTable {
+    List(Vec<Sprite>),
+    Named(BTreeMap<String, Sprite>),
+}
+
+impl SpriteTable {
+    /// The names, empty for a list, and the sprites in index order.
+    pub fn into_parts(self) -> (Vec<String>, Vec<Sprite>) {
+        match self {
+            SpriteTable::List(sprites) => (vec![], sprites),
+            SpriteTable::Named(map) => map.into_iter().unzip(),
+        }
+    }
+}
+
+/// How `Animate` steps through the frames of a sprite.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AnimMode {
+    /// 0, 1, 2, 0, 1, 2, ...
+    Loop,
+    /// 0, 1, 2, 1, 0, 1, ...
+    PingPong,
+    /// 0, 1, 2, 2, 2, ...
+    Once,
+}
+
+/// Which way a sprite is drawn. Sprites are written facing right, `Left`
+/// mirrors them horizontally.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Facing {
+    Right,
+    Left,
+    /// Mirrored when the speed is negative, so that it faces where it moves.
+    Auto,
+}
+
+impl Facing {
+    pub fn is_mirrored(&self, speed: f32) -> bool {
+        match *self {
+            Facing::Right => false,
+            Facing::Left => true,
+            Facing::Auto => speed < 0.0,
+        }
+    }
+}
+
+/// What `Draw` does at the left and right edges of the canvas.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Edge {
+    /// Continue from the other side.
+    Wrap,
+    /// Cut off the parts outside, the sprite can slide in from off-screen.
+    Clip,
+    /// Move back and forth between the edges.
+    Bounce,
+    /// Stop at the edge.
+    Clamp,
+}
+
+impl Edge {
+    /// The column of the sprite's left side and the direction it is moving
+    /// in, for a sprite `sprite_width` wide on a row `width` wide, which
+    /// would be at `pos` without the edges.
+    ///
+    /// For `Wrap` the column is in `0 .. width`, for the others it can be
+    /// negative or past the row. On a row with no width it is 0.
+    pub fn position(&self, pos: i64, speed: f32, sprite_width: i64, width: i64) -> (i64, f32) {
+        if width <= 0 {
+            return (0, speed);
+        }
+
+        // the furthest the left side can go and the sprite is still inside
+        let range = if width > sprite_width { width - sprite_width } else { 0 };
+
+        match *self {
+            Edge::Wrap => (((pos % width) + width) % width, speed),
+            Edge::Clip => (pos, speed),
+            Edge::Bounce => {
+                if range == 0 {
+                    return (0, speed);
+                }
+                let period = 2 * range;
+                let p = ((pos % period) + period) % period;
+                if p <= range { (p, speed) } else { (period - p, -speed) }
+            },
+            Edge::Clamp => (if pos < 0 { 0 } else if pos > range { range } else { pos }, speed),
+        }
+    }
+}
+
+/// Char pairs which swap places when a sprite is mirrored.
+pub const DEFAULT_MIRROR: [&'static str; 5] = ["<>", "()", "[]", "{}", "/\\"];
+
+/// The mirrored version of a char, looked up in both directions in the pairs
+/// of the mirror table. Other chars are unchanged.
+pub fn mirror_char(ch: char, mirror: &[String]) -> char {
+    for pair in mirror.iter() {
+        let mut chars = pair.chars();
+        if let (Some(a), Some(b)) = (chars.next(), chars.next()) {
+            if ch == a { return b; }
+            if ch == b { return a; }
+        }
+    }
+    ch
+}
+
+impl AnimMode {
+    /// The frame index at `time`, for `n_frames` frames shown at `fps` frames
+    /// per second.
+    pub fn frame_at(&self, time: f32, fps: f32, n_frames: usize) -> usize {
+        if n_frames <= 1 || !(time * fps > 0.0) {
+            return 0;
+        }
+
+        let step = (time * fps) as usize;
+
+        match *self {
+            AnimMode::Loop => step % n_frames,
+            AnimMode::PingPong => {
+                let period = 2 * n_frames - 2;
+                let p = step % period;
+                if p < n_frames { p } else { period - p }
+            },
+            AnimMode::Once => if step < n_frames { step } else { n_frames - 1 },
+        }
+    }
+}
+
+impl Sprite {
+    pub fn new(text: &str, transparent: Option<char>) -> Sprite {
+        Sprite::from_parts(vec![String::from(text)], transparent, None, vec![])
+    }
+
+    /// Uses the simplest YAML form which can represent the parts.
+    fn from_parts(frames: Vec<String>,
+                  transparent: Option<char>,
+                  colors: Option<String>,
+                  palette: Vec<Color>) -> Sprite {
+
+        if transparent.is_none() && colors.is_none() && palette.is_empty() {
+            if frames.len() == 1 {
+                return Sprite::Text(frames[0].clone());
+            }
+            return Sprite::Frames(frames);
+        }
+
+        let (text, frames) = if frames.len() == 1 {
+            (frames[0].clone(), vec![])
+        } else {
+            (String::new(), frames)
+        };
+
+        Sprite::Def(SpriteDef {
+            text: text,
+            frames: frames,
+            transparent: transparent,
+            colors: colors,
+            palette: palette,
+        })
+    }
+
+    fn frame_strings(&self) -> Vec<String> {
+        self.frames().iter().map(|f| String::from(*f)).collect()
+    }
+
+    /// Adds a color map and its palette.
+    pub fn with_colors(self, colors: &str, palette: Vec<Color>) -> Sprite {
+        Sprite::from_parts(self.frame_strings(),
+                           self.transparent(),
+                           Some(String::from(colors)),
+                           palette)
+    }
+
+    /// Replaces the frames.
+    pub fn with_frames(self, frames: Vec<String>) -> Sprite {
+        Sprite::from_parts(frames,
+                           self.transparent(),
+                           self.colors().map(String::from),
+                           self.palette().to_vec())
+    }
+
+    /// The text of the first frame.
+    pub fn text(&self) -> &str {
+        self.frames()[0]
+    }
+
+    /// The text of each frame, at least one.
+    pub fn frames(&self) -> Vec<&str> {
+        match *self {
+            Sprite::Text(ref text) => vec![text],
+            Sprite::Frames(ref frames) if !frames.is_empty() => frames.iter().map(|f| f.as_str()).collect(),
+            Sprite::Frames(_) => vec![""],
+            Sprite::Def(ref def) => {
+                if def.frames.is_empty() {
+                    vec![&def.text]
+                } else {
+                    def.frames.iter().map(|f| f.as_str()).collect()
+                }
+            },
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames().len()
+    }
+
+    pub fn transparent(&self) -> Option<char> {
+        match *self {
+            Sprite::Def(ref def) => def.transparent,
+            _ => None,
+        }
+    }
+
+    pub fn colors(&self) -> Option<&str> {
+        match *self {
+            Sprite::Def(ref def) => def.colors.as_ref().map(|c| c.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn palette(&self) -> &[Color] {
+        match *self {
+            Sprite::Def(ref def) => &def.palette,
+            _ => &[],
+        }
+    }
+
+    /// The foreground color of each cell from the color map, `None` where the
+    /// current color should be used.
+    pub fn color_lines(&self) -> Vec<Vec<Option<Color>>> {
+        let palette = self.palette();
+        match self.colors() {
+            None => vec![],
+            Some(colors) => colors.lines().map(|line| {
+                line.chars().map(|ch| {
+                    ch.to_digit(16).and_then(|n| palette.get(n as usize).cloned())
+                }).collect()
+            }).collect(),
+        }
+    }
+
+    /// The chars of each line of the first frame.
+    pub fn lines(&self) -> Vec<Vec<char>> {
+        self.frame_lines(0)
+    }
+
+    /// The chars of each line of a frame, wrapping around the number of
+    /// frames. A trailing newline, as in YAML block scalars, doesn't start a
+    /// new line.
+    pub fn frame_lines(&self, frame: usize) -> Vec<Vec<char>> {
+        let frames = self.frames();
+        frames[frame % frames.len()].lines().map(|line| line.chars().collect()).collect()
+    }
+
+    /// Width in chars of the longest line of all frames.
+    pub fn width(&self) -> usize {
+        self.frames().iter()
+            .flat_map(|f| f.lines())
+            .map(|line| line.chars().count())
+            .max().unwrap_or(0)
+    }
+
+    /// Number of lines of the tallest frame.
+    pub fn height(&self) -> usize {
+        self.frames().iter().map(|f| f.lines().count()).max().unwrap_or(0)
+    }
+}
+
+impl<'a> From<&'a str> for Sprite {
+    fn from(text: &'a str) -> Sprite {
+        Sprite::Text(String::from(text))
+    }
+}
@@ -0,0 +1,250 @@
+#![cfg(test)]
+
+use dmo::{Dmo, Context, Operator};
+use sprite::{Sprite, AnimMode, Facing, Edge};
+use layer::Layer;
+use track::Param;
+use scene::Scene;
+use transition::Blend;
+use collision::Overlap;
+use bytecode::*;
+
+const DEMO_YML: &'static str = r#"
+operators:
+  - Clear: 32
+  - Draw: [ 1, 5, 0, 8.0, Right, Wrap ]
+  - Draw: [ 0, 2, 0, 1.5, Right, Wrap ]
+  - Print
+  - Exit: 30.0
+
+context:
+  sprites:
+    - " ><(([°> "
+    - " ><> "
+"#;
+
+#[test]
+fn write_and_read_bytecode_stream() {
+    let dmo = Dmo::new_from_yml_str(DEMO_YML).unwrap();
+
+    let mut stream: Vec<u8> = Vec::new();
+    dmo.write_bytecode(&mut stream).unwrap();
+    assert_eq!(stream, dmo.to_bytecode());
+
+    let bytes: &[u8] = stream.as_slice();
+    let decoded = Dmo::read_bytecode(&mut &bytes[..]).unwrap();
+
+    assert_eq!(decoded.get_sprites(), dmo.get_sprites());
+    assert_eq!(decoded.to_bytecode(), stream);
+}
+
+#[test]
+fn read_bytecode_truncated_is_error() {
+    let dmo = Dmo::new_from_yml_str(DEMO_YML).unwrap();
+    let bytecode = dmo.to_bytecode();
+
+    let truncated: &[u8] = &bytecode[.. bytecode.len() - 2];
+    assert!(Dmo::read_bytecode(&mut &truncated[..]).is_err());
+}
+
+#[test]
+fn read_bytecode_invalid_char_is_error() {
+    // 50 x 1 canvas without a viewport, colors, mirror pairs, layers, tracks,
+    // emitters, shaders, variables, fonts, banners or post effects, seed 0,
+    // one 1 x 1 sprite without a transparent char, one frame, one line, one
+    // char, 0xD800 is a surrogate and not a valid char
+    let data: &[u8] = &[50, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                        1, 1, 0, 1, 0, 0xFF, 0xFF, 0xFF, 0xFF,
+                        1, 0, 1, 0,
+                        1, 0, 0x00, 0xD8, 0x00, 0x00,
+                        0];
+    assert!(Dmo::read_bytecode(&mut &data[..]).is_err());
+}
+
+#[test]
+fn read_bytecode_from_static_demo() {
+    let source = Dmo::new_from_yml_str(include_str!("../../examples/fish-demo.yml")).unwrap();
+    let bytecode = source.to_bytecode();
+    let data: &[u8] = bytecode.as_slice();
+    let dmo = Dmo::read_bytecode(&mut &data[..]).unwrap();
+
+    assert_eq!(dmo.get_sprites().len(), 4);
+    assert_eq!(dmo.to_bytecode().as_slice(), data);
+}
+
+// Expected values are written out big-endian, as you would read them, and then
+// reversed, so the tests don't use the same shifting as the code under test.
+
+fn reversed(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().rev().cloned().collect()
+}
+
+#[test]
+fn write_numbers_little_endian() {
+    let mut v: Vec<u8> = Vec::new();
+    write_u16(&mut v, 0x1234).unwrap();
+    assert_eq!(v, reversed(&[0x12, 0x34]));
+
+    let mut v: Vec<u8> = Vec::new();
+    write_u32(&mut v, 0x12345678).unwrap();
+    assert_eq!(v, reversed(&[0x12, 0x34, 0x56, 0x78]));
+
+    let mut v: Vec<u8> = Vec::new();
+    write_i32(&mut v, -2).unwrap();
+    assert_eq!(v, reversed(&[0xFF, 0xFF, 0xFF, 0xFE]));
+
+    // 1.5f32 is 0x3FC00000
+    let mut v: Vec<u8> = Vec::new();
+    write_f32(&mut v, 1.5).unwrap();
+    assert_eq!(v, reversed(&[0x3F, 0xC0, 0x00, 0x00]));
+
+    // -0.1f64 is 0xBFB999999999999A
+    let mut v: Vec<u8> = Vec::new();
+    write_f64(&mut v, -0.1).unwrap();
+    assert_eq!(v, reversed(&[0xBF, 0xB9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A]));
+
+    let mut v: Vec<u8> = Vec::new();
+    push_f32(&mut v, 1.5);
+    assert_eq!(v, reversed(&[0x3F, 0xC0, 0x00, 0x00]));
+}
+
+#[test]
+fn read_numbers_little_endian() {
+    let data = vec![reversed(&[0x12, 0x34]),
+                    reversed(&[0x12, 0x34, 0x56, 0x78]),
+                    reversed(&[0xFF, 0xFF, 0xFF, 0xFE]),
+                    reversed(&[0x3F, 0xC0, 0x00, 0x00]),
+                    reversed(&[0xBF, 0xB9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A])].concat();
+
+    let mut blob = DataBlob::new(data.as_slice());
+    assert_eq!(blob.read_u16().unwrap(), 0x1234);
+    assert_eq!(blob.read_u32().unwrap(), 0x12345678);
+    assert_eq!(blob.read_i32().unwrap(), -2);
+    assert_eq!(blob.read_f32().unwrap(), 1.5);
+    assert_eq!(blob.read_f64().unwrap(), -0.1);
+    assert_eq!(blob.get_idx(), data.len());
+    assert!(blob.read_u8().is_err());
+}
+
+#[test]
+fn big_endian_bytes_are_not_confused() {
+    // Reading the big-endian bytes must give the byte-swapped value, also on a
+    // big-endian host.
+    let be: &[u8] = &[0x12, 0x34, 0x56, 0x78];
+    let mut blob = DataBlob::new(be);
+    assert_eq!(blob.read_u32().unwrap(), 0x78563412);
+
+    for n in [0u32, 1, 0xFF, 0x100, 0xDEADBEEF, u32::max_value()].iter() {
+        let le = u32_to_le_bytes(*n);
+        assert_eq!(le_bytes_to_u32(&le), *n);
+        let be: Vec<u8> = reversed(&le);
+        assert_eq!(le_bytes_to_u32(&[be[0], be[1], be[2], be[3]]), n.swap_bytes());
+    }
+
+    for n in [0u64, 1, 0x0102030405060708, u64::max_value()].iter() {
+        assert_eq!(le_bytes_to_u64(&u64_to_le_bytes(*n)), *n);
+    }
+}
+
+#[test]
+fn verify_reports_every_error() {
+    let mut context = Context::new();
+    context.sprites.push(Sprite::from(" ><> "));
+
+    let operators = vec![
+        Operator::Clear(0xD800),
+        Operator::Draw(0, Param::Value(0.0), Param::Value(0.0), Param::Value(1.0), Facing::Right, Edge::Wrap),
+        Operator::Draw(9, Param::Value(0.0), Param::Value(0.0), Param::Value(::std::f32::NAN), Facing::Right, Edge::Wrap),
+        Operator::Print,
+        Operator::Exit(Param::Value(::std::f32::INFINITY)),
+    ];
+
+    let errors = verify(&context, &operators).unwrap_err();
+
+    assert_eq!(errors.len(), 4);
+    assert_eq!(errors[0], VerifyError::InvalidCharcode { op_idx: 0, charcode: 0xD800 });
+    assert_eq!(errors[1], VerifyError::SpriteOutOfRange { op_idx: 2, sprite_idx: 9, n_sprites: 1 });
+    match errors[2] {
+        VerifyError::NonFiniteFloat { op_idx, value } => {
+            assert_eq!(op_idx, 2);
+            assert!(value.is_nan());
+        },
+        _ => panic!("expected NonFiniteFloat"),
+    }
+    assert_eq!(errors[3], VerifyError::NonFiniteFloat { op_idx: 4, value: ::std::f32::INFINITY });
+}
+
+#[test]
+fn build_jit_fn_requires_verify() {
+    let text = r#"
+operators:
+  - Draw: [ 3, 0, 0, 0.3, Right, Wrap ]
+  - Print
+
+context:
+  sprites:
+    - " ><> "
+"#;
+    let mut dmo = Dmo::new_from_yml_str(text).unwrap();
+    assert!(dmo.verify().is_err());
+    assert!(dmo.build_jit_fn().is_err());
+
+    let mut dmo = Dmo::new_from_yml_str(DEMO_YML).unwrap();
+    assert!(dmo.build_jit_fn().is_ok());
+}
+
+fn prints(n: usize) -> Vec<Operator> {
+    (0 .. n).map(|_| Operator::Print).collect()
+}
+
+#[test]
+fn verify_counts_fit_in_a_byte() {
+    let mut context = Context::new();
+    context.sprites.push(Sprite::from(" ><> "));
+    for n in 0 .. 256 {
+        context.layers.push(Layer::new(&format!("layer {}", n)));
+    }
+
+    let mut dmo = Dmo::new(context, prints(300))
+        .with_timeline(vec![Scene::new("intro", 0.0, None, prints(255))], None);
+
+    let errors = dmo.verify().unwrap_err();
+    assert_eq!(errors, vec![VerifyError::TooMany { section: "layers", count: 256 },
+                            VerifyError::TooMany { section: "operators", count: 300 }]);
+    assert_eq!(errors[1].to_string(), "300 operators are too many, there can be at most 255");
+    assert!(dmo.build_jit_fn().is_err());
+
+    let dmo = Dmo::new(Context::new(), vec![])
+        .with_timeline(vec![Scene::new("intro", 0.0, None, prints(256))], None);
+    assert_eq!(dmo.verify().unwrap_err(),
+               vec![VerifyError::InScene {
+                   scene: 0,
+                   error: Box::new(VerifyError::TooMany { section: "operators", count: 256 }),
+               }]);
+}
+
+#[test]
+fn read_bytecode_invalid_codes_are_errors() {
+    // the code is counted from the end, after the operator there are no
+    // scenes and no end time
+    let cases = vec![
+        (Operator::Draw(0, Param::Value(0.0), Param::Value(0.0), Param::Value(1.0), Facing::Right, Edge::Wrap),
+         4, "invalid facing: 0x9"),
+        (Operator::Draw(0, Param::Value(0.0), Param::Value(0.0), Param::Value(1.0), Facing::Right, Edge::Wrap),
+         3, "invalid edge mode: 0x9"),
+        (Operator::Animate(0, 1.0, AnimMode::Loop), 3, "invalid animation mode: 0x9"),
+        (Operator::Transition(Blend::Fade, Param::Value(1.0), 0), 12, "invalid blend: 0x9"),
+        (Operator::Collide(0, 0, Overlap::Bounds, 0), 4, "invalid overlap: 0x9"),
+    ];
+
+    for (op, from_end, msg) in cases.into_iter() {
+        let mut context = Context::new();
+        context.sprites.push(Sprite::from(" ><> "));
+        let mut data = Dmo::new(context, vec![op]).to_bytecode();
+
+        let at = data.len() - from_end;
+        assert!(data[at] < 9);
+        data[at] = 9;
+        assert_eq!(Dmo::read_bytecode(&mut &data[..]).err().unwrap().to_string(), msg);
+    }
+}
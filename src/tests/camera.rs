@@ -0,0 +1,130 @@
+#![cfg(test)]
+
+use dmo::{Dmo, Context, Operator};
+use sprite::Sprite;
+use track::Param;
+use bytecode::{Bytecode, VerifyError, verify};
+
+/// Frames from the JIT fn at the times, checked against the interpreter.
+fn frames_at(text: &str, times: &[f32]) -> Vec<String> {
+    let mut jit = Dmo::new_from_yml_str(text).unwrap();
+    jit.build_jit_fn().unwrap();
+    let mut interpreter = Dmo::new_from_yml_str(text).unwrap();
+
+    let mut frames: Vec<String> = vec![];
+    let mut now = 0.0;
+    for t in times.iter() {
+        jit.add_to_time(t - now);
+        interpreter.add_to_time(t - now);
+        now = *t;
+
+        jit.run_jit_fn();
+        interpreter.run_operators();
+        assert_eq!(jit.get_context().render_frame(), interpreter.get_context().render_frame());
+        assert_eq!(jit.get_context().camera, interpreter.get_context().camera);
+        frames.push(jit.get_context().render_frame());
+    }
+    frames
+}
+
+const PAN_YML: &'static str = r#"
+operators:
+  - Draw: [ "reef", 0, 0, 0.0, Right, Clip ]
+  - Camera: [ { track: "pan" }, 0 ]
+
+context:
+  width: 12
+  height: 2
+  view_width: 4
+  tracks:
+    - name: "pan"
+      keys:
+        - { t: 0.0, value: 0.0 }
+        - { t: 2.0, value: 12.0 }
+  sprites:
+    reef: "0123456789AB\nabcdefghijkl"
+"#;
+
+#[test]
+fn camera_pans_over_the_canvas() {
+    let frames = frames_at(PAN_YML, &[0.0, 0.5, 2.0]);
+
+    assert_eq!(frames[0], "\x1b[H     0123\n     abcd\n");
+    assert_eq!(frames[1], "\x1b[H     3456\n     defg\n");
+    // the viewport stays on the canvas
+    assert_eq!(frames[2], "\x1b[H     89AB\n     ijkl\n");
+}
+
+const FOLLOW_YML: &'static str = r#"
+operators:
+  - Clear: 46
+  - Draw: [ "fish", 2, 1, 4.0, Right, Clip ]
+  - Follow: "fish"
+
+context:
+  width: 20
+  height: 3
+  view_width: 6
+  view_height: 1
+  sprites:
+    fish: "><>"
+"#;
+
+#[test]
+fn camera_follows_a_sprite() {
+    let frames = frames_at(FOLLOW_YML, &[0.0, 1.0, 4.0]);
+
+    assert_eq!(frames[0], "     ..><>.\r");
+    assert_eq!(frames[1], "     ..><>.\r");
+    assert_eq!(frames[2], "     ....><\r");
+}
+
+#[test]
+fn follow_before_draw_keeps_the_camera() {
+    let mut context = Context::new();
+    context.set_size(10, 1);
+    context.view_width = Some(4);
+    context.sprites.push(Sprite::from("o"));
+
+    context.impl_camera(3.0, 0.0);
+    context.impl_follow(0);
+    assert_eq!(context.camera, (3, 0));
+
+    context.impl_draw(0, 9.0, 0, 0, 0, 0.0);
+    context.impl_follow(0);
+    assert_eq!(context.camera, (6, 0));
+}
+
+#[test]
+fn viewport_in_bytecode() {
+    let dmo = Dmo::new_from_yml_str(FOLLOW_YML).unwrap();
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+    let context = decoded.get_context();
+    assert_eq!((context.width, context.height), (20, 3));
+    assert_eq!((context.view_width, context.view_height), (Some(6), Some(1)));
+    assert_eq!(context.view_size(), (6, 1));
+
+    // without a viewport, it is the whole canvas
+    let dmo = Dmo::new_from_yml_str(&FOLLOW_YML.replace("  view_width: 6\n  view_height: 1\n", "")).unwrap();
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+    assert_eq!(decoded.get_context().view_width, None);
+    assert_eq!(decoded.get_context().view_size(), (20, 3));
+}
+
+#[test]
+fn verify_viewport_and_follow() {
+    let mut context = Context::new();
+    context.set_size(10, 2);
+    context.view_width = Some(12);
+
+    let operators = vec![
+        Operator::Camera(Param::Value(1.0), Param::track(0)),
+        Operator::Follow(0),
+    ];
+
+    assert_eq!(verify(&context, &operators), Err(vec![
+        VerifyError::InvalidViewport { width: 12, height: 2 },
+        VerifyError::TrackOutOfRange { op_idx: 0, track: 0, n_tracks: 0 },
+        VerifyError::SpriteOutOfRange { op_idx: 1, sprite_idx: 0, n_sprites: 0 },
+    ]));
+}
@@ -0,0 +1,243 @@
+#![cfg(test)]
+
+use dmo::{Dmo, Context, Operator, edge_to_code};
+use sprite::{Sprite, Edge};
+use bytecode::{Bytecode, VerifyError};
+
+#[test]
+fn draw_on_rows_and_render_frame() {
+    let mut context = Context::new();
+    context.set_size(8, 3);
+    context.sprites.push(Sprite::from("><>"));
+    context.impl_clear(' ' as u32);
+
+    context.impl_draw(0, 1.0, 0, 0, 0, 0.0);
+    context.impl_draw(0, 6.0, 2, 0, 0, 0.0);
+
+    let expected = String::from("\x1b[H") +
+        "      ><>    \n" +
+        "             \n" +
+        "     >     ><\n";
+
+    assert_eq!(context.render_frame(), expected);
+}
+
+#[test]
+fn draw_edge_modes() {
+    let mut context = Context::new();
+    context.set_size(8, 4);
+    context.sprites.push(Sprite::from("ab"));
+    context.impl_clear('.' as u32);
+
+    context.impl_draw(0, 7.0, 0, 0, edge_to_code(Edge::Wrap), 0.0);
+    context.impl_draw(0, 7.0, 1, 0, edge_to_code(Edge::Clip), 0.0);
+    context.impl_draw(0, 7.0, 2, 0, edge_to_code(Edge::Clamp), 0.0);
+    context.impl_draw(0, 7.0, 3, 0, edge_to_code(Edge::Bounce), 0.0);
+
+    let expected = String::from("\x1b[H") +
+        "     b......a\n" +
+        "     .......a\n" +
+        "     ......ab\n" +
+        "     .....ab.\n";
+
+    assert_eq!(context.render_frame(), expected);
+
+    // Bouncing back off the right edge turns the direction around.
+    assert_eq!(Edge::Bounce.position(7, 1.0, 2, 8), (5, -1.0));
+    assert_eq!(Edge::Bounce.position(-2, 1.0, 2, 8), (2, -1.0));
+    assert_eq!(Edge::Clip.position(-2, 1.0, 2, 8), (-2, 1.0));
+}
+
+#[test]
+fn edges_on_an_empty_canvas() {
+    for edge in [Edge::Wrap, Edge::Clip, Edge::Bounce, Edge::Clamp].iter() {
+        assert_eq!(edge.position(7, 1.0, 2, 0), (0, 1.0));
+    }
+
+    let mut context = Context::new();
+    context.set_size(0, 1);
+    context.sprites.push(Sprite::from("ab"));
+    context.impl_draw(0, 3.0, 0, 0, edge_to_code(Edge::Wrap), 0.0);
+    assert_eq!(context.render_frame(), "     \r");
+}
+
+#[test]
+fn single_line_renders_with_rewind() {
+    let mut context = Context::new();
+    context.set_size(5, 1);
+    context.impl_clear('.' as u32);
+
+    assert_eq!(context.render_frame(), "     .....\r");
+}
+
+#[test]
+fn clear_with_an_invalid_char_without_verify() {
+    let mut context = Context::new();
+    context.set_size(5, 1);
+    let mut dmo = Dmo::new(context, vec![Operator::Clear('.' as u32), Operator::Clear(0xD800)]);
+
+    dmo.run_operators();
+    assert_eq!(dmo.get_context().render_frame(), "     .....\r");
+}
+
+#[test]
+fn canvas_size_in_yml_and_bytecode() {
+    let text = r#"
+operators:
+  - Draw: [ 0, 0, 3, 1.0, Right, Wrap ]
+  - Print
+
+context:
+  width: 20
+  height: 4
+  sprites:
+    - " ><> "
+"#;
+
+    let dmo = Dmo::new_from_yml_str(text).unwrap();
+    assert_eq!(dmo.get_context().buffer.len(), 80);
+
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+    assert_eq!(decoded.get_context().width, 20);
+    assert_eq!(decoded.get_context().height, 4);
+    assert_eq!(decoded.get_context().buffer.len(), 80);
+
+    // Single-line demos without a size are 50 x 1.
+    let dmo = Dmo::new_from_yml_str("operators: []\ncontext:\n  sprites: []\n").unwrap();
+    assert_eq!(dmo.get_context().width, 50);
+    assert_eq!(dmo.get_context().height, 1);
+}
+
+#[test]
+fn verify_row_out_of_range() {
+    let text = r#"
+operators:
+  - Draw: [ 0, 0, 4, 1.0, Right, Wrap ]
+
+context:
+  width: 20
+  height: 4
+  sprites:
+    - " ><> "
+"#;
+
+    let dmo = Dmo::new_from_yml_str(text).unwrap();
+    assert_eq!(dmo.verify().unwrap_err(),
+               vec![VerifyError::RowOutOfRange { op_idx: 0, y: 4, height: 4 }]);
+}
+
+#[test]
+fn multi_line_sprite_with_transparency() {
+    let text = r#"
+operators:
+  - Clear: 46
+  - Draw: [ 0, 0, 0, 0.0, Right, Wrap ]
+  - Draw: [ 1, 1, 0, 0.0, Right, Wrap ]
+  - Print
+
+context:
+  width: 6
+  height: 3
+  sprites:
+    - "~~~~~~"
+    - text: |
+        o
+         ><>
+      transparent: " "
+"#;
+
+    let mut dmo = Dmo::new_from_yml_str(text).unwrap();
+    dmo = Dmo::from_bytecode(dmo.to_bytecode());
+
+    {
+        let ref sprite = dmo.get_sprites()[1];
+        assert_eq!(sprite.width(), 4);
+        assert_eq!(sprite.height(), 2);
+        assert_eq!(sprite.transparent(), Some(' '));
+    }
+
+    let mut context = Context::new();
+    context.set_size(6, 3);
+    context.sprites = dmo.get_sprites().clone();
+    context.impl_clear('.' as u32);
+    context.impl_draw(0, 0.0, 1, 0, 0, 0.0);
+    context.impl_draw(1, 1.0, 1, 0, 0, 0.0);
+
+    let expected = String::from("\x1b[H") +
+        "     ......\n" +
+        "     ~o~~~~\n" +
+        "     ..><>.\n";
+
+    assert_eq!(context.render_frame(), expected);
+}
+
+#[test]
+fn draw_can_leave_out_the_later_arguments() {
+    let short = r#"
+operators:
+  - Draw: [ 0, 5, 0.3 ]
+  - Draw: [ 0, 5, 2, 0.3 ]
+  - Draw: [ 0, 5, 2, 0.3, Auto ]
+  - Print
+
+scenes:
+  - name: intro
+    start: 0.0
+    operators: [ { Draw: [ fish, 1, 8.0 ] } ]
+
+context:
+  height: 3
+  sprites:
+    fish: " ><> "
+"#;
+    let full = r#"
+operators:
+  - Draw: [ 0, 5, 0, 0.3, Right, Wrap ]
+  - Draw: [ 0, 5, 2, 0.3, Right, Wrap ]
+  - Draw: [ 0, 5, 2, 0.3, Auto, Wrap ]
+  - Print
+
+scenes:
+  - name: intro
+    start: 0.0
+    operators: [ { Draw: [ fish, 1, 0, 8.0, Right, Wrap ] } ]
+
+context:
+  height: 3
+  sprites:
+    fish: " ><> "
+"#;
+    assert_eq!(Dmo::new_from_yml_str(short).unwrap().to_bytecode(),
+               Dmo::new_from_yml_str(full).unwrap().to_bytecode());
+
+    assert!(Dmo::new_from_yml_str(&short.replace("[ 0, 5, 0.3 ]", "[ 0, 5 ]")).is_err());
+}
+
+#[test]
+fn single_line_demo_still_loads() {
+    let text = r#"
+operators:
+  - Clear: 32
+  - Draw: [ 3, 0, 0.3 ]
+  - Draw: [ 1, 5, 8.0 ]
+  - Draw: [ 0, 2, 1.5 ]
+  - Draw: [ 1, 30, 4.0 ]
+  - Draw: [ 2, 15, 6.0 ]
+  - Print
+  - Exit: 30.0
+
+context:
+  sprites:
+    - " ><(([°> "
+    - " ><> "
+    - " }-<ø> "
+    - "¸¸,,¸¸,¸¸,,¸¸,¸¸,,¸¸,¸¸,,¸¸,¸¸,,¸¸,¸¸,,¸¸,¸¸,,¸¸,,"
+"#;
+    let mut dmo = Dmo::new_from_yml_str(text).unwrap();
+    assert!(dmo.build_jit_fn().is_ok());
+
+    dmo.run_operators();
+    let frame = dmo.get_context().render_frame();
+    assert!(frame.contains(" ><(([°> "));
+    assert!(frame.ends_with("\r"));
+}
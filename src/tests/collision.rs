@@ -0,0 +1,129 @@
+#![cfg(test)]
+
+use dmo::{Dmo, Context, Operator, overlap_to_code};
+use sprite::Sprite;
+use collision::{Overlap, Collision};
+use bytecode::{Bytecode, VerifyError, verify};
+
+const TURN_YML: &'static str = r#"
+operators:
+  - Clear: 46
+  - Draw: [ "rock", 7, 0, 0.0, Right, Clip ]
+  - Add: [ "x", { var: "speed" } ]
+  - Draw: [ "fish", { var: "x" }, 0, 0.0, Right, Clip ]
+  - Collide: [ "fish", "rock", Cells, "hit" ]
+  - Branch: [ "hit", Equal, 0.0, 1 ]
+  - Mul: [ "speed", -1.0 ]
+
+context:
+  width: 10
+  vars:
+    - name: "x"
+    - name: "speed"
+      value: 1.0
+    - name: "hit"
+      kind: Int
+  sprites:
+    rock: "@"
+    fish: "><>"
+"#;
+
+#[test]
+fn fish_turns_around_at_the_rock() {
+    let mut jit = Dmo::new_from_yml_str(TURN_YML).unwrap();
+    jit.build_jit_fn().unwrap();
+    let mut interpreter = Dmo::new_from_yml_str(TURN_YML).unwrap();
+
+    let mut frames: Vec<String> = vec![];
+    let mut events: Vec<Vec<Collision>> = vec![];
+    for _ in 0 .. 6 {
+        jit.run_jit_fn();
+        interpreter.run_operators();
+        assert_eq!(jit.get_context().render_frame(), interpreter.get_context().render_frame());
+        assert_eq!(jit.get_collisions(), interpreter.get_collisions());
+
+        frames.push(jit.get_context().render_frame());
+        events.push(jit.get_collisions().clone());
+    }
+
+    assert_eq!(frames[3], "     ....><>@..\r");
+    assert_eq!(frames[4], "     .....><>..\r");
+    assert_eq!(frames[5], "     ....><>@..\r");
+
+    // the events only last for their frame, the named sprites are sorted
+    assert_eq!(events[4], vec![Collision { a: 0, b: 1 }]);
+    assert!(events[3].is_empty() && events[5].is_empty());
+    assert_eq!(jit.get_context().var_value(1), -1.0);
+}
+
+#[test]
+fn bounds_and_cells() {
+    let mut context = Context::new();
+    context.set_size(10, 3);
+    context.sprites.push(Sprite::new("x x\nx x", Some(' ')));
+    context.sprites.push(Sprite::from("o"));
+
+    context.impl_draw(0, 2.0, 0, 0, 0, 0.0);
+    context.impl_draw(1, 3.0, 1, 0, 0, 0.0);
+
+    context.impl_collide(0, 1, overlap_to_code(Overlap::Bounds), 0);
+    assert_eq!(context.var_value(0), 1.0);
+    // the `o` is in the transparent gap of the other sprite
+    context.impl_collide(0, 1, overlap_to_code(Overlap::Cells), 0);
+    assert_eq!(context.var_value(0), 0.0);
+
+    // below the bounds
+    context.impl_draw(1, 3.0, 2, 0, 0, 0.0);
+    context.impl_draw(1, 9.0, 2, 0, 0, 0.0);
+    context.impl_collide(1, 0, overlap_to_code(Overlap::Bounds), 0);
+    assert_eq!(context.collisions, vec![Collision { a: 0, b: 1 }, Collision { a: 1, b: 0 }]);
+
+    context.start_frame();
+    assert!(context.collisions.is_empty());
+    context.impl_collide(0, 1, overlap_to_code(Overlap::Bounds), 0);
+    assert_eq!(context.var_value(0), 0.0);
+}
+
+#[test]
+fn draws_of_the_same_sprite() {
+    let mut context = Context::new();
+    context.set_size(10, 1);
+    context.sprites.push(Sprite::from("oo"));
+
+    // a single draw doesn't overlap with itself
+    context.impl_draw(0, 2.0, 0, 0, 0, 0.0);
+    context.impl_collide(0, 0, overlap_to_code(Overlap::Cells), 0);
+    assert_eq!(context.var_value(0), 0.0);
+
+    context.impl_draw(0, 6.0, 0, 0, 0, 0.0);
+    context.impl_collide(0, 0, overlap_to_code(Overlap::Cells), 0);
+    assert_eq!(context.var_value(0), 0.0);
+
+    context.impl_draw(0, 3.0, 0, 0, 0, 0.0);
+    context.impl_collide(0, 0, overlap_to_code(Overlap::Cells), 0);
+    assert_eq!(context.var_value(0), 1.0);
+}
+
+#[test]
+fn collide_in_bytecode() {
+    let dmo = Dmo::new_from_yml_str(TURN_YML).unwrap();
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+
+    match decoded.get_operators()[4] {
+        Operator::Collide(0, 1, Overlap::Cells, 2) => {},
+        _ => panic!("expected Collide"),
+    }
+}
+
+#[test]
+fn verify_collide() {
+    let mut context = Context::new();
+    context.sprites.push(Sprite::from("o"));
+
+    let operators = vec![Operator::Collide(0, 1, Overlap::Bounds, 0)];
+
+    assert_eq!(verify(&context, &operators), Err(vec![
+        VerifyError::SpriteOutOfRange { op_idx: 0, sprite_idx: 1, n_sprites: 1 },
+        VerifyError::VarOutOfRange { op_idx: 0, var: 0, n_vars: 0 },
+    ]));
+}
@@ -0,0 +1,102 @@
+#![cfg(test)]
+
+use dmo::{Dmo, Context};
+use sprite::Sprite;
+use color::*;
+use bytecode::Bytecode;
+
+#[test]
+fn color_packs_to_u32() {
+    for c in [Color::Default, Color::Indexed(0), Color::Indexed(208), Color::Rgb(1, 2, 3)].iter() {
+        assert_eq!(Color::from_u32(c.to_u32()), *c);
+    }
+    assert_eq!(rgb_to_ansi256(0, 0, 0), 16);
+    assert_eq!(rgb_to_ansi256(255, 255, 255), 231);
+    assert_eq!(rgb_to_ansi256(255, 0, 0), 196);
+}
+
+#[test]
+fn sgr_only_where_the_style_changes() {
+    let plain = Style::default();
+    let orange = Style::new(Color::Rgb(255, 140, 0), Color::Default, ATTR_BOLD);
+    let cells = vec![Cell::new('a', plain),
+                     Cell::new('b', orange),
+                     Cell::new('c', orange),
+                     Cell::new('d', plain)];
+
+    assert_eq!(cells_to_string(&cells, ColorMode::NoColor), "abcd");
+    assert_eq!(cells_to_string(&cells, ColorMode::TrueColor),
+               "a\x1b[0;1;38;2;255;140;0mbc\x1b[0md");
+    assert_eq!(cells_to_string(&cells, ColorMode::Ansi256),
+               "a\x1b[0;1;38;5;214mbc\x1b[0md");
+
+    // reset at the end
+    let cells = vec![Cell::new('x', Style::new(Color::Default, Color::Indexed(4), ATTR_UNDERLINE))];
+    assert_eq!(cells_to_string(&cells, ColorMode::Ansi256), "\x1b[0;4;48;5;4mx\x1b[0m");
+}
+
+#[test]
+fn draw_with_color_map_and_pen() {
+    let mut context = Context::new();
+    context.set_size(4, 1);
+    context.color_mode = ColorMode::Ansi256;
+    context.sprites.push(Sprite::from("><>").with_colors("0 1", vec![Color::Indexed(1), Color::Indexed(2)]));
+
+    context.impl_set_color(Color::Indexed(7).to_u32(), Color::Default.to_u32(), 0);
+    context.impl_clear(' ' as u32);
+    context.impl_draw(0, 0.0, 0, 0, 0, 0.0);
+
+    assert_eq!(context.buffer[0].style.fg, Color::Indexed(1));
+    assert_eq!(context.buffer[1].style.fg, Color::Indexed(7));
+    assert_eq!(context.buffer[2].style.fg, Color::Indexed(2));
+    assert_eq!(context.buffer[3].style.fg, Color::Indexed(7));
+
+    assert_eq!(context.render_frame(),
+               "     \x1b[0;38;5;1m>\x1b[0;38;5;7m<\x1b[0;38;5;2m>\x1b[0;38;5;7m \x1b[0m\r");
+}
+
+#[test]
+fn colors_in_yml_and_bytecode() {
+    let text = r#"
+operators:
+  - SetColor: [ { Indexed: 33 }, Default, 1 ]
+  - Draw: [ 0, 0, 0, 0.0, Right, Wrap ]
+  - Print
+
+context:
+  color_mode: TrueColor
+  sprites:
+    - text: " ><(([°> "
+      colors: " 0011123 "
+      palette: [ { Indexed: 208 }, { Indexed: 214 }, Default, { Rgb: [ 0, 255, 255 ] } ]
+"#;
+
+    let dmo = Dmo::new_from_yml_str(text).unwrap();
+    let bytecode = dmo.to_bytecode();
+    let decoded = Dmo::from_bytecode(bytecode.clone());
+
+    assert_eq!(decoded.get_context().color_mode, ColorMode::TrueColor);
+    assert_eq!(decoded.get_sprites(), dmo.get_sprites());
+    assert_eq!(decoded.get_sprites()[0].palette().len(), 4);
+    assert_eq!(decoded.to_bytecode(), bytecode);
+}
+
+#[test]
+fn set_color_in_jit_fn() {
+    let text = r#"
+operators:
+  - SetColor: [ { Rgb: [ 10, 20, 30 ] }, { Indexed: 4 }, 3 ]
+  - Clear: 46
+
+context:
+  sprites: []
+"#;
+
+    let mut dmo = Dmo::new_from_yml_str(text).unwrap();
+    dmo.build_jit_fn().unwrap();
+    dmo.run_jit_fn();
+
+    let cell = dmo.get_context().buffer[0];
+    assert_eq!(cell.ch, '.');
+    assert_eq!(cell.style, Style::new(Color::Rgb(10, 20, 30), Color::Indexed(4), ATTR_BOLD | ATTR_UNDERLINE));
+}
@@ -0,0 +1,303 @@
+#![cfg(test)]
+
+use dmo::{Dmo, Context, Operator};
+use sprite::{Sprite, AnimMode, Facing, Edge};
+use color::{Color, ColorMode};
+use layer::Layer;
+use track::{Track, Keyframe, Ease, Param};
+use scene::Scene;
+use transition::Blend;
+use particle::Emitter;
+use shader::Shader;
+use var::{Var, VarKind, Compare};
+use font::{Font, Banner};
+use post::{PostFx, Axis};
+use collision::Overlap;
+use bytecode::Bytecode;
+
+/// Small xorshift generator, so that the property test is repeatable.
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    fn below(&mut self, n: u32) -> u32 {
+        self.next() % n
+    }
+
+    fn float(&mut self) -> f32 {
+        (self.next() % 100_000) as f32 / 100.0 - 500.0
+    }
+}
+
+fn random_param(rng: &mut Rng, n_tracks: usize, n_vars: usize) -> Param {
+    if n_tracks > 0 && rng.below(3) == 0 {
+        Param::track(rng.below(n_tracks as u32) as u8)
+    } else if n_vars > 0 && rng.below(3) == 0 {
+        Param::var(rng.below(n_vars as u32) as u8)
+    } else {
+        Param::Value(rng.float())
+    }
+}
+
+fn random_operators(rng: &mut Rng, context: &Context) -> Vec<Operator> {
+    let glyphs: Vec<char> = " _-~<>()[]{}°ø¸,.'`*#@%\"\\:!&|?".chars().collect();
+
+    let mut operators: Vec<Operator> = vec![];
+    for _ in 0 .. rng.below(12) {
+        let n_layers = context.layers.len() as u32 + 1;
+        let n_tracks = context.tracks.len();
+        let n_vars = context.vars.len();
+        let var = rng.below(n_vars as u32 + 1) as u8;
+        let op = match rng.below(26) {
+            0 => Operator::Exit(random_param(rng, n_tracks, n_vars)),
+            1 => Operator::Print,
+            2 => Operator::Draw(rng.below(context.sprites.len() as u32) as u8,
+                                random_param(rng, n_tracks, n_vars),
+                                random_param(rng, n_tracks, n_vars),
+                                random_param(rng, n_tracks, n_vars),
+                                [Facing::Right, Facing::Left, Facing::Auto][rng.below(3) as usize],
+                                [Edge::Wrap, Edge::Clip, Edge::Bounce, Edge::Clamp][rng.below(4) as usize]),
+            3 => Operator::SetColor(Color::Indexed(rng.below(256) as u8),
+                                    Color::Rgb(rng.below(256) as u8, 0, 255),
+                                    rng.below(4) as u8),
+            4 => Operator::Animate(rng.below(context.sprites.len() as u32) as u8,
+                                   rng.float(),
+                                   [AnimMode::Loop, AnimMode::PingPong, AnimMode::Once][rng.below(3) as usize]),
+            5 => Operator::SetLayer(rng.below(n_layers) as u8),
+            6 => Operator::ClearLayer(rng.below(n_layers) as u8),
+            7 => Operator::MoveLayer(rng.below(n_layers) as u8, rng.float(), rng.float()),
+            8 => Operator::Transition([Blend::WipeLeft, Blend::WipeRight, Blend::Dissolve, Blend::Fade][rng.below(4) as usize],
+                                      random_param(rng, n_tracks, n_vars),
+                                      rng.below(1000)),
+            9 => Operator::Emit(rng.below(context.emitters.len() as u32 + 1) as u8,
+                                random_param(rng, n_tracks, n_vars),
+                                random_param(rng, n_tracks, n_vars)),
+            10 => Operator::Plasma(random_param(rng, n_tracks, n_vars), random_param(rng, n_tracks, n_vars)),
+            11 => Operator::Fire(rng.below(256) as u8),
+            12 => Operator::Starfield(rng.below(1000) as u16, random_param(rng, n_tracks, n_vars)),
+            13 => Operator::SineScroll(rng.below(context.sprites.len() as u32) as u8,
+                                       random_param(rng, n_tracks, n_vars),
+                                       rng.float(),
+                                       rng.float()),
+            14 => Operator::Shade(rng.below(context.shaders.len() as u32 + 1) as u8),
+            15 => Operator::Set(var, random_param(rng, n_tracks, n_vars)),
+            16 => Operator::Add(var, random_param(rng, n_tracks, n_vars)),
+            17 => Operator::Mul(var, random_param(rng, n_tracks, n_vars)),
+            18 => Operator::Sin(var, random_param(rng, n_tracks, n_vars)),
+            19 => Operator::Branch(var,
+                                   [Compare::Less, Compare::LessOrEqual, Compare::Greater,
+                                    Compare::GreaterOrEqual, Compare::Equal, Compare::NotEqual][rng.below(6) as usize],
+                                   random_param(rng, n_tracks, n_vars),
+                                   rng.below(4) as u8),
+            20 => Operator::Text(rng.below(context.banners.len() as u32 + 1) as u8,
+                                 random_param(rng, n_tracks, n_vars),
+                                 random_param(rng, n_tracks, n_vars),
+                                 random_param(rng, n_tracks, n_vars),
+                                 [Edge::Wrap, Edge::Clip, Edge::Bounce, Edge::Clamp][rng.below(4) as usize]),
+            21 => Operator::Camera(random_param(rng, n_tracks, n_vars), random_param(rng, n_tracks, n_vars)),
+            22 => Operator::Follow(rng.below(context.sprites.len() as u32) as u8),
+            23 => Operator::Collide(rng.below(context.sprites.len() as u32) as u8,
+                                    rng.below(context.sprites.len() as u32) as u8,
+                                    [Overlap::Bounds, Overlap::Cells][rng.below(2) as usize],
+                                    var),
+            24 => Operator::NOOP,
+            _ => Operator::Clear(glyphs[rng.below(glyphs.len() as u32) as usize] as u32),
+        };
+        operators.push(op);
+    }
+
+    operators
+}
+
+fn random_dmo(rng: &mut Rng) -> Dmo {
+    // with newlines, so that sprite lines can be empty or end a frame
+    let glyphs: Vec<char> = " _-~<>()[]{}°ø¸,.'`*#@%\"\\:!&|?\n".chars().collect();
+
+    let mut context = Context::new();
+    context.set_size(rng.below(80) as u16 + 1, rng.below(25) as u16 + 1);
+    if rng.below(2) == 0 {
+        context.view_width = Some(rng.below(context.width as u32) as u16 + 1);
+        context.view_height = Some(rng.below(context.height as u32) as u16 + 1);
+    }
+    if rng.below(2) == 0 {
+        context.mirror = vec![String::from("<>"), String::from("bd")];
+    }
+    for i in 0 .. rng.below(3) {
+        let mut layer = Layer::new(&format!("layer {}", i));
+        layer.z = rng.below(5) as i16 - 2;
+        layer.visible = rng.below(2) == 0;
+        layer.scroll = (rng.below(20) as i16 - 10, rng.below(20) as i16 - 10);
+        context.layers.push(layer);
+    }
+    for i in 0 .. rng.below(3) {
+        let eases = [Ease::Step, Ease::Linear, Ease::Smoothstep, Ease::Cubic,
+                     Ease::EaseIn, Ease::EaseOut, Ease::EaseInOut];
+        let mut t = 0.0;
+        let mut keys: Vec<Keyframe> = vec![];
+        for _ in 0 .. rng.below(4) + 1 {
+            t += rng.below(100) as f32 / 10.0;
+            keys.push(Keyframe::new(t, rng.float(), eases[rng.below(7) as usize]));
+        }
+        context.tracks.push(Track::new(&format!("track {}", i), keys));
+    }
+    context.seed = rng.next();
+    for i in 0 .. rng.below(3) {
+        let mut emitter = Emitter::new(&format!("emitter {}", i), rng.float(), rng.float(),
+                                       &glyphs[.. rng.below(glyphs.len() as u32) as usize + 1].iter().collect::<String>());
+        emitter.speed_x = (rng.float(), rng.float());
+        emitter.speed_y = (rng.float(), rng.float());
+        context.emitters.push(emitter);
+    }
+    let fills = ["x", "ramp(sin(x*0.3 + t) + cos(t*2))", "mod(x + y, 3) * -abs(t)", "floor(y / 2)"];
+    for i in 0 .. rng.below(3) {
+        context.shaders.push(Shader::new(&format!("shader {}", i), fills[rng.below(4) as usize]));
+    }
+    for i in 0 .. rng.below(4) {
+        let kind = [VarKind::Float, VarKind::Int][rng.below(2) as usize];
+        context.vars.push(Var::new(&format!("var {}", i), kind, rng.float()));
+    }
+    for i in 0 .. rng.below(3) {
+        // a space and then letters from `!`, one or two lines high
+        let height = rng.below(2) + 1;
+        let mut flf = format!("flf2a$ {} {} 8 0 1\nfont {}\n", height, height, i);
+        for _ in 0 .. rng.below(4) + 1 {
+            for row in 0 .. height {
+                let len = rng.below(4) as usize;
+                // without the newline at the end of the glyphs
+                let line: String = (0 .. len).map(|_| glyphs[rng.below(glyphs.len() as u32 - 1) as usize]).collect();
+                flf.push_str(&format!("{}${}\n", line, if row + 1 == height { "@@" } else { "@" }));
+            }
+        }
+        context.fonts.push(Font::new(&format!("font {}", i), &flf));
+    }
+    for i in 0 .. rng.below(3) {
+        let font = format!("font {}", rng.below(context.fonts.len() as u32 + 1));
+        context.banners.push(Banner::new(&format!("banner {}", i), &font, "Hi!\n ! "));
+    }
+    for _ in 0 .. rng.below(4) {
+        let axis = [Axis::Horizontal, Axis::Vertical][rng.below(2) as usize];
+        let ramp: String = (0 .. rng.below(6)).map(|_| glyphs[rng.below(glyphs.len() as u32) as usize]).collect();
+        context.post.push(match rng.below(5) {
+            0 => PostFx::Wave(axis, rng.float(), rng.float(), rng.float()),
+            1 => PostFx::Mirror(axis),
+            2 => PostFx::Invert(ramp),
+            3 => PostFx::Remap(ramp.clone(), ramp.chars().rev().collect()),
+            _ => PostFx::Ascii,
+        });
+    }
+    context.reset();
+    context.color_mode = [ColorMode::NoColor, ColorMode::Ansi256, ColorMode::TrueColor][rng.below(3) as usize];
+    for _ in 0 .. rng.below(5) + 1 {
+        let mut lines: Vec<String> = vec![];
+        for _ in 0 .. rng.below(4) + 1 {
+            let len = rng.below(20) + 1;
+            lines.push((0 .. len).map(|_| glyphs[rng.below(glyphs.len() as u32) as usize]).collect());
+        }
+        let transparent = match rng.below(2) {
+            0 => None,
+            _ => Some(glyphs[rng.below(glyphs.len() as u32) as usize]),
+        };
+        let mut sprite = Sprite::new(&lines.join("\n"), transparent);
+        if rng.below(3) == 0 {
+            let mut frames = vec![lines.join("\n")];
+            for _ in 0 .. rng.below(3) + 1 {
+                frames.push(lines.iter().rev().cloned().collect::<Vec<String>>().join("\n"));
+            }
+            sprite = sprite.with_frames(frames);
+        }
+        if rng.below(3) == 0 {
+            let colors: Vec<String> = lines.iter().map(|line| {
+                line.chars().map(|_| ['0', '1', '2', ' '][rng.below(4) as usize]).collect()
+            }).collect();
+            let palette = vec![Color::Indexed(rng.below(256) as u8),
+                               Color::Rgb(rng.below(256) as u8, rng.below(256) as u8, rng.below(256) as u8),
+                               Color::Default];
+            sprite = sprite.with_colors(&colors.join("\n"), palette);
+        }
+        context.sprites.push(sprite);
+    }
+
+    let operators = random_operators(rng, &context);
+
+    let mut scenes: Vec<Scene> = vec![];
+    let mut start = 0.0;
+    for i in 0 .. rng.below(3) {
+        let end = match rng.below(2) {
+            0 => None,
+            _ => Some(start + rng.below(100) as f32 / 10.0),
+        };
+        scenes.push(Scene::new(&format!("scene {}", i), start, end, random_operators(rng, &context)));
+        start += rng.below(100) as f32 / 10.0;
+    }
+    let end = match rng.below(2) {
+        0 => None,
+        _ => Some(rng.float()),
+    };
+
+    Dmo::new(context, operators).with_timeline(scenes, end)
+}
+
+#[test]
+fn yml_to_bytecode_to_yml_is_stable() {
+    let mut rng = Rng(0x2545F491);
+
+    for _ in 0 .. 200 {
+        let text = random_dmo(&mut rng).to_yml_string().unwrap();
+
+        let dmo = Dmo::new_from_yml_str(&text).unwrap();
+        let bytecode = dmo.to_bytecode();
+
+        let decoded = Dmo::from_bytecode(bytecode.clone());
+        let decoded_text = decoded.to_yml_string().unwrap();
+
+        assert_eq!(text, decoded_text);
+        assert_eq!(Dmo::new_from_yml_str(&decoded_text).unwrap().to_bytecode(), bytecode);
+    }
+}
+
+#[test]
+fn decompile_fish_demo() {
+    let source = Dmo::new_from_yml_str(include_str!("../../examples/fish-demo.yml")).unwrap();
+
+    let dmo = Dmo::from_bytecode(source.to_bytecode());
+    let text = dmo.to_yml_string().unwrap();
+
+    assert_eq!(Dmo::new_from_yml_str(&text).unwrap().to_bytecode(), source.to_bytecode());
+}
+
+#[test]
+fn quoted_strings_read_back() {
+    let mut context = Context::new();
+    context.sprites.push(Sprite::new("'.\n*#", None));
+    context.sprites.push(Sprite::new("\"true\\", Some('\'')));
+    context.vars.push(Var::new("1.5", VarKind::Float, 1.0));
+    let dmo = Dmo::new(context, vec![Operator::Set(0, Param::Value(2.0))]);
+
+    let text = dmo.to_yml_string().unwrap();
+    let decoded = Dmo::new_from_yml_str(&text).unwrap();
+    assert_eq!(decoded.get_sprites()[0].text(), "'.\n*#");
+    assert_eq!(decoded.get_sprites()[1].text(), "\"true\\");
+    assert_eq!(decoded.to_bytecode(), dmo.to_bytecode());
+}
+
+#[test]
+fn trailing_newlines_are_kept() {
+    let mut context = Context::new();
+    context.sprites.push(Sprite::new("><>\n", None));
+    context.sprites.push(Sprite::new(" o\n\n><>\n\n", Some(' ')));
+    context.sprites.push(Sprite::new("\n><>", None));
+    let dmo = Dmo::new(context, vec![]);
+
+    let text = dmo.to_yml_string().unwrap();
+    assert!(text.contains("  sprites:\n    - |+\n      ><>\n    -\n      text: |2+\n"));
+
+    let decoded = Dmo::from_bytecode(Dmo::new_from_yml_str(&text).unwrap().to_bytecode());
+    assert_eq!(decoded.get_sprites(), dmo.get_sprites());
+    assert_eq!(decoded.to_yml_string().unwrap(), text);
+}
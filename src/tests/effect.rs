@@ -0,0 +1,171 @@
+#![cfg(test)]
+
+use std::f32;
+use std::f32::consts::PI;
+
+use dmo::{Dmo, Context, Operator};
+use sprite::Sprite;
+use effect::{RAMP, ramp_char, fire_step};
+use random::Rng;
+use track::Param;
+use bytecode::{Bytecode, VerifyError, verify};
+
+fn effect_yml(op: &str) -> String {
+    format!(r#"
+operators:
+  - {}
+
+context:
+  width: 16
+  height: 6
+  seed: 3
+  sprites:
+    - "FISH"
+"#, op)
+}
+
+/// Frames from the JIT fn, checked against the interpreter.
+fn run_frames(text: &str, n_frames: usize) -> Vec<String> {
+    let mut jit = Dmo::new_from_yml_str(text).unwrap();
+    jit.build_jit_fn().unwrap();
+    let mut interpreter = Dmo::new_from_yml_str(text).unwrap();
+
+    let mut frames: Vec<String> = vec![];
+    for _ in 0 .. n_frames {
+        jit.run_jit_fn();
+        interpreter.run_operators();
+        assert_eq!(jit.get_context().render_frame(), interpreter.get_context().render_frame());
+
+        frames.push(jit.get_context().render_frame());
+        jit.add_to_time(0.1);
+        interpreter.add_to_time(0.1);
+    }
+    frames
+}
+
+#[test]
+fn ramp() {
+    assert_eq!(ramp_char(0.0), ' ');
+    assert_eq!(ramp_char(-1.0), ' ');
+    assert_eq!(ramp_char(1.0), '@');
+    assert_eq!(ramp_char(2.0), '@');
+    assert_eq!(ramp_char(0.5), '+');
+}
+
+#[test]
+fn plasma_moves_with_the_time() {
+    let frames = run_frames(&effect_yml("Plasma: [ 0.3, 2.0 ]"), 3);
+    assert!(frames[0] != frames[1]);
+    for ch in frames[0].trim_left_matches("\x1b[H").chars() {
+        assert!(RAMP.contains(&ch) || ch == '\n');
+    }
+}
+
+#[test]
+fn fire_rises_from_the_bottom() {
+    let mut heat = vec![0; 4 * 4];
+    let mut rng = Rng::new(1);
+    fire_step(&mut heat, 4, 4, 0, &mut rng);
+    assert!(heat[.. 4].iter().all(|h| *h == 0));
+    assert!(heat[12 ..].iter().any(|h| *h > 0));
+
+    // all the heat is lost in the row above the bottom
+    fire_step(&mut heat, 4, 4, 255, &mut rng);
+    assert!(heat[.. 12].iter().all(|h| *h == 0));
+
+    let frames = run_frames(&effect_yml("Fire: 8"), 4);
+    assert!(frames[2] != frames[3]);
+    assert_eq!(frames, run_frames(&effect_yml("Fire: 8"), 4));
+}
+
+#[test]
+fn starfield_is_seeded() {
+    let frames = run_frames(&effect_yml("Starfield: [ 20, 0.5 ]"), 4);
+    assert!(frames[0].contains(|c| c == '.' || c == '+' || c == '*'));
+    assert!(frames[0] != frames[3]);
+
+    let other_seed = effect_yml("Starfield: [ 20, 0.5 ]").replace("seed: 3", "seed: 4");
+    assert!(run_frames(&other_seed, 4) != frames);
+}
+
+#[test]
+fn sine_scroll_comes_in_from_the_right() {
+    let mut context = Context::new();
+    context.set_size(10, 5);
+    context.sprites.push(Sprite::from("AB"));
+    context.impl_clear('.' as u32);
+
+    context.impl_sine_scroll(0, 1.0, 0.0, 0.0);
+    assert!(!context.render_frame().contains('A'));
+
+    context.time = 3.0;
+    context.impl_sine_scroll(0, 1.0, 0.0, 0.0);
+    let expected = String::from("\x1b[H") +
+        "     ..........\n" +
+        "     ..........\n" +
+        "     .......AB.\n" +
+        "     ..........\n" +
+        "     ..........\n";
+    assert_eq!(context.render_frame(), expected);
+
+    // a whole period later, 3 chars in, where col 7 is at the bottom of the
+    // wave and col 8 is in the middle
+    context.impl_clear('.' as u32);
+    context.time = 2.0 * PI;
+    context.impl_sine_scroll(0, 3.1 / (2.0 * PI), 2.0, PI / 2.0);
+    assert_eq!(context.render_frame().lines().nth(0).unwrap(), "\x1b[H     .......A..");
+    assert_eq!(context.render_frame().lines().nth(2).unwrap(), "     ........B.");
+}
+
+#[test]
+fn effects_in_bytecode() {
+    let text = r#"
+operators:
+  - Plasma: [ 0.3, 2.0 ]
+  - Fire: 8
+  - Starfield: [ 20, 0.5 ]
+  - SineScroll: [ "text", 4.0, 1.5, 0.3 ]
+
+context:
+  sprites:
+    text: "FISH"
+"#;
+    let dmo = Dmo::new_from_yml_str(text).unwrap();
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+
+    let operators = decoded.get_operators();
+    match operators[0] {
+        Operator::Plasma(scale, speed) => assert_eq!((scale, speed), (Param::Value(0.3), Param::Value(2.0))),
+        _ => panic!("expected Plasma"),
+    }
+    match operators[1] {
+        Operator::Fire(cooling) => assert_eq!(cooling, 8),
+        _ => panic!("expected Fire"),
+    }
+    match operators[2] {
+        Operator::Starfield(count, speed) => assert_eq!((count, speed), (20, Param::Value(0.5))),
+        _ => panic!("expected Starfield"),
+    }
+    match operators[3] {
+        Operator::SineScroll(idx, speed, amplitude, frequency) => {
+            assert_eq!((idx, speed), (0, Param::Value(4.0)));
+            assert_eq!((amplitude, frequency), (1.5, 0.3));
+        },
+        _ => panic!("expected SineScroll"),
+    }
+}
+
+#[test]
+fn verify_effects() {
+    let context = Context::new();
+    let operators = vec![
+        Operator::Plasma(Param::Value(0.3), Param::track(0)),
+        Operator::SineScroll(1, Param::Value(1.0), 1.0, f32::INFINITY),
+    ];
+
+    assert_eq!(verify(&context, &operators), Err(vec![
+        VerifyError::TrackOutOfRange { op_idx: 0, track: 0, n_tracks: 0 },
+        VerifyError::SpriteOutOfRange { op_idx: 1, sprite_idx: 1, n_sprites: 0 },
+        VerifyError::NonFiniteFloat { op_idx: 1, value: f32::INFINITY },
+    ]));
+}
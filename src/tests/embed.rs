@@ -0,0 +1,52 @@
+#![cfg(test)]
+
+use std::env;
+use std::fs;
+use std::process;
+use std::path::PathBuf;
+
+use dmo::Dmo;
+use embed::{compile_dmo_to, compile_yml_to_blob};
+use bytecode::Bytecode;
+use utils::file_to_string;
+
+/// An empty directory for the files of a test.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("fish-in-a-jit-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn compile_yml_and_read_the_blob() {
+    let dir = temp_dir("compile");
+    let src = PathBuf::from("./examples/fish-demo.yml");
+    let dst = dir.join("fish-demo.dmo");
+    let dmo = Dmo::new_from_yml_str(&file_to_string(&src).unwrap()).unwrap();
+
+    compile_yml_to_blob(&src, &dst).unwrap();
+    assert_eq!(Dmo::new_from_blob(&dst).unwrap().to_bytecode(), dmo.to_bytecode());
+
+    // as in a build script, the blob is named after the YAML
+    fs::remove_file(&dst).unwrap();
+    assert_eq!(compile_dmo_to("examples/fish-demo.yml", &dir).unwrap(), dst);
+    assert_eq!(Dmo::new_from_blob(&dst).unwrap().to_bytecode(), dmo.to_bytecode());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compile_errors_from_verify() {
+    let dir = temp_dir("verify");
+    let src = dir.join("broken.yml");
+    let dst = dir.join("broken.dmo");
+    let text = "operators:\n  - Clear: 55296\n  - Print\ncontext:\n  sprites: [ \"><>\" ]\n";
+    fs::write(&src, text).unwrap();
+
+    let err = compile_yml_to_blob(&src, &dst).err().unwrap();
+    assert_eq!(err.to_string(), "operator 0: 0xd800 is not a valid char");
+    assert!(!dst.exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,171 @@
+#![cfg(test)]
+
+use dmo::{Dmo, Context, Operator};
+use sprite::Edge;
+use track::Param;
+use font::{Font, Banner, parse_flf, banner_sprite};
+use bytecode::{Bytecode, VerifyError, verify};
+
+const TINY: &'static str = include_str!("../../examples/fonts/tiny.flf");
+
+#[test]
+fn parse_tiny_font() {
+    let glyphs = parse_flf(TINY).unwrap();
+    assert_eq!(glyphs.height, 3);
+    assert_eq!(glyphs.hardblank, '$');
+
+    assert_eq!(glyphs.render("Hi!"), vec!["    |", "|_|||", "| ||o"]);
+    assert_eq!(glyphs.render("I\nI"), vec![" ", "|", "|", " ", "|", "|"]);
+
+    // the German chars are empty, the degree sign has a code tag
+    let empty: Vec<Vec<char>> = vec![vec![], vec![], vec![]];
+    assert_eq!(glyphs.chars[&'ä'], empty);
+    assert_eq!(glyphs.chars[&'°'], vec![vec!['o'], vec![' '], vec![' ']]);
+}
+
+#[test]
+fn endmarks_and_hardblanks() {
+    let flf = "flf2a# 2 2 4 0 1\nnot a letter\n #@\n##@@\na @\nb#c  @@  \n";
+    let glyphs = parse_flf(flf).unwrap();
+
+    assert_eq!(glyphs.chars[&' '], vec![vec![' ', '#'], vec!['#', '#']]);
+    // rows are padded to the widest row
+    assert_eq!(glyphs.chars[&'!'], vec![vec!['a', ' ', ' ', ' ', ' '], vec!['b', '#', 'c', ' ', ' ']]);
+    assert_eq!(glyphs.chars.len(), 2);
+
+    assert_eq!(glyphs.render(" ! "), vec!["  a      ", "  b c    "]);
+}
+
+/// A font of one line high letters, with all the required chars.
+fn required_chars() -> String {
+    let mut flf = String::from("flf2a$ 1 1 1 0 0\n");
+    for _ in 32 .. 127 + 7 {
+        flf.push_str("x@\n");
+    }
+    flf
+}
+
+#[test]
+fn code_tagged_chars() {
+    let flf = required_chars() + "0x263A SMILE\n:)@\n01001 octal\n8@\n-3 not a char\n?@\n176\no@\n";
+    let glyphs = parse_flf(&flf).unwrap();
+
+    assert_eq!(glyphs.chars.len(), 95 + 7 + 3);
+    assert_eq!(glyphs.render("\u{263A}\u{201}°"), vec![":)8o"]);
+}
+
+#[test]
+fn flf_errors() {
+    assert_eq!(parse_flf("").err().unwrap(), "line 1: the file is empty");
+    assert_eq!(parse_flf("flf2a$ 0 0 1 0 0").err().unwrap(), "line 1: invalid height \"0\"");
+    assert_eq!(parse_flf("flf2a$ 2 2 1 0 x").err().unwrap(),
+               "line 1: invalid number of comment lines \"x\"");
+    assert!(parse_flf("figlet 2 2 1 0 0").err().unwrap().starts_with("line 1: expected a header"));
+    assert_eq!(parse_flf("flf2a$ 2 2 1 0 1\n\n @\n").err().unwrap(), "line 3: the letter has too few lines");
+
+    let flf = required_chars() + "0xZZ\nx@\n";
+    assert_eq!(parse_flf(&flf).err().unwrap(), "line 104: invalid char code \"0xZZ\"");
+}
+
+#[test]
+fn banner_in_unknown_font_is_empty() {
+    let fonts = vec![Font::new("tiny", TINY)];
+    assert_eq!(banner_sprite(&Banner::new("b", "tiny", "O"), &fonts).height(), 3);
+    assert_eq!(banner_sprite(&Banner::new("b", "huge", "O"), &fonts).height(), 0);
+}
+
+const SCROLL_YML: &'static str = r#"
+operators:
+  - Clear: 46
+  - Text: [ 0, 6.0, 0.0, -2.0, Wrap ]
+
+context:
+  width: 8
+  height: 3
+  fonts:
+    - name: "tiny"
+      file: "examples/fonts/tiny.flf"
+  banners:
+    - name: "greetings"
+      font: "tiny"
+      text: "HI"
+  sprites: []
+"#;
+
+#[test]
+fn text_scrolls_in_jit_fn_and_interpreter() {
+    let mut jit = Dmo::new_from_yml_str(SCROLL_YML).unwrap();
+    jit.build_jit_fn().unwrap();
+    let mut interpreter = Dmo::new_from_yml_str(SCROLL_YML).unwrap();
+
+    let mut frames: Vec<String> = vec![];
+    for _ in 0 .. 4 {
+        jit.run_jit_fn();
+        interpreter.run_operators();
+        assert_eq!(jit.get_context().render_frame(), interpreter.get_context().render_frame());
+        frames.push(jit.get_context().render_frame());
+
+        jit.add_to_time(0.5);
+        interpreter.add_to_time(0.5);
+    }
+
+    assert_eq!(frames[0], "\x1b[H     ........\n     ||....|_\n     ||....|.\n");
+    assert_eq!(frames[3], "\x1b[H     ........\n     ...|_||.\n     ...|.||.\n");
+}
+
+#[test]
+fn fonts_are_embedded_in_bytecode() {
+    let dmo = Dmo::new_from_yml_str(SCROLL_YML).unwrap();
+    assert_eq!(dmo.get_context().fonts[0].flf, TINY);
+
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+    let context = decoded.get_context();
+    assert_eq!(context.fonts[0].file, None);
+    assert_eq!(context.fonts[0].flf, TINY);
+    assert_eq!(context.banners, dmo.get_context().banners);
+    assert_eq!(context.banner_sprites, dmo.get_context().banner_sprites);
+
+    match decoded.get_operators()[1] {
+        Operator::Text(0, x, _, speed, Edge::Wrap) => assert_eq!((x, speed), (Param::Value(6.0), Param::Value(-2.0))),
+        _ => panic!("expected Text"),
+    }
+}
+
+#[test]
+fn missing_font_file_is_error() {
+    let text = SCROLL_YML.replace("tiny.flf", "huge.flf");
+    let err = Dmo::new_from_yml_str(&text).err().unwrap();
+    assert!(err.to_string().contains("huge.flf"), "{}", err);
+}
+
+#[test]
+fn verify_fonts_and_banners() {
+    let mut context = Context::new();
+    context.fonts.push(Font::new("tiny", TINY));
+    context.fonts.push(Font::new("broken", "flf2a$ x"));
+    context.banners.push(Banner::new("hello", "tiny", "HELLO"));
+    context.banners.push(Banner::new("bye", "huge", "BYE"));
+
+    let operators = vec![
+        Operator::Text(1, Param::Value(0.0), Param::Value(0.0), Param::Value(1.0), Edge::Clip),
+        Operator::Text(2, Param::Value(0.0), Param::var(0), Param::Value(1.0), Edge::Clip),
+    ];
+
+    assert_eq!(verify(&context, &operators), Err(vec![
+        VerifyError::InvalidFont { idx: 1, error: String::from("line 1: expected a header: flf2a, hardblank, height, baseline, max length, old layout, comment lines") },
+        VerifyError::InvalidBanner { idx: 1, font: String::from("huge") },
+        VerifyError::BannerOutOfRange { op_idx: 1, banner: 2, n_banners: 2 },
+        VerifyError::VarOutOfRange { op_idx: 1, var: 0, n_vars: 0 },
+    ]));
+}
+
+#[test]
+fn banners_by_name() {
+    let text = SCROLL_YML.replace("Text: [ 0,", "Text: [ \"greetings\",");
+    let dmo = Dmo::new_from_yml_str(&text).unwrap();
+    assert_eq!(dmo.to_bytecode(), Dmo::new_from_yml_str(SCROLL_YML).unwrap().to_bytecode());
+
+    let text = SCROLL_YML.replace("Text: [ 0,", "Text: [ \"tiny\",");
+    let err = Dmo::new_from_yml_str(&text).err().unwrap();
+    assert_eq!(err.to_string(), "line 4: operator 1: unknown banner \"tiny\"");
+}
@@ -0,0 +1,204 @@
+#![cfg(test)]
+
+use std::path::PathBuf;
+
+use dmo::Dmo;
+use sprite::Sprite;
+use color::Color;
+use image::{Image, Conversion, decode_image, read_image, image_to_sprite, sprite_yml_snippet, crc32};
+
+/// A PNG with the IHDR, the extra chunks and the rows in a stored (not
+/// compressed) zlib stream.
+fn png(width: u32, height: u32, depth: u8, color_type: u8, extra: Vec<(&[u8], Vec<u8>)>, rows: Vec<u8>) -> Vec<u8> {
+    let mut data: Vec<u8> = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+    let mut ihdr: Vec<u8> = vec![];
+    ihdr.extend_from_slice(&be_bytes(width));
+    ihdr.extend_from_slice(&be_bytes(height));
+    ihdr.extend_from_slice(&[depth, color_type, 0, 0, 0]);
+    chunk(&mut data, b"IHDR", &ihdr);
+
+    for (kind, payload) in extra.into_iter() {
+        chunk(&mut data, kind, &payload);
+    }
+
+    let len = rows.len() as u16;
+    let mut zlib: Vec<u8> = vec![0x78, 0x01, 0x01, len as u8, (len >> 8) as u8, !len as u8, (!len >> 8) as u8];
+    zlib.extend_from_slice(&rows);
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in rows.iter() {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    zlib.extend_from_slice(&be_bytes(b << 16 | a));
+    chunk(&mut data, b"IDAT", &zlib);
+
+    chunk(&mut data, b"IEND", &[]);
+    data
+}
+
+fn chunk(data: &mut Vec<u8>, kind: &[u8], payload: &[u8]) {
+    data.extend_from_slice(&be_bytes(payload.len() as u32));
+    let mut typed: Vec<u8> = kind.to_vec();
+    typed.extend_from_slice(payload);
+    data.extend_from_slice(&typed);
+    data.extend_from_slice(&be_bytes(crc32(&typed)));
+}
+
+fn be_bytes(n: u32) -> [u8; 4] {
+    [(n >> 24) as u8, (n >> 16) as u8, (n >> 8) as u8, n as u8]
+}
+
+fn opaque(values: &[(u8, u8, u8)]) -> Vec<[u8; 4]> {
+    values.iter().map(|&(r, g, b)| [r, g, b, 255]).collect()
+}
+
+#[test]
+fn decode_pnm_forms() {
+    let pgm = b"P2\n# four greys\n4 1\n15\n0 5 10 15\n";
+    let image = decode_image(pgm).unwrap();
+    assert_eq!((image.width, image.height), (4, 1));
+    assert_eq!(image.pixels, opaque(&[(0, 0, 0), (85, 85, 85), (170, 170, 170), (255, 255, 255)]));
+
+    let ppm = b"P6 2 1 255\n\x10\x20\x30\xff\x00\x80";
+    assert_eq!(decode_image(ppm).unwrap().pixels, opaque(&[(16, 32, 48), (255, 0, 128)]));
+
+    // 16-bit samples are big-endian
+    let pgm16 = b"P5 1 1 65535\n\x80\x00";
+    assert_eq!(decode_image(pgm16).unwrap().pixels, opaque(&[(128, 128, 128)]));
+
+    assert_eq!(decode_image(b"P6 2 1 255\n\x10\x20").err().unwrap(), "the image data is too short");
+    assert_eq!(decode_image(b"P3 1 1 255 0 x 0").err().unwrap(), "expected the sample at byte 13");
+    assert_eq!(decode_image(b"P3 1 1 0 0 0 0").err().unwrap(), "invalid max value 0");
+    assert_eq!(decode_image(b"GIF89a").err().unwrap(), "not a PNG, PPM or PGM image");
+}
+
+#[test]
+fn decode_png_filters() {
+    // sub, up, average and paeth
+    let rows = vec![1, 10, 20, 30, 30, 30, 30,
+                    2, 5, 5, 5, 5, 5, 5,
+                    3, 93, 244, 238, 184, 73, 40,
+                    4, 157, 2, 4, 200, 50, 97];
+    let image = decode_image(&png(2, 4, 8, 2, vec![], rows)).unwrap();
+
+    assert_eq!((image.width, image.height), (2, 4));
+    assert_eq!(image.pixels, opaque(&[(10, 20, 30), (40, 50, 60),
+                                      (15, 25, 35), (45, 55, 65),
+                                      (100, 0, 255), (0, 100, 200),
+                                      (1, 2, 3), (200, 150, 100)]));
+}
+
+#[test]
+fn decode_png_color_types() {
+    // 2-bit palette with a transparent entry: 0, 1, 2, 1, 0
+    let palette = vec![(&b"PLTE"[..], vec![255, 0, 0, 0, 255, 0, 0, 0, 255]),
+                       (&b"tRNS"[..], vec![255, 0])];
+    let image = decode_image(&png(5, 1, 2, 3, palette, vec![0, 0x19, 0x00])).unwrap();
+    assert_eq!(image.pixels, vec![[255, 0, 0, 255], [0, 255, 0, 0], [0, 0, 255, 255],
+                                  [0, 255, 0, 0], [255, 0, 0, 255]]);
+
+    // 1-bit grey
+    let image = decode_image(&png(3, 1, 1, 0, vec![], vec![0, 0xA0])).unwrap();
+    assert_eq!(image.pixels, opaque(&[(255, 255, 255), (0, 0, 0), (255, 255, 255)]));
+
+    // 16-bit grey and alpha
+    let image = decode_image(&png(1, 1, 16, 4, vec![], vec![0, 0x80, 0x00, 0xFF, 0xFF])).unwrap();
+    assert_eq!(image.pixels, vec![[127, 127, 127, 255]]);
+
+    // RGB with black as the transparent color
+    let key = vec![(&b"tRNS"[..], vec![0, 0, 0, 0, 0, 0])];
+    let image = decode_image(&png(2, 1, 8, 2, key, vec![0, 0, 0, 0, 1, 0, 0])).unwrap();
+    assert_eq!(image.pixels, vec![[0, 0, 0, 0], [1, 0, 0, 255]]);
+}
+
+#[test]
+fn png_errors() {
+    let mut data = png(1, 1, 8, 0, vec![], vec![0, 0]);
+    let len = data.len();
+    data[len - 20] ^= 1;
+    assert_eq!(decode_image(&data).err().unwrap(), "bad CRC in the IDAT chunk");
+
+    assert_eq!(decode_image(&png(1, 1, 8, 0, vec![], vec![5, 0])).err().unwrap(), "invalid filter 5 in row 0");
+    assert_eq!(decode_image(&png(1, 1, 4, 2, vec![], vec![0, 0])).err().unwrap(),
+               "invalid color type 2 with bit depth 4");
+    assert_eq!(decode_image(&png(2, 2, 8, 0, vec![], vec![0, 0])).err().unwrap(), "the image data is too short");
+    let no_palette = png(1, 1, 8, 3, vec![], vec![0, 0]);
+    assert_eq!(decode_image(&no_palette).err().unwrap(), "palette index 0 is out of range");
+    assert_eq!(decode_image(&no_palette[.. 50]).err().unwrap(), "a chunk is longer than the image");
+    assert_eq!(decode_image(&png(0, 1, 8, 0, vec![], vec![0])).err().unwrap(), "invalid size 0x1");
+    assert_eq!(decode_image(&png(1, 0, 8, 0, vec![], vec![])).err().unwrap(), "invalid size 1x0");
+    assert_eq!(decode_image(&png(1 << 31, 1 << 31, 16, 6, vec![], vec![0, 0])).err().unwrap(),
+               "the image data is too short");
+    assert_eq!(decode_image(&png(0xffff_ffff, 0xffff_ffff, 1, 0, vec![], vec![0, 0])).err().unwrap(),
+               "the image data is too short");
+}
+
+fn greys(width: usize, height: usize, values: &[u8]) -> Image {
+    Image {
+        width: width,
+        height: height,
+        pixels: values.iter().map(|v| [*v, *v, *v, 255]).collect(),
+    }
+}
+
+#[test]
+fn luminance_to_ramp() {
+    let image = greys(4, 2, &[0, 85, 170, 255, 255, 170, 85, 0]);
+
+    let mut conversion = Conversion::default();
+    conversion.ramp = String::from(" .oO");
+    conversion.aspect = 1.0;
+    assert_eq!(image_to_sprite(&image, &conversion), Sprite::new(" .oO\nOo. ", None));
+
+    conversion.invert = true;
+    assert_eq!(image_to_sprite(&image, &conversion), Sprite::new("Oo. \n .oO", None));
+
+    // 2 x 2 pixels for each char, as a char is twice as tall as wide
+    let image = greys(4, 2, &[0, 0, 255, 255, 0, 0, 255, 255]);
+    let mut conversion = Conversion::default();
+    conversion.columns = 2;
+    assert_eq!(image_to_sprite(&image, &conversion), Sprite::new(" @", None));
+}
+
+#[test]
+fn transparent_pixels_and_colors() {
+    let image = Image {
+        width: 3,
+        height: 2,
+        pixels: vec![[0, 255, 0, 255], [92, 92, 255, 255], [255, 255, 255, 0],
+                     [0, 255, 0, 255], [0, 0, 0, 255], [255, 255, 255, 0]],
+    };
+
+    let mut conversion = Conversion::default();
+    conversion.ramp = String::from(" xX");
+    conversion.aspect = 1.0;
+    conversion.transparent = Some(' ');
+    conversion.color = true;
+
+    // black gives the transparent char, so it has no color
+    let sprite = Sprite::new("xx \nx  ", Some(' '))
+        .with_colors("01 \n0  ", vec![Color::Indexed(10), Color::Indexed(12)]);
+    assert_eq!(image_to_sprite(&image, &conversion), sprite);
+}
+
+#[test]
+fn goldfish_to_yml() {
+    let image = read_image(&PathBuf::from("./examples/images/goldfish.ppm")).unwrap();
+    assert_eq!((image.width, image.height), (32, 16));
+
+    let mut conversion = Conversion::default();
+    conversion.columns = 24;
+    conversion.transparent = Some(' ');
+    conversion.color = true;
+    let sprite = image_to_sprite(&image, &conversion);
+    assert_eq!((sprite.width(), sprite.height()), (24, 6));
+
+    // the snippet goes into the context of a demo
+    let snippet = sprite_yml_snippet("goldfish", &sprite).unwrap();
+    let indented: Vec<String> = snippet.lines().filter(|l| *l != "---").map(|l| format!("  {}", l)).collect();
+    let text = format!("operators: []\ncontext:\n{}\n", indented.join("\n"));
+
+    let dmo = Dmo::new_from_yml_str(&text).unwrap();
+    assert_eq!(dmo.get_context().sprites, vec![sprite]);
+}
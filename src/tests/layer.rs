@@ -0,0 +1,105 @@
+#![cfg(test)]
+
+use dmo::{Dmo, Context, CANVAS_LAYER};
+use sprite::Sprite;
+use layer::Layer;
+use bytecode::{Bytecode, VerifyError};
+
+#[test]
+fn layers_composite_in_z_order() {
+    let mut context = Context::new();
+    context.set_size(6, 1);
+    let mut front = Layer::new("front");
+    front.z = 1;
+    context.layers.push(front);
+    context.layers.push(Layer::new("back"));
+    context.reset();
+    context.sprites.push(Sprite::from("ab"));
+    context.sprites.push(Sprite::from("xyz"));
+
+    context.impl_clear('.' as u32);
+    context.impl_set_layer(1);
+    context.impl_draw(0, 1.0, 0, 0, 0, 0.0);
+    context.impl_set_layer(2);
+    context.impl_draw(1, 2.0, 0, 0, 0, 0.0);
+
+    // "front" is first in the list, but has the higher z
+    assert_eq!(context.render_frame(), "     .abyz.\r");
+
+    context.layers[0].visible = false;
+    assert_eq!(context.render_frame(), "     ..xyz.\r");
+
+    context.impl_clear_layer(2);
+    assert_eq!(context.render_frame(), "     ......\r");
+    assert_eq!(context.target, 2);
+
+    context.impl_set_layer(CANVAS_LAYER);
+    context.impl_clear('-' as u32);
+    assert_eq!(context.render_frame(), "     ------\r");
+}
+
+#[test]
+fn layers_scroll_and_move() {
+    let text = r#"
+operators:
+  - SetLayer: 1
+  - Draw: [ 0, 0, 0, 0.0, Right, Wrap ]
+  - MoveLayer: [ 1, 2.0, 0.0 ]
+
+context:
+  width: 6
+  height: 2
+  layers:
+    - name: "fish"
+      scroll: [ 1, 1 ]
+  sprites:
+    - "><>"
+"#;
+
+    let mut dmo = Dmo::new_from_yml_str(text).unwrap();
+    dmo.build_jit_fn().unwrap();
+
+    dmo.run_jit_fn();
+    let expected = String::from("\x1b[H") +
+        "     ______\n" +
+        "     _><>__\n";
+    assert_eq!(dmo.get_context().render_frame(), expected);
+
+    // the layer wraps around when it moves past the end
+    dmo.add_to_time(2.0);
+    dmo.run_jit_fn();
+    let expected = String::from("\x1b[H") +
+        "     ______\n" +
+        "     <>___>\n";
+    assert_eq!(dmo.get_context().render_frame(), expected);
+
+    assert!(dmo.set_layer_visible("fish", false));
+    assert!(!dmo.set_layer_visible("birds", false));
+
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+    assert_eq!(decoded.get_context().layers[0].scroll, (1, 1));
+    assert_eq!(decoded.get_context().layers[0].visible, false);
+    assert_eq!(decoded.get_context().layers[0].cells.len(), 12);
+}
+
+#[test]
+fn verify_layer_numbers() {
+    let text = r#"
+operators:
+  - SetLayer: 0
+  - SetLayer: 1
+  - ClearLayer: 2
+  - MoveLayer: [ 3, 1.0, 0.0 ]
+
+context:
+  layers:
+    - name: "fish"
+  sprites: []
+"#;
+
+    let dmo = Dmo::new_from_yml_str(text).unwrap();
+    assert_eq!(dmo.verify(), Err(vec![
+        VerifyError::LayerOutOfRange { op_idx: 2, layer: 2, n_layers: 1 },
+        VerifyError::LayerOutOfRange { op_idx: 3, layer: 3, n_layers: 1 },
+    ]));
+}
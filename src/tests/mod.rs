@@ -1,3 +1,22 @@
 #[cfg(test)]
 
 pub mod draw_and_print;
+pub mod bytecode;
+pub mod decompile;
+pub mod canvas;
+pub mod color;
+pub mod sprite;
+pub mod layer;
+pub mod track;
+pub mod scene;
+pub mod transition;
+pub mod particle;
+pub mod effect;
+pub mod shader;
+pub mod var;
+pub mod font;
+pub mod image;
+pub mod post;
+pub mod camera;
+pub mod collision;
+pub mod embed;
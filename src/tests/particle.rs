@@ -0,0 +1,140 @@
+#![cfg(test)]
+
+use dmo::{Dmo, Context, Operator};
+use random::Rng;
+use particle::Emitter;
+use track::Param;
+use bytecode::{Bytecode, VerifyError, verify};
+
+#[test]
+fn rng_is_repeatable() {
+    let numbers = |seed: u32| {
+        let mut rng = Rng::new(seed);
+        (0 .. 8).map(|_| rng.next_u32()).collect::<Vec<u32>>()
+    };
+    assert_eq!(numbers(0), numbers(0));
+    assert!(numbers(0) != numbers(1));
+
+    let mut rng = Rng::new(42);
+    for _ in 0 .. 1000 {
+        let x = rng.range(-2.0, 3.0);
+        assert!(x >= -2.0 && x < 3.0);
+        assert!(rng.below(7) < 7);
+    }
+    assert_eq!(rng.below(0), 0);
+}
+
+#[test]
+fn emitter_spawns_at_its_rate() {
+    let mut rng = Rng::new(1);
+    let mut emitter = Emitter::new("bubbles", 2.0, 1.0, "o");
+    emitter.speed_y = (-1.0, -1.0);
+
+    emitter.update(0.0, 3.0, 5.0, &mut rng);
+    assert_eq!(emitter.particles.len(), 0);
+
+    emitter.update(0.75, 3.0, 5.0, &mut rng);
+    assert_eq!(emitter.particles.len(), 1);
+    assert_eq!(emitter.particles[0].position(1.25), (3.0, 4.5));
+
+    // the first particle is gone after its lifetime, the half particle
+    // left over is spawned now
+    emitter.update(2.0, 3.0, 5.0, &mut rng);
+    assert_eq!(emitter.particles.len(), 3);
+    assert!(emitter.particles.iter().all(|p| p.born == 2.0));
+
+    // going back in time starts over
+    emitter.update(0.5, 3.0, 5.0, &mut rng);
+    assert_eq!(emitter.particles.len(), 0);
+}
+
+const BUBBLES_YML: &'static str = r#"
+operators:
+  - Clear: 32
+  - Emit: [ 0, 4.0, 3.0 ]
+
+context:
+  width: 8
+  height: 4
+  seed: 7
+  emitters:
+    - name: "bubbles"
+      rate: 4.0
+      lifetime: 3.0
+      speed_x: [ -1.0, 1.0 ]
+      speed_y: [ -1.5, -0.5 ]
+      glyphs: "oO."
+  sprites: []
+"#;
+
+fn run_frames(text: &str, jit: bool) -> Vec<String> {
+    let mut dmo = Dmo::new_from_yml_str(text).unwrap();
+    dmo.build_jit_fn().unwrap();
+
+    let mut frames: Vec<String> = vec![];
+    for _ in 0 .. 10 {
+        if jit {
+            dmo.run_jit_fn();
+        } else {
+            dmo.run_operators();
+        }
+        frames.push(dmo.get_context().render_frame());
+        dmo.add_to_time(0.25);
+    }
+    frames
+}
+
+#[test]
+fn same_seed_same_frames() {
+    let frames = run_frames(BUBBLES_YML, true);
+    assert_eq!(frames, run_frames(BUBBLES_YML, true));
+    assert_eq!(frames, run_frames(BUBBLES_YML, false));
+
+    // bubbles were drawn, and they moved
+    assert!(frames[9].contains(|c| c == 'o' || c == 'O' || c == '.'));
+    assert!(frames[8] != frames[9]);
+
+    let other_seed = BUBBLES_YML.replace("seed: 7", "seed: 8");
+    assert!(run_frames(&other_seed, true) != frames);
+}
+
+#[test]
+fn emitters_in_bytecode() {
+    let dmo = Dmo::new_from_yml_str(BUBBLES_YML).unwrap();
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+
+    assert_eq!(decoded.get_context().seed, 7);
+    assert_eq!(decoded.get_context().emitters, dmo.get_context().emitters);
+    match decoded.get_operators()[1] {
+        Operator::Emit(emitter, x, y) => {
+            assert_eq!(emitter, 0);
+            assert_eq!((x, y), (Param::Value(4.0), Param::Value(3.0)));
+        },
+        _ => panic!("expected Emit"),
+    }
+}
+
+#[test]
+fn verify_emitters() {
+    let mut context = Context::new();
+    context.emitters.push(Emitter::new("no glyphs", 1.0, 1.0, ""));
+    context.emitters.push(Emitter::new("backwards", -1.0, 1.0, "o"));
+
+    let operators = vec![Operator::Emit(2, Param::Value(0.0), Param::Value(0.0))];
+
+    assert_eq!(verify(&context, &operators), Err(vec![
+        VerifyError::InvalidEmitter { idx: 0 },
+        VerifyError::InvalidEmitter { idx: 1 },
+        VerifyError::EmitterOutOfRange { op_idx: 0, emitter: 2, n_emitters: 2 },
+    ]));
+}
+
+#[test]
+fn emitters_by_name() {
+    let text = BUBBLES_YML.replace("Emit: [ 0,", "Emit: [ \"bubbles\",");
+    assert_eq!(run_frames(&text, true), run_frames(BUBBLES_YML, true));
+
+    let text = BUBBLES_YML.replace("Emit: [ 0,", "Emit: [ \"foam\",");
+    let err = Dmo::new_from_yml_str(&text).err().unwrap();
+    assert_eq!(err.to_string(), "line 4: operator 1: unknown emitter \"foam\"");
+}
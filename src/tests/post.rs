@@ -0,0 +1,136 @@
+#![cfg(test)]
+
+use std::f32;
+use std::f32::consts::PI;
+
+use dmo::{Dmo, Context};
+use color::{Cell, Style};
+use post::{PostFx, Axis, ascii_char};
+use bytecode::{Bytecode, VerifyError, verify};
+
+/// The chars of the cells, a row per line.
+fn apply(fx: PostFx, text: &str, time: f32, mirror: &[String]) -> String {
+    let rows: Vec<&str> = text.lines().collect();
+    let width = rows[0].chars().count();
+    let cells: Vec<Cell> = text.chars()
+        .filter(|ch| *ch != '\n')
+        .map(|ch| Cell::new(ch, Style::default()))
+        .collect();
+
+    let out = fx.apply(&cells, width, rows.len(), time, mirror);
+    let lines: Vec<String> = out.chunks(width).map(|row| row.iter().map(|c| c.ch).collect()).collect();
+    lines.join("\n")
+}
+
+#[test]
+fn wave_moves_rows_and_columns() {
+    // a quarter turn per row, so the rows shift by 0, 1, 0, -1 cells
+    let text = "abcd\nefgh\nijkl\nmnop";
+    let fx = PostFx::Wave(Axis::Horizontal, 1.0, PI / 2.0, 0.0);
+    assert_eq!(apply(fx, text, 0.0, &[]), "abcd\n efg\nijkl\nnop ");
+
+    // the speed moves the wave with the time
+    let fx = PostFx::Wave(Axis::Horizontal, 1.0, PI / 2.0, PI / 2.0);
+    assert_eq!(apply(fx, text, 1.0, &[]), " abc\nefgh\njkl \nmnop");
+
+    let fx = PostFx::Wave(Axis::Vertical, 1.0, PI / 2.0, 0.0);
+    assert_eq!(apply(fx, text, 0.0, &[]), "a ch\nebgl\nifkp\nmjo ");
+}
+
+#[test]
+fn huge_wave_shifts_everything_out() {
+    let text = "abcd\nefgh";
+    let fx = PostFx::Wave(Axis::Horizontal, 1e30, PI / 2.0, 0.0);
+    assert_eq!(apply(fx, text, 0.0, &[]), "abcd\n    ");
+    let fx = PostFx::Wave(Axis::Vertical, -1e30, PI / 2.0, 0.0);
+    assert_eq!(apply(fx, text, 0.0, &[]), "a   \ne   ");
+    // infinity times the sine of 0 is NaN, which doesn't shift
+    let fx = PostFx::Wave(Axis::Horizontal, f32::INFINITY, PI / 2.0, 0.0);
+    assert_eq!(apply(fx, text, 0.0, &[]), "abcd\n    ");
+}
+
+#[test]
+fn mirror_uses_the_mirror_table() {
+    let mirror = vec![String::from("<>"), String::from("()")];
+    assert_eq!(apply(PostFx::Mirror(Axis::Horizontal), "><(x\n.-^v", 0.0, &mirror), "x)><\nv^-.");
+    assert_eq!(apply(PostFx::Mirror(Axis::Vertical), "><(x\n.-^v", 0.0, &mirror), ".-^v\n><(x");
+}
+
+#[test]
+fn invert_remap_and_ascii() {
+    let fx = PostFx::Invert(String::from(" .:#"));
+    assert_eq!(apply(fx, " .:#x", 0.0, &[]), "#:. x");
+
+    let fx = PostFx::Remap(String::from("~ø"), String::from("-o"));
+    assert_eq!(apply(fx, "~ø~°", 0.0, &[]), "-o-°");
+
+    assert_eq!(apply(PostFx::Ascii, "<°)))><│█€", 0.0, &[]), "<o)))><|#?");
+    assert_eq!(ascii_char('~'), '~');
+}
+
+const POST_YML: &'static str = r#"
+operators:
+  - Clear: 46
+  - Draw: [ 0, 1, 1, 0.0, Right, Clip ]
+  - Print
+
+context:
+  width: 6
+  height: 3
+  mirror: [ "<>" ]
+  post:
+    - Mirror: Horizontal
+    - Remap: [ ".", " " ]
+    - Ascii
+  sprites:
+    - "°<><"
+"#;
+
+#[test]
+fn post_chain_in_jit_fn_and_interpreter() {
+    let mut jit = Dmo::new_from_yml_str(POST_YML).unwrap();
+    jit.build_jit_fn().unwrap();
+    let mut interpreter = Dmo::new_from_yml_str(POST_YML).unwrap();
+
+    jit.run_jit_fn();
+    interpreter.run_operators();
+    let frame = jit.get_context().render_frame();
+    assert_eq!(frame, interpreter.get_context().render_frame());
+    assert_eq!(frame, "\x1b[H           \n      ><>o \n           \n");
+
+    // the canvas keeps the frame as it was drawn
+    let row: String = jit.get_context().buffer[6 .. 12].iter().map(|c| c.ch).collect();
+    assert_eq!(row, ".°<><.");
+
+    jit.set_post(vec![]);
+    assert_eq!(jit.get_context().render_frame(), "\x1b[H     ......\n     .°<><.\n     ......\n");
+}
+
+#[test]
+fn post_chain_in_bytecode() {
+    let text = POST_YML.replace("    - Ascii\n", "    - Ascii\n    - Wave: [ Vertical, 2.0, 0.5, -1.0 ]\n    - Invert: \" .:\"\n");
+    let dmo = Dmo::new_from_yml_str(&text).unwrap();
+    assert_eq!(dmo.get_context().post, vec![
+        PostFx::Mirror(Axis::Horizontal),
+        PostFx::Remap(String::from("."), String::from(" ")),
+        PostFx::Ascii,
+        PostFx::Wave(Axis::Vertical, 2.0, 0.5, -1.0),
+        PostFx::Invert(String::from(" .:")),
+    ]);
+
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+    assert_eq!(decoded.get_context().post, dmo.get_context().post);
+}
+
+#[test]
+fn verify_post_effects() {
+    let mut context = Context::new();
+    context.post.push(PostFx::Wave(Axis::Horizontal, 1.0, f32::NAN, 1.0));
+    context.post.push(PostFx::Remap(String::from("ab"), String::from("c")));
+    context.post.push(PostFx::Remap(String::from("ab"), String::from("cd")));
+
+    assert_eq!(verify(&context, &vec![]), Err(vec![
+        VerifyError::InvalidPost { idx: 0 },
+        VerifyError::InvalidPost { idx: 1 },
+    ]));
+}
@@ -0,0 +1,184 @@
+#![cfg(test)]
+
+use std::f32;
+
+use dmo::{Dmo, Context, Operator};
+use scene::{Scene, scene_end, active_scene, timeline_end};
+use sprite::{Facing, Edge};
+use track::Param;
+use bytecode::{Bytecode, VerifyError, verify_scenes};
+
+const SCENE_YML: &'static str = r#"
+context:
+  width: 6
+  sprites:
+    fish: "><>"
+
+scenes:
+  - name: "dots"
+    start: 0.0
+    end: 2.0
+    operators:
+      - Clear: 46
+      - Draw: [ fish, 0, 0, 1.0, Right, Clip ]
+  - name: "dashes"
+    start: 2.0
+    operators:
+      - Clear: 45
+      - Draw: [ fish, 0, 0, 1.0, Right, Clip ]
+      - Exit: 1.5
+"#;
+
+#[test]
+fn scene_end_and_active_scene() {
+    let scenes = vec![
+        Scene::new("a", 0.0, Some(2.0), vec![]),
+        Scene::new("b", 3.0, None, vec![]),
+        Scene::new("c", 5.0, None, vec![]),
+    ];
+
+    assert_eq!(scene_end(&scenes, 0), 2.0);
+    assert_eq!(scene_end(&scenes, 1), 5.0);
+    assert_eq!(scene_end(&scenes, 2), f32::INFINITY);
+
+    assert_eq!(active_scene(&scenes, 1.0), Some(0));
+    assert_eq!(active_scene(&scenes, 2.5), None);
+    assert_eq!(active_scene(&scenes, 3.0), Some(1));
+    assert_eq!(active_scene(&scenes, 100.0), Some(2));
+
+    assert_eq!(timeline_end(&scenes), f32::INFINITY);
+    assert_eq!(timeline_end(&scenes[0 .. 1]), 2.0);
+}
+
+#[test]
+fn scenes_run_with_local_time() {
+    let mut jit = Dmo::new_from_yml_str(SCENE_YML).unwrap();
+    jit.build_jit_fn().unwrap();
+    let mut interpreter = Dmo::new_from_yml_str(SCENE_YML).unwrap();
+
+    let mut frames: Vec<String> = vec![];
+    let mut running: Vec<bool> = vec![];
+    for _ in 0 .. 5 {
+        jit.run_jit_fn();
+        interpreter.run_operators();
+        assert_eq!(jit.get_context().render_frame(), interpreter.get_context().render_frame());
+        assert_eq!(jit.get_is_running(), interpreter.get_is_running());
+
+        frames.push(jit.get_context().render_frame());
+        running.push(jit.get_is_running());
+        jit.add_to_time(1.0);
+        interpreter.add_to_time(1.0);
+    }
+
+    // the second scene starts over at x 0, and its Exit ends the timeline,
+    // as it is the last scene
+    assert_eq!(frames, vec!["     ><>...\r",
+                            "     .><>..\r",
+                            "     ><>---\r",
+                            "     -><>--\r",
+                            "     --><>-\r"]);
+    assert_eq!(running, vec![true, true, true, true, false]);
+}
+
+#[test]
+fn exit_in_a_scene_skips_to_its_end() {
+    let text = SCENE_YML
+        .replace("    end: 2.0\n", "    end: 3.0\n")
+        .replace("      - Clear: 46\n", "      - Clear: 46\n      - Exit: 0.5\n")
+        .replace("    start: 2.0\n", "    start: 3.0\n");
+    let mut dmo = Dmo::new_from_yml_str(&text).unwrap();
+
+    dmo.add_to_time(1.0);
+    dmo.run_operators();
+    assert!(dmo.get_is_running());
+    assert_eq!(dmo.get_context().time, 3.0);
+
+    dmo.run_operators();
+    assert_eq!(dmo.get_context().render_frame(), "     ><>---\r");
+}
+
+#[test]
+fn end_stops_the_demo() {
+    let text = SCENE_YML.replace("      - Exit: 1.5\n", "") + "end: 2.5\n";
+
+    let mut jit = Dmo::new_from_yml_str(&text).unwrap();
+    jit.build_jit_fn().unwrap();
+    let mut interpreter = Dmo::new_from_yml_str(&text).unwrap();
+    assert_eq!(jit.get_end(), Some(2.5));
+
+    jit.add_to_time(2.0);
+    interpreter.add_to_time(2.0);
+    jit.run_jit_fn();
+    interpreter.run_operators();
+    assert!(jit.get_is_running());
+    assert!(interpreter.get_is_running());
+
+    jit.add_to_time(1.0);
+    interpreter.add_to_time(1.0);
+    jit.run_jit_fn();
+    interpreter.run_operators();
+    assert!(!jit.get_is_running());
+    assert!(!interpreter.get_is_running());
+}
+
+#[test]
+fn scenes_in_bytecode() {
+    let text = String::from(SCENE_YML) + "end: 30.0\n";
+    let dmo = Dmo::new_from_yml_str(&text).unwrap();
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+
+    let scenes = decoded.get_scenes();
+    assert_eq!(scenes.len(), 2);
+    assert_eq!(scenes[0].name, "dots");
+    assert_eq!((scenes[0].start, scenes[0].end), (0.0, Some(2.0)));
+    assert_eq!((scenes[1].start, scenes[1].end), (2.0, None));
+    assert_eq!(scenes[1].operators.len(), 3);
+    match scenes[1].operators[2] {
+        Operator::Exit(limit) => assert_eq!(limit, Param::Value(1.5)),
+        _ => panic!("expected Exit"),
+    }
+    assert_eq!(decoded.get_end(), Some(30.0));
+}
+
+#[test]
+fn noops_before_scenes_in_bytecode() {
+    let text = SCENE_YML.replace("      - Exit", "      - NOOP\n      - Exit") + "operators:\n  - NOOP\n";
+    let dmo = Dmo::new_from_yml_str(&text).unwrap();
+    let decoded = Dmo::read_bytecode(&mut &dmo.to_bytecode()[..]).unwrap();
+
+    assert_eq!(decoded.get_operators().len(), 1);
+    assert_eq!(decoded.get_scenes().len(), 2);
+    assert_eq!(decoded.get_scenes()[1].operators.len(), 4);
+    assert_eq!(decoded.to_bytecode(), dmo.to_bytecode());
+}
+
+#[test]
+fn unknown_names_in_scenes_are_errors() {
+    let text = SCENE_YML.replace("      - Draw: [ fish, 0, 0, 1.0, Right, Clip ]\n      - Exit",
+                                 "      - Draw: [ shark, 0, 0, 1.0, Right, Clip ]\n      - Exit");
+    let err = Dmo::new_from_yml_str(&text).err().unwrap();
+    assert_eq!(err.to_string(), "line 18: scene 1: operator 1: unknown sprite \"shark\"");
+}
+
+#[test]
+fn verify_scene_times_and_operators() {
+    let context = Context::new();
+    let scenes = vec![
+        Scene::new("backwards", 2.0, Some(1.0), vec![]),
+        Scene::new("never", f32::NAN, None, vec![]),
+        Scene::new("no end", 0.0, Some(f32::NAN), vec![]),
+        Scene::new("forever", 0.0, Some(f32::INFINITY), vec![]),
+        Scene::new("bad sprite", 0.0, None, vec![Operator::NOOP, Operator::Draw(
+            3, Param::Value(0.0), Param::Value(0.0), Param::Value(0.0), Facing::Right, Edge::Clip)]),
+    ];
+
+    assert_eq!(verify_scenes(&context, &scenes, Some(f32::INFINITY)), Err(vec![
+        VerifyError::InvalidScene { idx: 0 },
+        VerifyError::InvalidScene { idx: 1 },
+        VerifyError::InvalidScene { idx: 2 },
+        VerifyError::InvalidScene { idx: 3 },
+        VerifyError::InScene { scene: 4, error: Box::new(
+            VerifyError::SpriteOutOfRange { op_idx: 1, sprite_idx: 3, n_sprites: 0 }) },
+        VerifyError::InvalidEnd { end: f32::INFINITY },
+    ]));
+}
@@ -0,0 +1,197 @@
+#![cfg(test)]
+
+use dmo::{Dmo, Context, Operator};
+use shader::{Shader, Expr, Func, MAX_REGISTERS, parse, shade_char};
+use bytecode::{Bytecode, VerifyError, verify};
+
+#[test]
+fn parse_expressions() {
+    use shader::Expr::*;
+
+    assert_eq!(parse("x + y * 2").unwrap(),
+               Add(Box::new(X), Box::new(Mul(Box::new(Y), Box::new(Num(2.0))))));
+    assert_eq!(parse(" -(t - 1) / 4").unwrap(),
+               Div(Box::new(Neg(Box::new(Sub(Box::new(T), Box::new(Num(1.0)))))), Box::new(Num(4.0))));
+    assert_eq!(parse("mod(x, 3)").unwrap(),
+               Call(Func::Mod, vec![X, Num(3.0)]));
+
+    assert_eq!(parse("sin(x").err().unwrap(), "char 5: expected ')'");
+    assert_eq!(parse("x + z").err().unwrap(), "char 4: unknown name \"z\"");
+    assert_eq!(parse("mod(x)").err().unwrap(), "char 6: mod takes 2 arguments");
+    assert_eq!(parse("1..2").err().unwrap(), "char 0: invalid number \"1..2\"");
+    assert_eq!(parse("x y").err().unwrap(), "char 2: unexpected char");
+    assert_eq!(parse("").err().unwrap(), "char 0: unexpected end");
+}
+
+#[test]
+fn eval_expressions() {
+    let at = |text: &str, x: f32, y: f32, t: f32| parse(text).unwrap().eval(x, y, t);
+
+    assert_eq!(at("x * 10 + y - t", 2.0, 3.0, 1.5), 21.5);
+    assert_eq!(at("floor(-x / 2)", 3.0, 0.0, 0.0), -2.0);
+    assert_eq!(at("mod(x, 3)", -1.0, 0.0, 0.0), 2.0);
+    assert_eq!(at("abs(y - 5)", 0.0, 2.0, 0.0), 3.0);
+    assert_eq!(at("ramp(cos(0))", 0.0, 0.0, 0.0), 10.0);
+    assert_eq!(at("ramp(-1)", 0.0, 0.0, 0.0), 0.0);
+
+    assert_eq!(shade_char(0.0), ' ');
+    assert_eq!(shade_char(4.9), '=');
+    assert_eq!(shade_char(10.0), '@');
+    assert_eq!(shade_char(-3.0), ' ');
+    assert_eq!(shade_char(::std::f32::NAN), ' ');
+}
+
+#[test]
+fn registers() {
+    assert_eq!(parse("x").unwrap().registers(), 1);
+    assert_eq!(parse("(x + 1) * (y + 2)").unwrap().registers(), 3);
+    assert_eq!(parse("floor(x)").unwrap().registers(), 2);
+    assert_eq!(parse("mod(x, y)").unwrap().registers(), 4);
+
+    let deep = "x+(x+(x+(x+(x+(x+(x+(x+x)))))))";
+    assert_eq!(parse(deep).unwrap().registers(), 9);
+
+    let mut context = Context::new();
+    context.shaders.push(Shader::new("deep", deep));
+    context.shaders.push(Shader::new("broken", "sin("));
+    assert!(parse(deep).unwrap().registers() > MAX_REGISTERS);
+
+    let operators = vec![Operator::Shade(2)];
+    assert_eq!(verify(&context, &operators), Err(vec![
+        VerifyError::InvalidShader { idx: 0, error: String::from("the expression is nested too deep") },
+        VerifyError::InvalidShader { idx: 1, error: String::from("char 4: unexpected end") },
+        VerifyError::ShaderOutOfRange { op_idx: 0, shader: 2, n_shaders: 2 },
+    ]));
+}
+
+fn shader_yml(fill: &str) -> String {
+    format!(r#"
+operators:
+  - Shade: 0
+
+context:
+  width: 17
+  height: 5
+  shaders:
+    - name: "test"
+      fill: "{}"
+  sprites: []
+"#, fill)
+}
+
+/// The values from the JIT fn and from the interpreter at a few times.
+fn jit_and_interpreter_values(fill: &str) -> Vec<(Vec<f32>, Vec<f32>)> {
+    let text = shader_yml(fill);
+    let mut jit = Dmo::new_from_yml_str(&text).unwrap();
+    jit.build_jit_fn().unwrap();
+    let mut interpreter = Dmo::new_from_yml_str(&text).unwrap();
+
+    let mut values: Vec<(Vec<f32>, Vec<f32>)> = vec![];
+    for _ in 0 .. 3 {
+        jit.run_jit_fn();
+        interpreter.run_operators();
+        values.push((jit.get_context().shade_values.clone(),
+                     interpreter.get_context().shade_values.clone()));
+        jit.add_to_time(0.7);
+        interpreter.add_to_time(0.7);
+    }
+    values
+}
+
+#[test]
+fn jit_shader_is_exact_without_trig() {
+    let fill = "mod(x - 7.5, 3) + floor(-y / 2) - abs(x - 8) * t + -x / (y + 1)";
+    for (jit, interpreter) in jit_and_interpreter_values(fill) {
+        assert_eq!(jit, interpreter);
+    }
+}
+
+#[test]
+fn jit_shader_matches_the_interpreter() {
+    let fill = "ramp(sin(x*0.3 + t) + cos(t*2))";
+    for (jit, interpreter) in jit_and_interpreter_values(fill) {
+        assert_eq!(jit.len(), 17 * 5);
+        for (a, b) in jit.iter().zip(interpreter.iter()) {
+            assert!((a - b).abs() < 0.0001, "{} != {}", a, b);
+        }
+    }
+
+    let text = shader_yml("floor(x / 2) + y");
+    let mut dmo = Dmo::new_from_yml_str(&text).unwrap();
+    dmo.build_jit_fn().unwrap();
+    dmo.run_jit_fn();
+    let expected = String::from("\x1b[H") +
+        "       ..::--==++**##%\n" +
+        "     ..::--==++**##%%@\n" +
+        "     ::--==++**##%%@@@\n" +
+        "     --==++**##%%@@@@@\n" +
+        "     ==++**##%%@@@@@@@\n";
+    assert_eq!(dmo.get_context().render_frame(), expected);
+}
+
+#[test]
+fn shaders_in_bytecode() {
+    let text = shader_yml("ramp(sin(x*0.3 + t))");
+    let dmo = Dmo::new_from_yml_str(&text).unwrap();
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+
+    assert_eq!(decoded.get_context().shaders, dmo.get_context().shaders);
+    assert!(decoded.get_context().shaders[0].expr.is_some());
+    match decoded.get_operators()[0] {
+        Operator::Shade(shader) => assert_eq!(shader, 0),
+        _ => panic!("expected Shade"),
+    }
+}
+
+#[test]
+fn expr_size() {
+    let expr = Expr::Call(Func::Abs, vec![Expr::Num(-2.0)]);
+    assert_eq!(expr.eval(0.0, 0.0, 0.0), 2.0);
+    assert_eq!(expr.size(), 2);
+}
+
+#[test]
+fn shaders_by_name() {
+    let text = shader_yml("floor(x / 2) + y");
+    let dmo = Dmo::new_from_yml_str(&text.replace("Shade: 0", "Shade: \"test\"")).unwrap();
+    assert_eq!(dmo.to_bytecode(), Dmo::new_from_yml_str(&text).unwrap().to_bytecode());
+
+    let err = Dmo::new_from_yml_str(&text.replace("Shade: 0", "Shade: \"tset\"")).err().unwrap();
+    assert_eq!(err.to_string(), "line 3: operator 0: unknown shader \"tset\"");
+}
+
+#[test]
+fn jit_trig_matches_the_interpreter_far_from_zero() {
+    // fsin and fcos are off by a lot here, the JIT code calls the same
+    // functions as the interpreter
+    let text = shader_yml("sin(t + x) * 4 + cos(t * (y + 1)) + sin(cos(x))");
+    let mut jit = Dmo::new_from_yml_str(&text).unwrap();
+    jit.build_jit_fn().unwrap();
+    let mut interpreter = Dmo::new_from_yml_str(&text).unwrap();
+
+    for t in [1.0e6, 3.0e9, 1.0e20].iter() {
+        jit.add_to_time(*t);
+        interpreter.add_to_time(*t);
+        jit.run_jit_fn();
+        interpreter.run_operators();
+        assert_eq!(jit.get_context().shade_values, interpreter.get_context().shade_values);
+    }
+}
+
+#[test]
+fn jit_floor_matches_the_interpreter_out_of_the_int_range() {
+    let fills = [
+        "floor(x*10000000000 - 5000000000)",
+        "mod(x*10000000000, 7)",
+        "floor(x/0) + floor((x - 8)/0)",
+        "mod(x - 8, 0)",
+        "floor(x*1000000 + 0.5) + floor(-x*0.7)",
+    ];
+    for fill in fills.iter() {
+        for (jit, interpreter) in jit_and_interpreter_values(fill) {
+            for (a, b) in jit.iter().zip(interpreter.iter()) {
+                assert!(a == b || a.is_nan() && b.is_nan(), "{}: {} != {}", fill, a, b);
+            }
+        }
+    }
+}
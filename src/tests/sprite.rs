@@ -0,0 +1,240 @@
+#![cfg(test)]
+
+use dmo::{Dmo, Context, Operator, UnknownName, UnknownNames, facing_to_code};
+use sprite::{Sprite, AnimMode, Facing, DEFAULT_MIRROR, mirror_char};
+use bytecode::{Bytecode, VerifyError, verify};
+
+#[test]
+fn anim_mode_frames() {
+    let frames = |mode: AnimMode| -> Vec<usize> {
+        (0 .. 8).map(|t| mode.frame_at(t as f32 * 0.5, 2.0, 3)).collect()
+    };
+
+    assert_eq!(frames(AnimMode::Loop), vec![0, 1, 2, 0, 1, 2, 0, 1]);
+    assert_eq!(frames(AnimMode::PingPong), vec![0, 1, 2, 1, 0, 1, 2, 1]);
+    assert_eq!(frames(AnimMode::Once), vec![0, 1, 2, 2, 2, 2, 2, 2]);
+
+    // a single frame, or a negative time
+    assert_eq!(AnimMode::PingPong.frame_at(5.0, 2.0, 1), 0);
+    assert_eq!(AnimMode::Loop.frame_at(-1.0, 2.0, 3), 0);
+}
+
+#[test]
+fn frames_in_yml() {
+    let text = r#"
+operators: []
+
+context:
+  sprites:
+    - [ "><>", ">=>" ]
+    - frames:
+        - |
+          o
+          ><>
+        - |
+          O
+          >=>
+      transparent: " "
+    - text: "><>"
+"#;
+
+    let dmo = Dmo::new_from_yml_str(text).unwrap();
+    let ref sprites = dmo.get_sprites();
+
+    assert_eq!(sprites[0].frame_count(), 2);
+    assert_eq!(sprites[0].frame_lines(1), vec![vec!['>', '=', '>']]);
+    assert_eq!(sprites[1].frame_count(), 2);
+    assert_eq!(sprites[1].height(), 2);
+    assert_eq!(sprites[1].frame_lines(1)[0], vec!['O']);
+    assert_eq!(sprites[2].frame_count(), 1);
+
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+    assert_eq!(decoded.get_sprites()[0], sprites[0]);
+    // the newline at the end of the block scalars is kept
+    assert_eq!(decoded.get_sprites()[1].frames(), vec!["o\n><>\n", "O\n>=>\n"]);
+    assert_eq!(decoded.get_sprites()[1], sprites[1]);
+    assert_eq!(decoded.get_sprites()[2], Sprite::from("><>"));
+}
+
+#[test]
+fn animate_in_jit_fn() {
+    let text = r#"
+operators:
+  - Clear: 46
+  - Animate: [ 0, 4.0, PingPong ]
+  - Draw: [ 0, 0, 0, 0.0, Right, Wrap ]
+
+context:
+  width: 3
+  sprites:
+    - [ "a", "b", "c" ]
+"#;
+
+    let mut dmo = Dmo::from_bytecode(Dmo::new_from_yml_str(text).unwrap().to_bytecode());
+    match dmo.get_operators()[1] {
+        Operator::Animate(0, fps, AnimMode::PingPong) => assert_eq!(fps, 4.0),
+        _ => panic!("expected Animate"),
+    }
+
+    dmo.build_jit_fn().unwrap();
+
+    let mut drawn: Vec<char> = vec![];
+    for _ in 0 .. 6 {
+        dmo.run_jit_fn();
+        drawn.push(dmo.get_context().buffer[0].ch);
+        dmo.add_to_time(0.25);
+    }
+
+    assert_eq!(drawn, vec!['a', 'b', 'c', 'b', 'a', 'b']);
+}
+
+#[test]
+fn mirror_chars() {
+    let mirror: Vec<String> = DEFAULT_MIRROR.iter().map(|p| String::from(*p)).collect();
+    let flipped: String = "}-<ø>/".chars().map(|ch| mirror_char(ch, &mirror)).collect();
+    assert_eq!(flipped, "{->ø<\\");
+}
+
+#[test]
+fn negative_speed_wraps_to_the_end_of_the_row() {
+    let mut context = Context::new();
+    context.set_size(10, 1);
+    context.sprites.push(Sprite::from("><>"));
+    context.impl_clear('.' as u32);
+
+    // x = 1, moved 1.5 to the left is -0.5, floor() is -1, which is column 9
+    context.time = 1.0;
+    context.impl_draw(0, 1.0, 0, facing_to_code(Facing::Right), 0, -1.5);
+
+    assert_eq!(context.render_frame(), "     <>.......>\r");
+}
+
+#[test]
+fn mirrored_multi_line_sprite() {
+    let text = r#"
+operators:
+  - Clear: 46
+  - Draw: [ 0, 0, 0, 0.0, Left, Wrap ]
+  - Draw: [ 1, 4, 0, -1.0, Auto, Wrap ]
+  - Draw: [ 1, 4, 1, 1.0, Auto, Wrap ]
+
+context:
+  width: 10
+  height: 3
+  mirror: [ "<>", "()", "/\\" ]
+  sprites:
+    - text: |
+        ><>
+        (/_
+      transparent: " "
+    - "><((°>"
+"#;
+
+    let mut dmo = Dmo::from_bytecode(Dmo::new_from_yml_str(text).unwrap().to_bytecode());
+    dmo.build_jit_fn().unwrap();
+    dmo.run_jit_fn();
+
+    let expected = String::from("\x1b[H") +
+        "     <><.<°))><\n" +
+        "     _\\).><((°>\n" +
+        "     ..........\n";
+    assert_eq!(dmo.get_context().render_frame(), expected);
+}
+
+#[test]
+fn verify_mirror_table() {
+    let mut context = Context::new();
+    context.mirror = vec![String::from("<>"), String::from("(")];
+    assert_eq!(verify(&context, &vec![]).unwrap_err(),
+               vec![VerifyError::InvalidMirrorPair { idx: 1 }]);
+}
+
+#[test]
+fn sprites_by_name() {
+    let text = r#"
+operators:
+  - Animate: [ "fish", 2.0, Loop ]
+  - Draw: [ "shark", 0, 0, 0.0, Right, Wrap ]
+  - Draw: [ 1, 0, 0, 0.0, Right, Wrap ]
+
+context:
+  sprites:
+    shark: " ><(([°> "
+    fish: [ " ><> ", " >=> " ]
+"#;
+
+    let dmo = Dmo::new_from_yml_str(text).unwrap();
+
+    // named sprites are in the order of their names
+    assert_eq!(dmo.get_sprites()[0], Sprite::Frames(vec![String::from(" ><> "), String::from(" >=> ")]));
+    assert_eq!(dmo.get_sprites()[1], Sprite::from(" ><(([°> "));
+
+    match dmo.get_operators()[0] {
+        Operator::Animate(idx, _, _) => assert_eq!(idx, 0),
+        _ => panic!("expected Animate"),
+    }
+    match dmo.get_operators()[1] {
+        Operator::Draw(idx, _, _, _, _, _) => assert_eq!(idx, 1),
+        _ => panic!("expected Draw"),
+    }
+    match dmo.get_operators()[2] {
+        Operator::Draw(idx, _, _, _, _, _) => assert_eq!(idx, 1),
+        _ => panic!("expected Draw"),
+    }
+}
+
+#[test]
+fn unknown_sprite_names_are_errors() {
+    let text = r#"
+operators:
+  - Clear: 32
+  - Draw: [ "shrak", 0, 0, 0.0, Right, Wrap ]
+  - Draw: [ "fish", 0, 0, 0.0, Right, Wrap ]
+  - Animate: [ "squid", 2.0, Loop ]
+
+context:
+  sprites:
+    shark: " ><(([°> "
+    fish: " ><> "
+"#;
+
+    let err = Dmo::new_from_yml_str(text).err().unwrap();
+    let expected = UnknownNames(vec![
+        UnknownName { scene: None, op_idx: 1, kind: "sprite", name: String::from("shrak"), idx: None, line: Some(4) },
+        UnknownName { scene: None, op_idx: 3, kind: "sprite", name: String::from("squid"), idx: None, line: Some(6) },
+    ]);
+    assert_eq!(err.to_string(), expected.to_string());
+    assert_eq!(expected.to_string(),
+               "line 4: operator 1: unknown sprite \"shrak\"\n\
+                line 6: operator 3: unknown sprite \"squid\"");
+
+    // names can't refer to a sprite list
+    let text = "operators:\n  - Draw: [ \"fish\", 0, 0, 0.0, Right, Wrap ]\ncontext:\n  sprites: [ \" ><> \" ]\n";
+    let err = Dmo::new_from_yml_str(text).err().unwrap();
+    assert_eq!(err.to_string(), "line 2: operator 0: unknown sprite \"fish\"");
+}
+
+#[test]
+fn names_past_255_are_errors() {
+    // the names are padded so that their order is the order of the numbers
+    let mut text = String::from("operators:\n  - Draw: [ \"s299\", 0, 0, 0.0, Right, Wrap ]\ncontext:\n  sprites:\n");
+    for i in 0 .. 300 {
+        text.push_str(&format!("    s{:03}: \"o\"\n", i));
+    }
+
+    let err = Dmo::new_from_yml_str(&text).err().unwrap();
+    assert_eq!(err.to_string(), "line 2: operator 0: sprite \"s299\" has index 299, over 255");
+
+    let dmo = Dmo::new_from_yml_str(&text.replace("\"s299\"", "\"s255\"")).unwrap();
+    match dmo.get_operators()[0] {
+        Operator::Draw(idx, _, _, _, _, _) => assert_eq!(idx, 255),
+        _ => panic!("expected Draw"),
+    }
+}
+
+#[test]
+fn flow_style_operators_have_no_lines() {
+    let text = "operators: [ { Draw: [ \"shrak\", 0, 0, 0.0, Right, Wrap ] } ]\ncontext:\n  sprites:\n    fish: \"o\"\n";
+    let err = Dmo::new_from_yml_str(text).err().unwrap();
+    assert_eq!(err.to_string(), "operator 0: unknown sprite \"shrak\"");
+}
@@ -0,0 +1,131 @@
+#![cfg(test)]
+
+use dmo::{Dmo, Context, Operator};
+use track::{Track, Keyframe, Ease, Param};
+use bytecode::{Bytecode, VerifyError, verify};
+
+#[test]
+fn ease_curves() {
+    let at = |ease: Ease, u: f32| ease.interpolate(0.0, 0.0, 10.0, 10.0, u);
+
+    assert_eq!(at(Ease::Step, 0.9), 0.0);
+    assert_eq!(at(Ease::Linear, 0.25), 2.5);
+    assert_eq!(at(Ease::Smoothstep, 0.5), 5.0);
+    assert_eq!(at(Ease::Smoothstep, 0.25), 1.5625);
+    assert_eq!(at(Ease::EaseIn, 0.5), 2.5);
+    assert_eq!(at(Ease::EaseOut, 0.5), 7.5);
+    assert_eq!(at(Ease::EaseInOut, 0.25), 1.25);
+    assert_eq!(at(Ease::EaseInOut, 0.75), 8.75);
+
+    for ease in [Ease::Step, Ease::Linear, Ease::Smoothstep, Ease::Cubic,
+                 Ease::EaseIn, Ease::EaseOut, Ease::EaseInOut].iter() {
+        assert_eq!(at(*ease, 0.0), 0.0);
+        if *ease != Ease::Step {
+            assert_eq!(at(*ease, 1.0), 10.0);
+        }
+    }
+
+    // the spline passes through the keys, and follows the slope of the
+    // neighbours in between
+    assert_eq!(Ease::Cubic.interpolate(0.0, 10.0, 20.0, 30.0, 0.5), 15.0);
+}
+
+#[test]
+fn track_value_at() {
+    let track = Track::new("swim", vec![
+        Keyframe::new(1.0, 0.0, Ease::Linear),
+        Keyframe::new(3.0, 10.0, Ease::Step),
+        Keyframe::new(4.0, 20.0, Ease::Linear),
+    ]);
+
+    assert_eq!(track.value_at(0.0), 0.0);
+    assert_eq!(track.value_at(2.0), 5.0);
+    assert_eq!(track.value_at(3.5), 10.0);
+    assert_eq!(track.value_at(4.0), 20.0);
+    assert_eq!(track.value_at(100.0), 20.0);
+
+    assert_eq!(Track::new("empty", vec![]).value_at(1.0), 0.0);
+}
+
+const TRACK_YML: &'static str = r#"
+operators:
+  - Clear: 46
+  - Draw: [ 0, { track: "x" }, 0, 0.0, Right, Clip ]
+  - Exit: { track: "end" }
+
+context:
+  width: 8
+  tracks:
+    - name: "x"
+      keys:
+        - { t: 0.0, value: 0.0 }
+        - { t: 2.0, value: 4.0, ease: Step }
+    - name: "end"
+      keys:
+        - { t: 0.0, value: 2.5 }
+  sprites:
+    - "><>"
+"#;
+
+#[test]
+fn track_params_in_jit_fn_and_interpreter() {
+    let mut jit = Dmo::new_from_yml_str(TRACK_YML).unwrap();
+    jit.build_jit_fn().unwrap();
+    let mut interpreter = Dmo::new_from_yml_str(TRACK_YML).unwrap();
+
+    let mut frames: Vec<String> = vec![];
+    for _ in 0 .. 4 {
+        jit.run_jit_fn();
+        interpreter.run_operators();
+        assert_eq!(jit.get_context().render_frame(), interpreter.get_context().render_frame());
+        assert_eq!(jit.get_is_running(), interpreter.get_is_running());
+
+        frames.push(jit.get_context().render_frame());
+        jit.add_to_time(1.0);
+        interpreter.add_to_time(1.0);
+    }
+
+    assert_eq!(frames, vec!["     ><>.....\r",
+                            "     ..><>...\r",
+                            "     ....><>.\r",
+                            "     ....><>.\r"]);
+    assert!(!jit.get_is_running());
+}
+
+#[test]
+fn tracks_in_bytecode() {
+    let dmo = Dmo::new_from_yml_str(TRACK_YML).unwrap();
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+
+    assert_eq!(decoded.get_context().tracks, dmo.get_context().tracks);
+    assert_eq!(decoded.get_context().track_values, vec![0.0, 2.5]);
+    match decoded.get_operators()[1] {
+        Operator::Draw(_, x, _, _, _, _) => assert_eq!(x, Param::track(0)),
+        _ => panic!("expected Draw"),
+    }
+}
+
+#[test]
+fn unknown_track_names_are_errors() {
+    let text = TRACK_YML.replace("{ track: \"end\" }", "{ track: \"stop\" }");
+    let err = Dmo::new_from_yml_str(&text).err().unwrap();
+    assert_eq!(err.to_string(), "line 5: operator 2: unknown track \"stop\"");
+}
+
+#[test]
+fn verify_tracks() {
+    let mut context = Context::new();
+    context.tracks.push(Track::new("empty", vec![]));
+    context.tracks.push(Track::new("backwards", vec![
+        Keyframe::new(2.0, 0.0, Ease::Linear),
+        Keyframe::new(1.0, 0.0, Ease::Linear),
+    ]));
+
+    let operators = vec![Operator::Exit(Param::track(2))];
+
+    assert_eq!(verify(&context, &operators), Err(vec![
+        VerifyError::InvalidTrack { idx: 0 },
+        VerifyError::InvalidTrack { idx: 1 },
+        VerifyError::TrackOutOfRange { op_idx: 0, track: 2, n_tracks: 2 },
+    ]));
+}
@@ -0,0 +1,137 @@
+#![cfg(test)]
+
+use dmo::{Dmo, Operator};
+use color::{Cell, Style};
+use transition::{Blend, dissolve_threshold};
+use track::Param;
+use bytecode::Bytecode;
+
+fn cells(text: &str) -> Vec<Cell> {
+    text.chars().map(|ch| Cell::new(ch, Style::default())).collect()
+}
+
+fn text(cells: &Vec<Cell>) -> String {
+    cells.iter().map(|c| c.ch).collect()
+}
+
+#[test]
+fn wipe_and_fade() {
+    let (from, to) = (cells("ab  "), cells("wxyz"));
+    let at = |blend: Blend, progress: f32| text(&blend.blend(&from, &to, 4, progress, 0));
+
+    assert_eq!(at(Blend::WipeRight, 0.0), "ab  ");
+    assert_eq!(at(Blend::WipeRight, 0.5), "wx  ");
+    assert_eq!(at(Blend::WipeLeft, 0.25), "ab z");
+    assert_eq!(at(Blend::WipeLeft, 1.0), "wxyz");
+
+    // blank cells stay blank, the rest goes down the ramp and back up
+    assert_eq!(at(Blend::Fade, 0.0), "ab  ");
+    assert_eq!(at(Blend::Fade, 0.25), "++  ");
+    assert_eq!(at(Blend::Fade, 0.5), "    ");
+    assert_eq!(at(Blend::Fade, 0.75), "++++");
+    assert_eq!(at(Blend::Fade, 1.0), "wxyz");
+}
+
+#[test]
+fn dissolve_is_seeded() {
+    let from = cells(&".".repeat(64));
+    let to = cells(&"#".repeat(64));
+    let at = |progress: f32, seed: u32| text(&Blend::Dissolve.blend(&from, &to, 8, progress, seed));
+
+    assert_eq!(at(0.0, 1), ".".repeat(64));
+    assert_eq!(at(1.0, 1), "#".repeat(64));
+    assert_eq!(at(0.5, 1), at(0.5, 1));
+    assert!(at(0.5, 1) != at(0.5, 2));
+
+    // a cell which has changed stays changed
+    let (half, more) = (at(0.5, 1), at(0.7, 1));
+    for (a, b) in half.chars().zip(more.chars()) {
+        assert!(a == '.' || b == '#');
+    }
+
+    for idx in 0 .. 64 {
+        let t = dissolve_threshold(1, idx);
+        assert!(t >= 0.0 && t < 1.0);
+    }
+}
+
+const TRANSITION_YML: &'static str = r#"
+context:
+  width: 4
+  sprites: []
+
+scenes:
+  - name: "dots"
+    start: 0.0
+    end: 1.0
+    operators:
+      - Clear: 46
+  - name: "hashes"
+    start: 1.0
+    operators:
+      - Clear: 35
+      - Transition: [ WipeRight, 2.0, 0 ]
+"#;
+
+#[test]
+fn transition_from_the_scene_before() {
+    let mut jit = Dmo::new_from_yml_str(TRANSITION_YML).unwrap();
+    jit.build_jit_fn().unwrap();
+    let mut interpreter = Dmo::new_from_yml_str(TRANSITION_YML).unwrap();
+
+    let mut frames: Vec<String> = vec![];
+    for _ in 0 .. 4 {
+        jit.run_jit_fn();
+        interpreter.run_operators();
+        assert_eq!(jit.get_context().render_frame(), interpreter.get_context().render_frame());
+
+        frames.push(jit.get_context().render_frame());
+        jit.add_to_time(1.0);
+        interpreter.add_to_time(1.0);
+    }
+
+    assert_eq!(frames, vec!["     ....\r",
+                            "     ....\r",
+                            "     ##..\r",
+                            "     ####\r"]);
+}
+
+#[test]
+fn fade_in_without_scenes() {
+    let text = r#"
+operators:
+  - Clear: 35
+  - Transition: [ Fade, 4.0, 0 ]
+
+context:
+  width: 4
+  sprites: []
+"#;
+
+    let mut dmo = Dmo::new_from_yml_str(text).unwrap();
+    dmo.build_jit_fn().unwrap();
+
+    dmo.run_jit_fn();
+    assert_eq!(dmo.get_context().render_frame(), "         \r");
+    dmo.add_to_time(3.0);
+    dmo.run_jit_fn();
+    assert_eq!(dmo.get_context().render_frame(), "     ++++\r");
+    dmo.add_to_time(1.0);
+    dmo.run_jit_fn();
+    assert_eq!(dmo.get_context().render_frame(), "     ####\r");
+}
+
+#[test]
+fn transitions_in_bytecode() {
+    let dmo = Dmo::new_from_yml_str(TRANSITION_YML).unwrap();
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+
+    match decoded.get_scenes()[1].operators[1] {
+        Operator::Transition(blend, duration, seed) => {
+            assert_eq!(blend, Blend::WipeRight);
+            assert_eq!(duration, Param::Value(2.0));
+            assert_eq!(seed, 0);
+        },
+        _ => panic!("expected Transition"),
+    }
+}
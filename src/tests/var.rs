@@ -0,0 +1,189 @@
+#![cfg(test)]
+
+use std::f32;
+
+use dmo::{Dmo, Context, Operator};
+use track::Param;
+use var::{Var, VarKind, Compare, to_int, MAX_VARS};
+use bytecode::{Bytecode, VerifyError, verify};
+
+#[test]
+fn int_vars_round_toward_zero() {
+    assert_eq!(to_int(2.7), 2.0);
+    assert_eq!(to_int(-2.7), -2.0);
+    assert_eq!(to_int(3e9), -2147483648.0);
+    assert_eq!(to_int(f32::NAN), -2147483648.0);
+
+    assert_eq!(VarKind::Int.store(-0.5), 0.0);
+    assert_eq!(VarKind::Float.store(-0.5), -0.5);
+}
+
+#[test]
+fn compare_with_nan() {
+    let all = [Compare::Less, Compare::LessOrEqual, Compare::Greater,
+               Compare::GreaterOrEqual, Compare::Equal, Compare::NotEqual];
+
+    let holds: Vec<bool> = all.iter().map(|c| c.holds(1.0, 2.0)).collect();
+    assert_eq!(holds, vec![true, true, false, false, false, true]);
+
+    let holds: Vec<bool> = all.iter().map(|c| c.holds(2.0, 2.0)).collect();
+    assert_eq!(holds, vec![false, true, false, true, true, false]);
+
+    let holds: Vec<bool> = all.iter().map(|c| c.holds(f32::NAN, 2.0)).collect();
+    assert_eq!(holds, vec![false, false, false, false, false, true]);
+}
+
+const BOUNCE_YML: &'static str = r#"
+operators:
+  - Clear: 46
+  - Add: [ "frames", 1.0 ]
+  - Add: [ "y", { var: "dy" } ]
+  - Branch: [ "y", Less, 3.0, 1 ]
+  - Set: [ "dy", -1.0 ]
+  - Branch: [ "y", Greater, 0.0, 1 ]
+  - Set: [ "dy", 1.0 ]
+  - Draw: [ 0, { var: "frames" }, { var: "y" }, 0.0, Right, Wrap ]
+  - Sin: [ "wave", { var: "y" } ]
+  - Mul: [ "wave", 2.5 ]
+
+context:
+  width: 6
+  height: 4
+  vars:
+    - name: "frames"
+      kind: Int
+    - name: "y"
+    - name: "dy"
+      value: 1.0
+    - name: "wave"
+  sprites:
+    - "o"
+"#;
+
+#[test]
+fn vars_keep_their_value_across_frames() {
+    let mut jit = Dmo::new_from_yml_str(BOUNCE_YML).unwrap();
+    jit.build_jit_fn().unwrap();
+    let mut interpreter = Dmo::new_from_yml_str(BOUNCE_YML).unwrap();
+
+    let mut rows: Vec<f32> = vec![];
+    for _ in 0 .. 8 {
+        jit.run_jit_fn();
+        interpreter.run_operators();
+        assert_eq!(jit.get_context().render_frame(), interpreter.get_context().render_frame());
+
+        let (a, b) = (jit.get_context().var_values, interpreter.get_context().var_values);
+        assert_eq!(a[.. 4], b[.. 4]);
+
+        rows.push(a[1]);
+    }
+
+    assert_eq!(rows, vec![1.0, 2.0, 3.0, 2.0, 1.0, 0.0, 1.0, 2.0]);
+    assert_eq!(jit.get_context().var_values[0], 8.0);
+    assert_eq!(jit.get_context().render_frame(),
+               "\x1b[H     ......\n     ......\n     ..o...\n     ......\n");
+}
+
+/// x is 1, 2, 3 and then NaN, and each comparison of x with 2 sets a bit of
+/// the hits when it doesn't hold, as the branch jumps over the `Add`.
+fn branch_dmo() -> Dmo {
+    let compares = [Compare::Less, Compare::LessOrEqual, Compare::Greater,
+                    Compare::GreaterOrEqual, Compare::Equal, Compare::NotEqual];
+
+    let mut context = Context::new();
+    context.vars.push(Var::new("hits", VarKind::Int, 0.0));
+    context.vars.push(Var::new("x", VarKind::Float, 0.0));
+    context.reset();
+
+    let mut operators = vec![
+        Operator::Add(1, Param::Value(1.0)),
+        Operator::Branch(1, Compare::Less, Param::Value(3.5), 3),
+        Operator::Set(1, Param::Value(1e30)),
+        Operator::Mul(1, Param::Value(1e30)),
+        Operator::Mul(1, Param::Value(0.0)),
+        Operator::Set(0, Param::Value(0.0)),
+    ];
+    for (n, compare) in compares.iter().enumerate() {
+        operators.push(Operator::Branch(1, *compare, Param::Value(2.0), 1));
+        operators.push(Operator::Add(0, Param::Value((1 << n) as f32)));
+    }
+    // past the end
+    operators.push(Operator::Branch(0, Compare::Greater, Param::Value(0.0), 200));
+
+    Dmo::new(context, operators)
+}
+
+#[test]
+fn branches_in_jit_fn_match_the_interpreter() {
+    let mut jit = branch_dmo();
+    jit.build_jit_fn().unwrap();
+    let mut interpreter = branch_dmo();
+
+    let mut hits: Vec<f32> = vec![];
+    for _ in 0 .. 4 {
+        jit.run_jit_fn();
+        interpreter.run_operators();
+        assert_eq!(jit.get_context().var_values[0], interpreter.get_context().var_values[0]);
+        hits.push(jit.get_context().var_values[0]);
+    }
+
+    assert_eq!(hits, vec![0b011100 as f32, 0b100101 as f32, 0b010011 as f32, 0b011111 as f32]);
+    assert!(jit.get_context().var_values[1].is_nan());
+}
+
+#[test]
+fn vars_in_bytecode() {
+    let dmo = Dmo::new_from_yml_str(BOUNCE_YML).unwrap();
+    let decoded = Dmo::from_bytecode(dmo.to_bytecode());
+
+    assert_eq!(decoded.get_context().vars, dmo.get_context().vars);
+    assert_eq!(decoded.get_context().var_values[2], 1.0);
+    match decoded.get_operators()[3] {
+        Operator::Branch(1, Compare::Less, value, 1) => assert_eq!(value, Param::Value(3.0)),
+        _ => panic!("expected Branch"),
+    }
+}
+
+#[test]
+fn unknown_var_names_are_errors() {
+    let text = BOUNCE_YML.replace("{ var: \"dy\" }", "{ var: \"dx\" }");
+    let err = Dmo::new_from_yml_str(&text).err().unwrap();
+    assert_eq!(err.to_string(), "line 5: operator 2: unknown variable \"dx\"");
+}
+
+#[test]
+fn verify_vars() {
+    let mut context = Context::new();
+    context.vars.push(Var::new("speed", VarKind::Float, f32::NAN));
+    for i in 0 .. MAX_VARS {
+        context.vars.push(Var::new(&format!("v{}", i), VarKind::Int, 0.0));
+    }
+
+    let operators = vec![
+        Operator::Set(40, Param::Value(1.0)),
+        Operator::Branch(0, Compare::Equal, Param::var(33), 1),
+    ];
+
+    assert_eq!(verify(&context, &operators), Err(vec![
+        VerifyError::InvalidVar { idx: 0 },
+        VerifyError::InvalidVar { idx: MAX_VARS },
+        VerifyError::VarOutOfRange { op_idx: 0, var: 40, n_vars: MAX_VARS + 1 },
+        VerifyError::VarOutOfRange { op_idx: 1, var: 33, n_vars: MAX_VARS + 1 },
+    ]));
+}
+
+#[test]
+fn jit_sin_matches_the_interpreter_far_from_zero() {
+    let mut context = Context::new();
+    context.vars.push(Var::new("wave", VarKind::Float, 0.0));
+    context.reset();
+    let operators = vec![Operator::Sin(0, Param::Value(1.0e20))];
+
+    let mut jit = Dmo::new(context, operators);
+    jit.build_jit_fn().unwrap();
+    jit.run_jit_fn();
+    assert_eq!(jit.get_context().var_values[0], 1.0e20f32.sin());
+
+    jit.run_operators();
+    assert_eq!(jit.get_context().var_values[0], 1.0e20f32.sin());
+}
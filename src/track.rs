@@ -0,0 +1,175 @@
+/// A float value which changes with the time, given by keyframes. Operator
+/// arguments can refer to a track instead of a number, see `Param`.
+///
+/// In the YAML:
+///
+/// ```yaml
+/// tracks:
+///   - name: "swim"
+///     keys:
+///       - { t: 0.0, value: 0.0, ease: EaseInOut }
+///       - { t: 4.0, value: 40.0, ease: Step }
+///       - { t: 6.0, value: 0.0 }
+/// ```
+///
+/// Before the first key the value is the first value, after the last key it
+/// is the last value.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Track {
+    pub name: String,
+    /// In the order of their time
+    pub keys: Vec<Keyframe>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    /// Time in seconds
+    pub t: f32,
+    pub value: f32,
+    /// How the value changes from this key to the next one
+    #[serde(default)]
+    pub ease: Ease,
+}
+
+/// Interpolation between two keyframes.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Ease {
+    /// Keeps the value until the next key.
+    Step,
+    Linear,
+    /// Starts and stops slowly, `3u² - 2u³`.
+    Smoothstep,
+    /// Catmull-Rom spline, a smooth curve through the neighbouring keys.
+    Cubic,
+    /// Starts slowly, `u²`.
+    EaseIn,
+    /// Stops slowly.
+    EaseOut,
+    /// Starts and stops slowly, made of `EaseIn` and `EaseOut`.
+    EaseInOut,
+}
+
+/// An operator argument, a number, the current value of a track or of a
+/// variable (see `var::Var`). In the YAML it is a number, `{ track: "name" }`
+/// or `{ var: "name" }`.
+///
+/// `S` is how the track or the variable is referred to, see `dmo::Operator`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Param<S = u8> {
+    Value(f32),
+    Track(TrackArg<S>),
+    Var(VarArg<S>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TrackArg<S = u8> {
+    pub track: S,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct VarArg<S = u8> {
+    pub var: S,
+}
+
+impl Default for Ease {
+    fn default() -> Ease {
+        Ease::Linear
+    }
+}
+
+impl Ease {
+    /// The value between `v1` and `v2` at `u`, which goes from 0 to 1. `v0`
+    /// and `v3` are the values before and after, only `Cubic` uses them.
+    pub fn interpolate(&self, v0: f32, v1: f32, v2: f32, v3: f32, u: f32) -> f32 {
+        let lerp = |w: f32| v1 + (v2 - v1) * w;
+        match *self {
+            Ease::Step => v1,
+            Ease::Linear => lerp(u),
+            Ease::Smoothstep => lerp(u * u * (3.0 - 2.0 * u)),
+            Ease::Cubic => {
+                let u2 = u * u;
+                let u3 = u2 * u;
+                0.5 * ((2.0 * v1) +
+                       (v2 - v0) * u +
+                       (2.0 * v0 - 5.0 * v1 + 4.0 * v2 - v3) * u2 +
+                       (3.0 * v1 - v0 - 3.0 * v2 + v3) * u3)
+            },
+            Ease::EaseIn => lerp(u * u),
+            Ease::EaseOut => lerp(u * (2.0 - u)),
+            Ease::EaseInOut => {
+                if u < 0.5 {
+                    lerp(2.0 * u * u)
+                } else {
+                    lerp(1.0 - 2.0 * (1.0 - u) * (1.0 - u))
+                }
+            },
+        }
+    }
+}
+
+impl Track {
+    pub fn new(name: &str, keys: Vec<Keyframe>) -> Track {
+        Track {
+            name: String::from(name),
+            keys: keys,
+        }
+    }
+
+    /// The value at `time`. A track without keys is 0.
+    pub fn value_at(&self, time: f32) -> f32 {
+        let keys = &self.keys;
+        if keys.is_empty() {
+            return 0.0;
+        }
+        if time <= keys[0].t {
+            return keys[0].value;
+        }
+
+        // the key which starts the segment
+        let i = match keys.iter().rposition(|k| k.t <= time) {
+            Some(i) => i,
+            None => 0,
+        };
+        if i + 1 >= keys.len() {
+            return keys[i].value;
+        }
+
+        let (k1, k2) = (keys[i], keys[i + 1]);
+        let duration = k2.t - k1.t;
+        if duration <= 0.0 {
+            return k2.value;
+        }
+
+        let v0 = if i > 0 { keys[i - 1].value } else { k1.value };
+        let v3 = if i + 2 < keys.len() { keys[i + 2].value } else { k2.value };
+
+        k1.ease.interpolate(v0, k1.value, k2.value, v3, (time - k1.t) / duration)
+    }
+}
+
+impl Keyframe {
+    pub fn new(t: f32, value: f32, ease: Ease) -> Keyframe {
+        Keyframe {
+            t: t,
+            value: value,
+            ease: ease,
+        }
+    }
+}
+
+impl<S> Param<S> {
+    pub fn track(track: S) -> Param<S> {
+        Param::Track(TrackArg { track: track })
+    }
+
+    pub fn var(var: S) -> Param<S> {
+        Param::Var(VarArg { var: var })
+    }
+}
+
+impl From<f32> for Param {
+    fn from(value: f32) -> Param {
+        Param::Value(value)
+    }
+}
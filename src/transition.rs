@@ -0,0 +1,78 @@
+use color::Cell;
+
+/// The chars of the `Fade`, from dense to empty.
+pub const FADE_RAMP: &'static str = "@%#*+=-:. ";
+
+/// How the `Transition` operator blends the frame where the scene started
+/// with the new frame, as the progress goes from 0 to 1.
+///
+/// In the YAML, with the duration in seconds and the seed of `Dissolve`:
+///
+/// ```yaml
+/// operators:
+///   - Clear: 32
+///   - Draw: [ "fish", 0, 0, 8.0, Right, Wrap ]
+///   - Transition: [ Dissolve, 1.5, 7 ]
+///   - Print
+/// ```
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Blend {
+    /// The new frame comes in from the right edge.
+    WipeLeft,
+    /// The new frame comes in from the left edge.
+    WipeRight,
+    /// The cells change one by one, in an order given by the seed.
+    Dissolve,
+    /// The old chars thin out along `FADE_RAMP` to empty, then the new chars
+    /// come in the other way.
+    Fade,
+}
+
+impl Blend {
+    /// Blends `from` with `to`, both `width` cells per row. `from` is shown at
+    /// `progress` 0, `to` at 1.
+    pub fn blend(&self, from: &[Cell], to: &[Cell], width: u16, progress: f32, seed: u32) -> Vec<Cell> {
+        let w = width as usize;
+        let p = if progress < 0.0 { 0.0 } else if progress > 1.0 { 1.0 } else { progress };
+
+        from.iter().zip(to.iter()).enumerate().map(|(n, (&a, &b))| {
+            let col = if w > 0 { (n % w) as f32 } else { 0.0 };
+            match *self {
+                Blend::WipeLeft => if col >= (1.0 - p) * width as f32 { b } else { a },
+                Blend::WipeRight => if col < p * width as f32 { b } else { a },
+                Blend::Dissolve => if dissolve_threshold(seed, n) < p { b } else { a },
+                Blend::Fade => {
+                    if p < 0.5 {
+                        fade_cell(a, p * 2.0)
+                    } else {
+                        fade_cell(b, (1.0 - p) * 2.0)
+                    }
+                },
+            }
+        }).collect()
+    }
+}
+
+/// When the cell `idx` changes in a `Dissolve`, from 0 to 1. The same seed
+/// gives the same pattern.
+pub fn dissolve_threshold(seed: u32, idx: usize) -> f32 {
+    let mut h = seed ^ (idx as u32).wrapping_mul(0x9E37_79B9);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85EB_CA6B);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xC2B2_AE35);
+    h ^= h >> 16;
+    (h >> 8) as f32 / (1 << 24) as f32
+}
+
+/// The char of `FADE_RAMP` at `level`, 0 keeps the char and 1 is empty.
+/// Blank cells stay blank.
+fn fade_cell(cell: Cell, level: f32) -> Cell {
+    if cell.ch == ' ' || level <= 0.0 {
+        return cell;
+    }
+    let ramp: Vec<char> = FADE_RAMP.chars().collect();
+    let last = ramp.len() - 1;
+    let idx = (level * last as f32) as usize;
+    Cell::new(ramp[if idx > last { last } else { idx }], cell.style)
+}
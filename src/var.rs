@@ -0,0 +1,99 @@
+/// The JIT fn keeps the values in `Context.var_values`, an array in the
+/// context, so there can be at most this many variables.
+pub const MAX_VARS: usize = 32;
+
+/// A named value which the operators can change, and which keeps its value
+/// from one frame to the next, such as a counter or a position. Operator
+/// arguments can refer to a variable instead of a number, see `track::Param`.
+///
+/// In the YAML:
+///
+/// ```yaml
+/// vars:
+///   - name: "bounces"
+///     kind: Int
+///   - name: "y"
+///     value: 2.0
+/// ```
+///
+/// The variables start with their `value` when the context is reset. The
+/// operators `Set`, `Add`, `Mul` and `Sin` write to a variable, `Branch`
+/// compares one with a value.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Var {
+    pub name: String,
+    #[serde(default)]
+    pub kind: VarKind,
+    /// The value at the start
+    #[serde(default)]
+    pub value: f32,
+}
+
+/// Variables are floats. An `Int` holds whole numbers: the result of an
+/// operator is rounded toward zero to an `i32`, see `to_int()`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum VarKind {
+    Float,
+    Int,
+}
+
+/// How `Branch` compares a variable with a value. A comparison with NaN only
+/// holds for `NotEqual`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Compare {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Default for VarKind {
+    fn default() -> VarKind {
+        VarKind::Float
+    }
+}
+
+impl Var {
+    pub fn new(name: &str, kind: VarKind, value: f32) -> Var {
+        Var {
+            name: String::from(name),
+            kind: kind,
+            value: value,
+        }
+    }
+}
+
+impl VarKind {
+    /// The value as the variable keeps it.
+    pub fn store(&self, value: f32) -> f32 {
+        match *self {
+            VarKind::Float => value,
+            VarKind::Int => to_int(value),
+        }
+    }
+}
+
+impl Compare {
+    pub fn holds(&self, a: f32, b: f32) -> bool {
+        match *self {
+            Compare::Less => a < b,
+            Compare::LessOrEqual => a <= b,
+            Compare::Greater => a > b,
+            Compare::GreaterOrEqual => a >= b,
+            Compare::Equal => a == b,
+            Compare::NotEqual => a != b,
+        }
+    }
+}
+
+/// Rounds toward zero to an `i32`, as the JIT does with `cvttss2si`: values
+/// out of the range and NaN are `i32::MIN`.
+pub fn to_int(value: f32) -> f32 {
+    if value >= -2147483648.0 && value < 2147483648.0 {
+        value.trunc()
+    } else {
+        -2147483648.0
+    }
+}
@@ -0,0 +1,174 @@
+use std::error::Error;
+
+use serde_yaml::{self, Value};
+
+/// Serializes to YAML in the block layout of `serde_yaml::to_string()`.
+///
+/// `serde_yaml` leaves some strings plain which don't read back, such as
+/// `'.` or `*#`, so here a string is plain only when it can't be read as
+/// anything else, otherwise it is double-quoted. Strings with lines are
+/// literal blocks, as sprites are written by hand. The text is read back
+/// and compared with the value before it is returned.
+pub fn to_yml_string(value: &Value) -> Result<String, Box<Error>> {
+    let mut text = String::from("---");
+    emit_node(&mut text, value, -1);
+    text.push('\n');
+
+    let parsed: Value = match serde_yaml::from_str(&text) {
+        Ok(v) => v,
+        Err(e) => return Err(From::from(format!("the YAML doesn't parse: {}", e))),
+    };
+    if parsed != *value {
+        return Err(From::from("the YAML doesn't read back as the same value"));
+    }
+
+    Ok(text)
+}
+
+/// Writes `value`, nested `level` deep. A collection starts on a new line,
+/// a scalar right where the text is.
+fn emit_node(out: &mut String, value: &Value, level: i32) {
+    match *value {
+        Value::Sequence(ref items) if !items.is_empty() => {
+            for item in items.iter() {
+                new_line(out, level + 1);
+                out.push('-');
+                emit_child(out, item, level + 1);
+            }
+        },
+        Value::Mapping(ref entries) if !entries.is_empty() => {
+            for (key, value) in entries.iter() {
+                new_line(out, level + 1);
+                emit_scalar(out, key);
+                out.push(':');
+                emit_child(out, value, level + 1);
+            }
+        },
+        _ => emit_scalar(out, value),
+    }
+}
+
+fn emit_child(out: &mut String, value: &Value, level: i32) {
+    match *value {
+        Value::Sequence(ref items) if !items.is_empty() => emit_node(out, value, level),
+        Value::Mapping(ref entries) if !entries.is_empty() => emit_node(out, value, level),
+        Value::String(ref s) if is_block(s) => {
+            out.push(' ');
+            emit_block(out, s, level);
+        },
+        _ => {
+            out.push(' ');
+            emit_scalar(out, value);
+        },
+    }
+}
+
+/// Writes a string with lines as a literal block, indented under the item or
+/// key at `level`. `|+` keeps the newline at the end, `|-` is for a string
+/// without one.
+fn emit_block(out: &mut String, s: &str, level: i32) {
+    let mut lines: Vec<&str> = s.split('\n').collect();
+    out.push('|');
+    // the indentation is found from the first line with chars, unless it is
+    // given
+    if lines.iter().find(|l| !l.is_empty()).map_or(false, |l| l.starts_with(' ')) {
+        out.push('2');
+    }
+    if s.ends_with('\n') {
+        out.push('+');
+        lines.pop();
+    } else {
+        out.push('-');
+    }
+
+    for line in lines.iter() {
+        if line.is_empty() {
+            out.push('\n');
+        } else {
+            new_line(out, level + 1);
+            out.push_str(line);
+        }
+    }
+}
+
+fn new_line(out: &mut String, level: i32) {
+    out.push('\n');
+    for _ in 0 .. level {
+        out.push_str("  ");
+    }
+}
+
+fn emit_scalar(out: &mut String, value: &Value) {
+    match *value {
+        Value::Null => out.push('~'),
+        Value::Bool(b) => out.push_str(&b.to_string()),
+        Value::I64(n) => out.push_str(&n.to_string()),
+        Value::F64(f) => {
+            // so that it doesn't read back as an integer
+            let text = f.to_string();
+            out.push_str(&text);
+            if text.parse::<i64>().is_ok() {
+                out.push_str(".0");
+            }
+        },
+        Value::String(ref s) => emit_str(out, s),
+        Value::Sequence(_) => out.push_str("[]"),
+        Value::Mapping(_) => out.push_str("{}"),
+    }
+}
+
+fn emit_str(out: &mut String, s: &str) {
+    if is_plain(s) {
+        out.push_str(s);
+    } else {
+        out.push_str(&quote(s));
+    }
+}
+
+/// Whether the string has lines and reads back as itself in a literal
+/// block. Other strings with lines are double-quoted.
+pub fn is_block(s: &str) -> bool {
+    let no_breaks = s.chars().all(|ch| {
+        ch == '\n' || !(ch < ' ' || ch == '\u{7f}' || ch == '\u{85}' || ch == '\u{2028}' ||
+                        ch == '\u{2029}' || ch == '\u{feff}')
+    });
+    s.contains('\n') && no_breaks && s.split('\n').any(|l| !l.is_empty())
+}
+
+/// Whether the string reads back as itself without quotes: it starts with a
+/// letter, has no chars with a meaning in YAML and is not a bool, a null or
+/// a number.
+pub fn is_plain(s: &str) -> bool {
+    let starts_with_letter = match s.chars().next() {
+        Some(ch) => ch.is_ascii_alphabetic(),
+        None => false,
+    };
+    let plain_chars = s.chars().all(|ch| ch.is_ascii_alphanumeric() || " _.()/+-".contains(ch));
+
+    let lower = s.to_lowercase();
+    let keyword = ["true", "false", "null", "yes", "no", "on", "off"].contains(&lower.as_str());
+
+    starts_with_letter && plain_chars && !s.ends_with(' ') && !keyword && s.parse::<f64>().is_err()
+}
+
+/// The string in double quotes, with escapes for the quote, the backslash
+/// and the chars which YAML doesn't keep as they are.
+pub fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            '\u{85}' | '\u{a0}' | '\u{2028}' | '\u{2029}' | '\u{feff}' => {
+                quoted.push_str(&format!("\\u{:04x}", ch as u32))
+            },
+            _ if ch < ' ' || ch == '\u{7f}' => quoted.push_str(&format!("\\x{:02x}", ch as u32)),
+            _ => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}